@@ -1,31 +1,33 @@
 //! Generational arena implementation.
 
-use std::{
+use alloc::vec::Vec;
+use core::{
     fmt::{Debug, Formatter},
+    marker::PhantomData,
     mem::ManuallyDrop,
     ops::{Deref, DerefMut, Index, IndexMut},
 };
 
-use crate::Key;
+use crate::{Key, KeyType, SecondaryMap};
 
 /// Internal slot data: either occupied with a value or pointing to the next.
-union Container<T> {
+pub(crate) union Container<T> {
     /// Stored data in the container.
-    data: ManuallyDrop<T>,
+    pub(crate) data: ManuallyDrop<T>,
     /// Index of the next free slot.
-    next: usize,
+    pub(crate) next: usize,
 }
 
 /// Slot that can store data and the current version of it.
-struct Slot<T> {
+pub(crate) struct Slot<T> {
     /// Data stored in the slot.
-    container: Container<T>,
+    pub(crate) container: Container<T>,
     /// Current slot version. Even is empty, odd is occupied.
-    version: usize,
+    pub(crate) version: usize,
 }
 
 /// Safe access to the slot data.
-enum Access<'a, T: 'a> {
+pub(crate) enum Access<'a, T: 'a> {
     /// Occupied variant with a reference to the stored data.
     Occupied(&'a T),
     /// Empty variant with a reference to next free slot index.
@@ -52,7 +54,7 @@ impl<T> Slot<T> {
 
 impl<T> Drop for Slot<T> {
     fn drop(&mut self) {
-        if std::mem::needs_drop::<T>() && !self.empty() {
+        if core::mem::needs_drop::<T>() && !self.empty() {
             unsafe {
                 ManuallyDrop::drop(&mut self.container.data);
             }
@@ -103,7 +105,7 @@ impl<T: Clone> Clone for Slot<T> {
 }
 
 impl<T: Debug> Debug for Slot<T> {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         match self.get() {
             Access::Occupied(data) => data.fmt(f),
             Access::Empty(next) => write!(f, "next {}", next),
@@ -112,29 +114,52 @@ impl<T: Debug> Debug for Slot<T> {
 }
 
 /// Slotmap arena structure.
-pub struct Arena<T> {
+///
+/// Elements are addressed by keys of type `K`, which defaults to the untyped
+/// [`Key`]. Use [`new_key_type!`](crate::new_key_type) to declare distinct key
+/// types so keys of one arena cannot be used on another.
+pub struct Arena<T, K = Key> {
     /// Storage for the slots.
-    slots: Vec<Slot<T>>,
+    pub(crate) slots: Vec<Slot<T>>,
     /// Index of the next free slot.
-    head: usize,
+    pub(crate) head: usize,
     /// Number of occupied slots.
-    count: usize,
+    pub(crate) count: usize,
+    /// Version of vacant slots grown past the end. Even, and above the
+    /// version of any slot released by compaction, so stale keys never match.
+    pub(crate) floor: usize,
+    /// Marker for the key type.
+    pub(crate) _marker: PhantomData<fn(K) -> K>,
 }
 
 impl<T> Arena<T> {
     /// Create a new arena with the given capacity.
     pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_key(capacity)
+    }
+
+    /// Create a new empty arena.
+    pub fn new() -> Self {
+        Self::with_capacity(0)
+    }
+}
+
+impl<T, K: KeyType> Arena<T, K> {
+    /// Create a new arena with the given capacity and a custom key type.
+    pub fn with_capacity_and_key(capacity: usize) -> Self {
         let slots = Vec::with_capacity(capacity);
         Self {
             slots,
             head: 0,
             count: 0,
+            floor: 0,
+            _marker: PhantomData,
         }
     }
 
-    /// Create a new empty arena.
-    pub fn new() -> Self {
-        Self::with_capacity(0)
+    /// Create a new empty arena with a custom key type.
+    pub fn with_key() -> Self {
+        Self::with_capacity_and_key(0)
     }
 
     /// Returns the number of elements in the arena.
@@ -161,19 +186,21 @@ impl<T> Arena<T> {
     pub fn try_reserve(
         &mut self,
         additional: usize,
-    ) -> Result<(), std::collections::TryReserveError> {
+    ) -> Result<(), alloc::collections::TryReserveError> {
         self.slots.try_reserve(additional)
     }
 
     /// Returns true if the arena contains the given key.
-    pub fn contains_key(&self, key: Key) -> bool {
+    pub fn contains_key(&self, key: K) -> bool {
+        let key = key.key();
         self.slots
             .get(key.index())
             .is_some_and(|slot| slot.version == key.version())
     }
 
     /// Returns a reference to the value corresponding to the key.
-    pub fn get(&self, key: Key) -> Option<&T> {
+    pub fn get(&self, key: K) -> Option<&T> {
+        let key = key.key();
         self.slots
             .get(key.index())
             .filter(|s| s.version == key.version())
@@ -181,15 +208,35 @@ impl<T> Arena<T> {
     }
 
     /// Returns a mutable reference to the value corresponding to the key.
-    pub fn get_mut(&mut self, key: Key) -> Option<&mut T> {
+    pub fn get_mut(&mut self, key: K) -> Option<&mut T> {
+        let key = key.key();
         self.slots
             .get_mut(key.index())
             .filter(|s| s.version == key.version())
             .map(|s| unsafe { s.container.data.deref_mut() })
     }
 
+    /// Returns mutable references to the values of several distinct keys at once.
+    ///
+    /// Returns `None` if any key is invalid or if the same element is requested
+    /// more than once.
+    pub fn get_disjoint_mut<const N: usize>(&mut self, keys: [K; N]) -> Option<[&mut T; N]> {
+        for (i, key) in keys.iter().enumerate() {
+            if !self.contains_key(*key) {
+                return None;
+            }
+            let index = key.key().index();
+            if keys[..i].iter().any(|other| other.key().index() == index) {
+                return None;
+            }
+        }
+        let slots = self.slots.as_mut_ptr();
+        // Safety: all keys are valid and point to pairwise distinct slots.
+        Some(keys.map(|key| unsafe { (*slots.add(key.key().index())).container.data.deref_mut() }))
+    }
+
     /// Insert a value into the arena, returning a key to access it.
-    pub fn insert(&mut self, value: T) -> Key {
+    pub fn insert(&mut self, value: T) -> K {
         let index = if self.head < self.slots.len() {
             let slot = &mut self.slots[self.head];
             let index = self.head;
@@ -205,20 +252,21 @@ impl<T> Arena<T> {
                 container: Container {
                     data: ManuallyDrop::new(value),
                 },
-                version: 1,
+                version: self.floor + 1,
             });
             self.head = self.slots.len();
             index
         };
         self.count += 1;
-        Key {
+        K::from_key(Key {
             index,
             version: self.slots[index].version,
-        }
+        })
     }
 
     /// Remove the value associated with the given key, returning it if it exists.
-    pub fn remove(&mut self, key: Key) -> Option<T> {
+    pub fn remove(&mut self, key: K) -> Option<T> {
+        let key = key.key();
         let slot = self.slots.get_mut(key.index())?;
         if slot.version != key.version() {
             return None;
@@ -232,41 +280,42 @@ impl<T> Arena<T> {
     }
 
     /// Insert a value created from a closure that receives the key it will be stored under.
-    pub fn insert_with_key(&mut self, f: impl FnOnce(Key) -> T) -> Key {
+    pub fn insert_with_key(&mut self, f: impl FnOnce(K) -> T) -> K {
         let (index, version) = if self.head < self.slots.len() {
             let slot = &self.slots[self.head];
             (self.head, slot.version + 1)
         } else {
-            (self.slots.len(), 1)
+            (self.slots.len(), self.floor + 1)
         };
-        let key = Key { index, version };
+        let key = K::from_key(Key { index, version });
         self.insert(f(key))
     }
 }
 
-impl<T> Index<Key> for Arena<T> {
+impl<T, K: KeyType> Index<K> for Arena<T, K> {
     type Output = T;
 
-    fn index(&self, key: Key) -> &Self::Output {
+    fn index(&self, key: K) -> &Self::Output {
         self.get(key).expect("invalid arena key")
     }
 }
 
-impl<T> IndexMut<Key> for Arena<T> {
-    fn index_mut(&mut self, key: Key) -> &mut Self::Output {
+impl<T, K: KeyType> IndexMut<K> for Arena<T, K> {
+    fn index_mut(&mut self, key: K) -> &mut Self::Output {
         self.get_mut(key).expect("invalid arena key")
     }
 }
 
 /// Iterator over shared references to arena elements.
-pub struct Iter<'a, T> {
-    slots: std::slice::Iter<'a, Slot<T>>,
+pub struct Iter<'a, T, K = Key> {
+    slots: core::slice::Iter<'a, Slot<T>>,
     index: usize,
     remaining: usize,
+    _marker: PhantomData<fn(K) -> K>,
 }
 
-impl<'a, T> Iterator for Iter<'a, T> {
-    type Item = (Key, &'a T);
+impl<'a, T, K: KeyType> Iterator for Iter<'a, T, K> {
+    type Item = (K, &'a T);
 
     fn next(&mut self) -> Option<Self::Item> {
         loop {
@@ -277,10 +326,10 @@ impl<'a, T> Iterator for Iter<'a, T> {
                 self.remaining -= 1;
                 let data = unsafe { slot.container.data.deref() };
                 return Some((
-                    Key {
+                    K::from_key(Key {
                         index,
                         version: slot.version,
-                    },
+                    }),
                     data,
                 ));
             }
@@ -292,17 +341,18 @@ impl<'a, T> Iterator for Iter<'a, T> {
     }
 }
 
-impl<T> ExactSizeIterator for Iter<'_, T> {}
+impl<T, K: KeyType> ExactSizeIterator for Iter<'_, T, K> {}
 
 /// Iterator over mutable references to arena elements.
-pub struct IterMut<'a, T> {
-    slots: std::slice::IterMut<'a, Slot<T>>,
+pub struct IterMut<'a, T, K = Key> {
+    slots: core::slice::IterMut<'a, Slot<T>>,
     index: usize,
     remaining: usize,
+    _marker: PhantomData<fn(K) -> K>,
 }
 
-impl<'a, T> Iterator for IterMut<'a, T> {
-    type Item = (Key, &'a mut T);
+impl<'a, T, K: KeyType> Iterator for IterMut<'a, T, K> {
+    type Item = (K, &'a mut T);
 
     fn next(&mut self) -> Option<Self::Item> {
         loop {
@@ -313,10 +363,10 @@ impl<'a, T> Iterator for IterMut<'a, T> {
                 self.remaining -= 1;
                 let data = unsafe { slot.container.data.deref_mut() };
                 return Some((
-                    Key {
+                    K::from_key(Key {
                         index,
                         version: slot.version,
-                    },
+                    }),
                     data,
                 ));
             }
@@ -328,17 +378,18 @@ impl<'a, T> Iterator for IterMut<'a, T> {
     }
 }
 
-impl<T> ExactSizeIterator for IterMut<'_, T> {}
+impl<T, K: KeyType> ExactSizeIterator for IterMut<'_, T, K> {}
 
 /// Owning iterator over arena elements.
-pub struct IntoIter<T> {
-    slots: std::vec::IntoIter<Slot<T>>,
+pub struct IntoIter<T, K = Key> {
+    slots: alloc::vec::IntoIter<Slot<T>>,
     index: usize,
     remaining: usize,
+    _marker: PhantomData<fn(K) -> K>,
 }
 
-impl<T> Iterator for IntoIter<T> {
-    type Item = (Key, T);
+impl<T, K: KeyType> Iterator for IntoIter<T, K> {
+    type Item = (K, T);
 
     fn next(&mut self) -> Option<Self::Item> {
         loop {
@@ -350,10 +401,10 @@ impl<T> Iterator for IntoIter<T> {
                 let data = unsafe { ManuallyDrop::take(&mut slot.container.data) };
                 slot.version += 1; // mark empty so Drop doesn't double-free
                 return Some((
-                    Key {
+                    K::from_key(Key {
                         index,
                         version: slot.version - 1,
-                    },
+                    }),
                     data,
                 ));
             }
@@ -365,9 +416,9 @@ impl<T> Iterator for IntoIter<T> {
     }
 }
 
-impl<T> ExactSizeIterator for IntoIter<T> {}
+impl<T, K: KeyType> ExactSizeIterator for IntoIter<T, K> {}
 
-impl<T> Arena<T> {
+impl<T, K: KeyType> Arena<T, K> {
     /// Remove all elements from the arena, keeping the allocated memory.
     /// Old keys will be invalid after this operation.
     pub fn clear(&mut self) {
@@ -377,20 +428,22 @@ impl<T> Arena<T> {
     }
 
     /// Returns an iterator over shared references to the arena elements.
-    pub fn iter(&self) -> Iter<'_, T> {
+    pub fn iter(&self) -> Iter<'_, T, K> {
         Iter {
             slots: self.slots.iter(),
             index: 0,
             remaining: self.count,
+            _marker: PhantomData,
         }
     }
 
     /// Returns an iterator over mutable references to the arena elements.
-    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+    pub fn iter_mut(&mut self) -> IterMut<'_, T, K> {
         IterMut {
             slots: self.slots.iter_mut(),
             index: 0,
             remaining: self.count,
+            _marker: PhantomData,
         }
     }
 
@@ -405,21 +458,21 @@ impl<T> Arena<T> {
     }
 
     /// Returns an iterator over the keys in the arena.
-    pub fn keys(&self) -> impl Iterator<Item = Key> {
+    pub fn keys(&self) -> impl Iterator<Item = K> {
         self.iter().map(|(k, _)| k)
     }
 
     /// Retains only the elements specified by the predicate.
-    pub fn retain(&mut self, mut f: impl FnMut(Key, &mut T) -> bool) {
+    pub fn retain(&mut self, mut f: impl FnMut(K, &mut T) -> bool) {
         for i in 0..self.slots.len() {
             let slot = &mut self.slots[i];
             if slot.empty() {
                 continue;
             }
-            let key = Key {
+            let key = K::from_key(Key {
                 index: i,
                 version: slot.version,
-            };
+            });
             if !f(key, unsafe { &mut slot.container.data }) {
                 unsafe { ManuallyDrop::drop(&mut slot.container.data) };
                 slot.container = Container { next: self.head };
@@ -431,49 +484,52 @@ impl<T> Arena<T> {
     }
 }
 
-impl<T> IntoIterator for Arena<T> {
-    type Item = (Key, T);
-    type IntoIter = IntoIter<T>;
+impl<T, K: KeyType> IntoIterator for Arena<T, K> {
+    type Item = (K, T);
+    type IntoIter = IntoIter<T, K>;
 
     fn into_iter(self) -> Self::IntoIter {
         IntoIter {
             slots: self.slots.into_iter(),
             index: 0,
             remaining: self.count,
+            _marker: PhantomData,
         }
     }
 }
 
-impl<'a, T> IntoIterator for &'a Arena<T> {
-    type Item = (Key, &'a T);
-    type IntoIter = Iter<'a, T>;
+impl<'a, T, K: KeyType> IntoIterator for &'a Arena<T, K> {
+    type Item = (K, &'a T);
+    type IntoIter = Iter<'a, T, K>;
 
     fn into_iter(self) -> Self::IntoIter {
         self.iter()
     }
 }
 
-impl<'a, T> IntoIterator for &'a mut Arena<T> {
-    type Item = (Key, &'a mut T);
-    type IntoIter = IterMut<'a, T>;
+impl<'a, T, K: KeyType> IntoIterator for &'a mut Arena<T, K> {
+    type Item = (K, &'a mut T);
+    type IntoIter = IterMut<'a, T, K>;
 
     fn into_iter(self) -> Self::IntoIter {
         self.iter_mut()
     }
 }
 
-impl<T> Default for Arena<T> {
+impl<T, K: KeyType> Default for Arena<T, K> {
     fn default() -> Self {
-        Self::new()
+        Self::with_key()
     }
 }
 
-impl<T: Clone> Clone for Arena<T> {
+impl<T: Clone, K> Clone for Arena<T, K> {
     fn clone(&self) -> Self {
         Self {
             slots: self.slots.clone(),
             head: self.head,
             count: self.count,
+            floor: self.floor,
+            _marker: PhantomData,
         }
     }
 
@@ -481,10 +537,11 @@ impl<T: Clone> Clone for Arena<T> {
         self.slots.clone_from(&source.slots);
         self.head = source.head;
         self.count = source.count;
+        self.floor = source.floor;
     }
 }
 
-impl<T: PartialEq> PartialEq for Arena<T> {
+impl<T: PartialEq, K: KeyType> PartialEq for Arena<T, K> {
     fn eq(&self, other: &Self) -> bool {
         if self.count != other.count {
             return false;
@@ -493,16 +550,16 @@ impl<T: PartialEq> PartialEq for Arena<T> {
     }
 }
 
-impl<T: Eq> Eq for Arena<T> {}
+impl<T: Eq, K: KeyType> Eq for Arena<T, K> {}
 
 /// Draining iterator that removes all elements from the arena.
-pub struct Drain<'a, T> {
-    arena: &'a mut Arena<T>,
+pub struct Drain<'a, T, K: KeyType = Key> {
+    arena: &'a mut Arena<T, K>,
     index: usize,
 }
 
-impl<T> Iterator for Drain<'_, T> {
-    type Item = (Key, T);
+impl<T, K: KeyType> Iterator for Drain<'_, T, K> {
+    type Item = (K, T);
 
     fn next(&mut self) -> Option<Self::Item> {
         loop {
@@ -515,10 +572,10 @@ impl<T> Iterator for Drain<'_, T> {
             if slot.empty() {
                 continue;
             }
-            let key = Key {
+            let key = K::from_key(Key {
                 index: i,
                 version: slot.version,
-            };
+            });
             let value = unsafe { ManuallyDrop::take(&mut slot.container.data) };
             slot.container = Container {
                 next: self.arena.head,
@@ -531,17 +588,17 @@ impl<T> Iterator for Drain<'_, T> {
     }
 }
 
-impl<T> Drop for Drain<'_, T> {
+impl<T, K: KeyType> Drop for Drain<'_, T, K> {
     fn drop(&mut self) {
         // Exhaust remaining elements.
         self.for_each(drop);
     }
 }
 
-impl<T> Arena<T> {
+impl<T, K: KeyType> Arena<T, K> {
     /// Drains all elements from the arena, returning them as an iterator.
     /// The arena keeps its allocated memory for reuse.
-    pub fn drain(&mut self) -> Drain<'_, T> {
+    pub fn drain(&mut self) -> Drain<'_, T, K> {
         Drain {
             arena: self,
             index: 0,
@@ -549,7 +606,327 @@ impl<T> Arena<T> {
     }
 }
 
-impl<T> Extend<T> for Arena<T> {
+impl<T, K: KeyType> Arena<T, K> {
+    /// Move all elements into a dense prefix of the slot storage and release
+    /// the remaining memory.
+    ///
+    /// Returns a map from every old key to the key of the same element after
+    /// compaction. Elements that did not move keep their key. Keys that are not
+    /// present in the returned map never match an element again: slots grown
+    /// back past the prefix start above every version released here.
+    pub fn compact(&mut self) -> SecondaryMap<K, K> {
+        let mut remap = SecondaryMap::with_capacity_and_key(self.slots.len());
+        let mut dst = 0;
+        for src in 0..self.slots.len() {
+            if self.slots[src].empty() {
+                continue;
+            }
+            let old_key = Key {
+                index: src,
+                version: self.slots[src].version,
+            };
+            if src != dst {
+                // Every slot before src is either occupied by an already
+                // compacted element or empty, so dst is free.
+                let slot = &mut self.slots[src];
+                let value = unsafe { ManuallyDrop::take(&mut slot.container.data) };
+                slot.container = Container { next: 0 };
+                slot.version += 1;
+
+                let slot = &mut self.slots[dst];
+                slot.container = Container {
+                    data: ManuallyDrop::new(value),
+                };
+                slot.version += 1;
+            }
+            let new_key = Key {
+                index: dst,
+                version: self.slots[dst].version,
+            };
+            remap.insert(K::from_key(old_key), K::from_key(new_key));
+            dst += 1;
+        }
+        // Released slots may be grown back later, so they must start past
+        // every version they had. Vacant versions are even.
+        let released = self.slots[dst..].iter().map(|slot| slot.version);
+        self.floor = released.fold(self.floor, usize::max);
+        // All slots past the prefix are empty, so truncating drops no data.
+        self.slots.truncate(dst);
+        self.slots.shrink_to_fit();
+        self.head = self.slots.len();
+        remap
+    }
+}
+
+/// Marker stored as the next free index of a reserved slot.
+///
+/// Reserved slots are vacant but detached from the free list. No free list
+/// link can hold this value, since links never exceed the slot count.
+const RESERVED: usize = usize::MAX;
+
+/// Guard for a slot reserved with [`Arena::reserve_slot`].
+///
+/// The key is known before the value is built, which allows storing values
+/// that refer to their own key. The reservation is released if the guard is
+/// dropped without being filled. A released key never becomes valid.
+pub struct ReservedKey<'a, T, K: KeyType = Key> {
+    arena: &'a mut Arena<T, K>,
+    key: K,
+    filled: bool,
+}
+
+impl<T, K: KeyType> ReservedKey<'_, T, K> {
+    /// Returns the key the value will be stored under.
+    pub fn key(&self) -> K {
+        self.key
+    }
+
+    /// Store the value in the reserved slot, returning its key.
+    pub fn fill(mut self, value: T) -> K {
+        self.arena.fill_reserved(self.key.key(), value);
+        self.filled = true;
+        self.key
+    }
+}
+
+impl<T, K: KeyType> Drop for ReservedKey<'_, T, K> {
+    fn drop(&mut self) {
+        if !self.filled {
+            self.arena.release_reserved(self.key.key());
+        }
+    }
+}
+
+/// Guard for a batch of slots reserved with [`Arena::reserve_slots`].
+///
+/// The batch is filled all at once or not at all. Every reservation is
+/// released if the guard is dropped without being filled.
+pub struct ReservedKeys<'a, T, K: KeyType = Key> {
+    arena: &'a mut Arena<T, K>,
+    keys: Vec<K>,
+    filled: bool,
+}
+
+impl<T, K: KeyType> ReservedKeys<'_, T, K> {
+    /// Returns the keys the values will be stored under, in fill order.
+    pub fn keys(&self) -> &[K] {
+        &self.keys
+    }
+
+    /// Store one value per reserved slot, returning their keys.
+    ///
+    /// If the number of values does not match the number of reserved slots,
+    /// nothing is stored, the reservations are released and the values are
+    /// handed back.
+    pub fn fill_many(mut self, values: impl IntoIterator<Item = T>) -> Result<Vec<K>, Vec<T>> {
+        let values: Vec<T> = values.into_iter().collect();
+        if values.len() != self.keys.len() {
+            return Err(values);
+        }
+        for (key, value) in self.keys.iter().zip(values) {
+            self.arena.fill_reserved(key.key(), value);
+        }
+        self.filled = true;
+        Ok(core::mem::take(&mut self.keys))
+    }
+}
+
+impl<T, K: KeyType> Drop for ReservedKeys<'_, T, K> {
+    fn drop(&mut self) {
+        if !self.filled {
+            for key in self.keys.iter().rev() {
+                self.arena.release_reserved(key.key());
+            }
+        }
+    }
+}
+
+impl<T, K: KeyType> Arena<T, K> {
+    /// Reserve a slot, returning a guard that knows the key of the future value.
+    pub fn reserve_slot(&mut self) -> ReservedKey<'_, T, K> {
+        let key = K::from_key(self.reserve_index());
+        ReservedKey {
+            arena: self,
+            key,
+            filled: false,
+        }
+    }
+
+    /// Reserve `count` slots, returning a guard that fills them all at once.
+    pub fn reserve_slots(&mut self, count: usize) -> ReservedKeys<'_, T, K> {
+        let keys = (0..count)
+            .map(|_| K::from_key(self.reserve_index()))
+            .collect();
+        ReservedKeys {
+            arena: self,
+            keys,
+            filled: false,
+        }
+    }
+
+    /// Detach a vacant slot from the free list and return its future key.
+    fn reserve_index(&mut self) -> Key {
+        if self.head < self.slots.len() {
+            let index = self.head;
+            let slot = &mut self.slots[index];
+            self.head = unsafe { slot.container.next };
+            slot.container = Container { next: RESERVED };
+            Key {
+                index,
+                version: slot.version + 1,
+            }
+        } else {
+            let index = self.slots.len();
+            self.slots.push(Slot {
+                container: Container { next: RESERVED },
+                version: self.floor,
+            });
+            self.head = self.slots.len();
+            Key {
+                index,
+                version: self.floor + 1,
+            }
+        }
+    }
+
+    /// Store a value in a slot detached by `reserve_index`.
+    fn fill_reserved(&mut self, key: Key, value: T) {
+        let slot = &mut self.slots[key.index];
+        debug_assert!(slot.empty() && unsafe { slot.container.next } == RESERVED);
+        debug_assert_eq!(slot.version + 1, key.version);
+        slot.container = Container {
+            data: ManuallyDrop::new(value),
+        };
+        slot.version += 1;
+        self.count += 1;
+    }
+
+    /// Return a slot detached by `reserve_index` to the free list.
+    fn release_reserved(&mut self, key: Key) {
+        let slot = &mut self.slots[key.index];
+        debug_assert!(slot.empty() && unsafe { slot.container.next } == RESERVED);
+        slot.container = Container { next: self.head };
+        // Skip the reserved version so the released key never becomes valid.
+        slot.version += 2;
+        self.head = key.index;
+    }
+}
+
+/// View into a single arena element, which may be live or not.
+pub enum Entry<'a, T, K: KeyType = Key> {
+    /// The key refers to a live element.
+    Occupied(OccupiedEntry<'a, T, K>),
+    /// The key is stale or was never handed out by this arena.
+    Vacant(VacantEntry<'a, T, K>),
+}
+
+/// View into a live arena element.
+pub struct OccupiedEntry<'a, T, K: KeyType = Key> {
+    arena: &'a mut Arena<T, K>,
+    key: K,
+}
+
+/// View into the arena for a key that does not refer to a live element.
+///
+/// A stale key cannot be brought back to life. Inserting through a vacant
+/// entry stores the value under a fresh key.
+pub struct VacantEntry<'a, T, K: KeyType = Key> {
+    arena: &'a mut Arena<T, K>,
+    key: K,
+}
+
+impl<'a, T, K: KeyType> Entry<'a, T, K> {
+    /// Returns the key this entry was requested with.
+    pub fn key(&self) -> K {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+
+    /// Returns the live element, inserting the given value under a fresh key if
+    /// the entry is vacant.
+    pub fn or_insert(self, value: T) -> (K, &'a mut T) {
+        self.or_insert_with(|| value)
+    }
+
+    /// Returns the live element, inserting the value produced by `f` under a
+    /// fresh key if the entry is vacant.
+    pub fn or_insert_with(self, f: impl FnOnce() -> T) -> (K, &'a mut T) {
+        match self {
+            Entry::Occupied(entry) => (entry.key(), entry.into_mut()),
+            Entry::Vacant(entry) => entry.insert(f()),
+        }
+    }
+
+    /// Applies `f` to the element if the entry is occupied.
+    pub fn and_modify(mut self, f: impl FnOnce(&mut T)) -> Self {
+        if let Entry::Occupied(entry) = &mut self {
+            f(entry.get_mut());
+        }
+        self
+    }
+}
+
+impl<'a, T, K: KeyType> OccupiedEntry<'a, T, K> {
+    /// Returns the key of the element.
+    pub fn key(&self) -> K {
+        self.key
+    }
+
+    /// Returns a reference to the element.
+    pub fn get(&self) -> &T {
+        &self.arena[self.key]
+    }
+
+    /// Returns a mutable reference to the element.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.arena[self.key]
+    }
+
+    /// Converts the entry into a mutable reference bound to the arena lifetime.
+    pub fn into_mut(self) -> &'a mut T {
+        &mut self.arena[self.key]
+    }
+
+    /// Replaces the element, returning the previous value.
+    pub fn insert(&mut self, value: T) -> T {
+        core::mem::replace(self.get_mut(), value)
+    }
+
+    /// Removes the element from the arena, returning it.
+    pub fn remove(self) -> T {
+        self.arena
+            .remove(self.key)
+            .expect("occupied entry refers to a live element")
+    }
+}
+
+impl<'a, T, K: KeyType> VacantEntry<'a, T, K> {
+    /// Returns the key this entry was requested with.
+    pub fn key(&self) -> K {
+        self.key
+    }
+
+    /// Inserts a value under a fresh key, returning the key and a reference to it.
+    pub fn insert(self, value: T) -> (K, &'a mut T) {
+        let key = self.arena.insert(value);
+        (key, &mut self.arena[key])
+    }
+}
+
+impl<T, K: KeyType> Arena<T, K> {
+    /// Returns the entry for the given key.
+    pub fn entry(&mut self, key: K) -> Entry<'_, T, K> {
+        if self.contains_key(key) {
+            Entry::Occupied(OccupiedEntry { arena: self, key })
+        } else {
+            Entry::Vacant(VacantEntry { arena: self, key })
+        }
+    }
+}
+
+impl<T, K: KeyType> Extend<T> for Arena<T, K> {
     fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
         for value in iter {
             self.insert(value);
@@ -557,16 +934,16 @@ impl<T> Extend<T> for Arena<T> {
     }
 }
 
-impl<T: Debug> Debug for Arena<T> {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+impl<T: Debug, K: KeyType> Debug for Arena<T, K> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         f.debug_map().entries(self.iter()).finish()
     }
 }
 
-impl<T> FromIterator<T> for Arena<T> {
+impl<T, K: KeyType> FromIterator<T> for Arena<T, K> {
     fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
         let iter = iter.into_iter();
-        let mut arena = Self::with_capacity(iter.size_hint().0);
+        let mut arena = Self::with_capacity_and_key(iter.size_hint().0);
         arena.extend(iter);
         arena
     }
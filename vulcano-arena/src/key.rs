@@ -1,5 +1,7 @@
 //! Key type for the arena.
 
+use core::{fmt::Debug, hash::Hash};
+
 /// A key with index and version for arena access.
 ///
 /// Keys are stable references to slots in the arena. Even after deletion
@@ -24,3 +26,66 @@ impl Key {
         self.version
     }
 }
+
+/// Trait implemented by key types usable to index an arena.
+///
+/// Implemented by [`Key`] itself and by every type declared with
+/// [`new_key_type!`](crate::new_key_type).
+pub trait KeyType: Copy + Eq + Hash + Debug {
+    /// Wrap an untyped key.
+    fn from_key(key: Key) -> Self;
+
+    /// Return the underlying untyped key.
+    fn key(self) -> Key;
+}
+
+impl KeyType for Key {
+    fn from_key(key: Key) -> Self {
+        key
+    }
+
+    fn key(self) -> Key {
+        self
+    }
+}
+
+/// Declare one or more distinct key types.
+///
+/// Each declared type wraps a [`Key`] and implements [`KeyType`], so an
+/// `Arena<T, MyKey>` only accepts and returns `MyKey` values. Attributes are
+/// forwarded, so extra derives such as serde's can be added per type.
+///
+/// ```
+/// use vulcano_arena::{Arena, new_key_type};
+///
+/// new_key_type! {
+///     /// Key for nodes.
+///     pub struct NodeKey;
+///     /// Key for edges.
+///     pub struct EdgeKey;
+/// }
+///
+/// let mut nodes: Arena<&str, NodeKey> = Arena::with_key();
+/// let node: NodeKey = nodes.insert("a");
+/// assert_eq!(nodes[node], "a");
+/// ```
+#[macro_export]
+macro_rules! new_key_type {
+    ( $( $(#[$outer:meta])* $vis:vis struct $name:ident; )* ) => {
+        $(
+            $(#[$outer])*
+            #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+            $vis struct $name($crate::Key);
+
+            impl $crate::KeyType for $name {
+                fn from_key(key: $crate::Key) -> Self {
+                    Self(key)
+                }
+
+                fn key(self) -> $crate::Key {
+                    self.0
+                }
+            }
+        )*
+    };
+}
@@ -3,12 +3,37 @@
 //! This crate provides a data structure for stable, reusable keys with O(1)
 //! insertion, deletion, and lookup. Keys are generational, meaning stale
 //! references to deleted slots are detected automatically.
+//!
+//! The crate is `no_std` and only requires `alloc`. The default `std` feature
+//! enables [`SparseSecondaryMap`], which is backed by a hash map, and
+//! [`SyncArena`], which relies on std locking.
+
+#![no_std]
+
+extern crate alloc;
+
+#[cfg(any(test, feature = "std"))]
+extern crate std;
 
 mod arena;
 mod key;
+mod secondary;
+
+#[cfg(feature = "serde")]
+mod serialization;
+#[cfg(feature = "std")]
+mod sync;
 
 #[cfg(test)]
 mod tests;
 
-pub use arena::{Arena, Drain, IntoIter, Iter, IterMut};
-pub use key::Key;
+pub use arena::{
+    Arena, Drain, Entry, IntoIter, Iter, IterMut, OccupiedEntry, ReservedKey, ReservedKeys,
+    VacantEntry,
+};
+pub use key::{Key, KeyType};
+pub use secondary::SecondaryMap;
+#[cfg(feature = "std")]
+pub use secondary::SparseSecondaryMap;
+#[cfg(feature = "std")]
+pub use sync::SyncArena;
@@ -0,0 +1,491 @@
+//! Secondary maps keyed by arena keys.
+//!
+//! A secondary map attaches auxiliary data to the elements of an arena
+//! without storing it in the arena itself. Lookups perform the same version
+//! check as the arena, so data attached to a removed element is never
+//! returned for a key that reuses its slot.
+
+use alloc::vec::Vec;
+use core::{
+    fmt::{Debug, Formatter},
+    marker::PhantomData,
+    ops::{Index, IndexMut},
+};
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+
+use crate::{Key, KeyType};
+
+/// Dense secondary map.
+///
+/// Storage grows with the largest key index inserted, making it the right
+/// choice when most elements of the primary arena carry data.
+pub struct SecondaryMap<V, K = Key> {
+    /// Storage indexed by key index. Each entry keeps the key version.
+    slots: Vec<Option<(usize, V)>>,
+    /// Number of stored values.
+    count: usize,
+    /// Marker for the key type.
+    _marker: PhantomData<fn(K) -> K>,
+}
+
+impl<V> SecondaryMap<V> {
+    /// Create a new secondary map with the given capacity.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_key(capacity)
+    }
+
+    /// Create a new empty secondary map.
+    pub fn new() -> Self {
+        Self::with_capacity(0)
+    }
+}
+
+impl<V, K: KeyType> SecondaryMap<V, K> {
+    /// Create a new secondary map with the given capacity and a custom key type.
+    pub fn with_capacity_and_key(capacity: usize) -> Self {
+        Self {
+            slots: Vec::with_capacity(capacity),
+            count: 0,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Create a new empty secondary map with a custom key type.
+    pub fn with_key() -> Self {
+        Self::with_capacity_and_key(0)
+    }
+
+    /// Returns the number of elements in the map.
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    /// Returns true if the map is empty.
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Returns the capacity of the map.
+    pub fn capacity(&self) -> usize {
+        self.slots.capacity()
+    }
+
+    /// Returns true if the map contains a value for the given key.
+    pub fn contains_key(&self, key: K) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Returns a reference to the value corresponding to the key.
+    pub fn get(&self, key: K) -> Option<&V> {
+        let key = key.key();
+        match self.slots.get(key.index()) {
+            Some(Some((version, value))) if *version == key.version() => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Returns a mutable reference to the value corresponding to the key.
+    pub fn get_mut(&mut self, key: K) -> Option<&mut V> {
+        let key = key.key();
+        match self.slots.get_mut(key.index()) {
+            Some(Some((version, value))) if *version == key.version() => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Insert a value for the given key, returning the previous value for that key.
+    ///
+    /// Values stored for an older version of the slot are replaced. If the map
+    /// already holds a value for a newer version, the key is stale and nothing
+    /// is inserted.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let key = key.key();
+        if key.index() >= self.slots.len() {
+            self.slots.resize_with(key.index() + 1, || None);
+        }
+        let slot = &mut self.slots[key.index()];
+        match slot {
+            Some((version, _)) if *version > key.version() => None,
+            Some((version, old)) if *version == key.version() => {
+                Some(core::mem::replace(old, value))
+            }
+            Some(_) => {
+                *slot = Some((key.version(), value));
+                None
+            }
+            None => {
+                *slot = Some((key.version(), value));
+                self.count += 1;
+                None
+            }
+        }
+    }
+
+    /// Remove the value associated with the given key, returning it if it exists.
+    pub fn remove(&mut self, key: K) -> Option<V> {
+        let key = key.key();
+        let slot = self.slots.get_mut(key.index())?;
+        match slot {
+            Some((version, _)) if *version == key.version() => {
+                self.count -= 1;
+                slot.take().map(|(_, value)| value)
+            }
+            _ => None,
+        }
+    }
+
+    /// Remove all elements from the map, keeping the allocated memory.
+    pub fn clear(&mut self) {
+        self.slots.clear();
+        self.count = 0;
+    }
+
+    /// Retains only the elements specified by the predicate.
+    pub fn retain(&mut self, mut f: impl FnMut(K, &mut V) -> bool) {
+        for (index, slot) in self.slots.iter_mut().enumerate() {
+            if let Some((version, value)) = slot {
+                let key = K::from_key(Key {
+                    index,
+                    version: *version,
+                });
+                if !f(key, value) {
+                    *slot = None;
+                    self.count -= 1;
+                }
+            }
+        }
+    }
+
+    /// Returns an iterator over shared references to the map elements.
+    pub fn iter(&self) -> impl Iterator<Item = (K, &V)> {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            slot.as_ref().map(|(version, value)| {
+                (
+                    K::from_key(Key {
+                        index,
+                        version: *version,
+                    }),
+                    value,
+                )
+            })
+        })
+    }
+
+    /// Returns an iterator over mutable references to the map elements.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (K, &mut V)> {
+        self.slots
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, slot)| {
+                slot.as_mut().map(|(version, value)| {
+                    (
+                        K::from_key(Key {
+                            index,
+                            version: *version,
+                        }),
+                        value,
+                    )
+                })
+            })
+    }
+
+    /// Returns an iterator over the keys in the map.
+    pub fn keys(&self) -> impl Iterator<Item = K> {
+        self.iter().map(|(k, _)| k)
+    }
+
+    /// Returns an iterator over shared references to the values in the map.
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.iter().map(|(_, v)| v)
+    }
+
+    /// Returns an iterator over mutable references to the values in the map.
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut V> {
+        self.iter_mut().map(|(_, v)| v)
+    }
+}
+
+impl<V, K: KeyType> Index<K> for SecondaryMap<V, K> {
+    type Output = V;
+
+    fn index(&self, key: K) -> &Self::Output {
+        self.get(key).expect("invalid secondary map key")
+    }
+}
+
+impl<V, K: KeyType> IndexMut<K> for SecondaryMap<V, K> {
+    fn index_mut(&mut self, key: K) -> &mut Self::Output {
+        self.get_mut(key).expect("invalid secondary map key")
+    }
+}
+
+impl<V, K: KeyType> Default for SecondaryMap<V, K> {
+    fn default() -> Self {
+        Self::with_key()
+    }
+}
+
+impl<V: Clone, K> Clone for SecondaryMap<V, K> {
+    fn clone(&self) -> Self {
+        Self {
+            slots: self.slots.clone(),
+            count: self.count,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<V: Debug, K: KeyType> Debug for SecondaryMap<V, K> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<V, K: KeyType> Extend<(K, V)> for SecondaryMap<V, K> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (key, value) in iter {
+            self.insert(key, value);
+        }
+    }
+}
+
+impl<V, K: KeyType> FromIterator<(K, V)> for SecondaryMap<V, K> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = Self::with_key();
+        map.extend(iter);
+        map
+    }
+}
+
+/// Sparse secondary map.
+///
+/// Backed by a hash map, so memory is proportional to the number of stored
+/// values rather than to the size of the primary arena.
+#[cfg(feature = "std")]
+pub struct SparseSecondaryMap<V, K = Key> {
+    /// Values keyed by key index. Each entry keeps the key version.
+    slots: HashMap<usize, (usize, V)>,
+    /// Marker for the key type.
+    _marker: PhantomData<fn(K) -> K>,
+}
+
+#[cfg(feature = "std")]
+impl<V> SparseSecondaryMap<V> {
+    /// Create a new sparse secondary map with the given capacity.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_key(capacity)
+    }
+
+    /// Create a new empty sparse secondary map.
+    pub fn new() -> Self {
+        Self::with_capacity(0)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<V, K: KeyType> SparseSecondaryMap<V, K> {
+    /// Create a new sparse secondary map with the given capacity and a custom key type.
+    pub fn with_capacity_and_key(capacity: usize) -> Self {
+        Self {
+            slots: HashMap::with_capacity(capacity),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Create a new empty sparse secondary map with a custom key type.
+    pub fn with_key() -> Self {
+        Self::with_capacity_and_key(0)
+    }
+
+    /// Returns the number of elements in the map.
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Returns true if the map is empty.
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// Returns the capacity of the map.
+    pub fn capacity(&self) -> usize {
+        self.slots.capacity()
+    }
+
+    /// Returns true if the map contains a value for the given key.
+    pub fn contains_key(&self, key: K) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Returns a reference to the value corresponding to the key.
+    pub fn get(&self, key: K) -> Option<&V> {
+        let key = key.key();
+        self.slots
+            .get(&key.index())
+            .filter(|(version, _)| *version == key.version())
+            .map(|(_, value)| value)
+    }
+
+    /// Returns a mutable reference to the value corresponding to the key.
+    pub fn get_mut(&mut self, key: K) -> Option<&mut V> {
+        let key = key.key();
+        self.slots
+            .get_mut(&key.index())
+            .filter(|(version, _)| *version == key.version())
+            .map(|(_, value)| value)
+    }
+
+    /// Insert a value for the given key, returning the previous value for that key.
+    ///
+    /// Follows the same version rules as [`SecondaryMap::insert`].
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let key = key.key();
+        match self.slots.get_mut(&key.index()) {
+            Some((version, _)) if *version > key.version() => None,
+            Some((version, old)) if *version == key.version() => {
+                Some(core::mem::replace(old, value))
+            }
+            Some(slot) => {
+                *slot = (key.version(), value);
+                None
+            }
+            None => {
+                self.slots.insert(key.index(), (key.version(), value));
+                None
+            }
+        }
+    }
+
+    /// Remove the value associated with the given key, returning it if it exists.
+    pub fn remove(&mut self, key: K) -> Option<V> {
+        if self.contains_key(key) {
+            self.slots
+                .remove(&key.key().index())
+                .map(|(_, value)| value)
+        } else {
+            None
+        }
+    }
+
+    /// Remove all elements from the map, keeping the allocated memory.
+    pub fn clear(&mut self) {
+        self.slots.clear();
+    }
+
+    /// Retains only the elements specified by the predicate.
+    pub fn retain(&mut self, mut f: impl FnMut(K, &mut V) -> bool) {
+        self.slots.retain(|&index, (version, value)| {
+            f(
+                K::from_key(Key {
+                    index,
+                    version: *version,
+                }),
+                value,
+            )
+        });
+    }
+
+    /// Returns an iterator over shared references to the map elements.
+    ///
+    /// Iteration order is unspecified.
+    pub fn iter(&self) -> impl Iterator<Item = (K, &V)> {
+        self.slots.iter().map(|(&index, (version, value))| {
+            (
+                K::from_key(Key {
+                    index,
+                    version: *version,
+                }),
+                value,
+            )
+        })
+    }
+
+    /// Returns an iterator over mutable references to the map elements.
+    ///
+    /// Iteration order is unspecified.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (K, &mut V)> {
+        self.slots.iter_mut().map(|(&index, (version, value))| {
+            (
+                K::from_key(Key {
+                    index,
+                    version: *version,
+                }),
+                value,
+            )
+        })
+    }
+
+    /// Returns an iterator over the keys in the map.
+    pub fn keys(&self) -> impl Iterator<Item = K> {
+        self.iter().map(|(k, _)| k)
+    }
+
+    /// Returns an iterator over shared references to the values in the map.
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.iter().map(|(_, v)| v)
+    }
+
+    /// Returns an iterator over mutable references to the values in the map.
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut V> {
+        self.iter_mut().map(|(_, v)| v)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<V, K: KeyType> Index<K> for SparseSecondaryMap<V, K> {
+    type Output = V;
+
+    fn index(&self, key: K) -> &Self::Output {
+        self.get(key).expect("invalid secondary map key")
+    }
+}
+
+#[cfg(feature = "std")]
+impl<V, K: KeyType> IndexMut<K> for SparseSecondaryMap<V, K> {
+    fn index_mut(&mut self, key: K) -> &mut Self::Output {
+        self.get_mut(key).expect("invalid secondary map key")
+    }
+}
+
+#[cfg(feature = "std")]
+impl<V, K: KeyType> Default for SparseSecondaryMap<V, K> {
+    fn default() -> Self {
+        Self::with_key()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<V: Clone, K> Clone for SparseSecondaryMap<V, K> {
+    fn clone(&self) -> Self {
+        Self {
+            slots: self.slots.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<V: Debug, K: KeyType> Debug for SparseSecondaryMap<V, K> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<V, K: KeyType> Extend<(K, V)> for SparseSecondaryMap<V, K> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (key, value) in iter {
+            self.insert(key, value);
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<V, K: KeyType> FromIterator<(K, V)> for SparseSecondaryMap<V, K> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = Self::with_key();
+        map.extend(iter);
+        map
+    }
+}
@@ -0,0 +1,169 @@
+//! Serde support for arenas and keys.
+//!
+//! Arenas serialize every slot, including vacant ones, together with the head
+//! of the free list and the version floor left by compaction. Deserializing
+//! therefore restores the exact slot layout:
+//! keys handed out before serialization stay valid afterwards, stale keys stay
+//! stale, and future insertions reuse slots in the same order.
+
+use alloc::vec::Vec;
+use core::{marker::PhantomData, mem::ManuallyDrop};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::Error};
+
+use crate::{
+    Arena, Key, KeyType,
+    arena::{Container, Slot},
+};
+
+/// Serialized form of a slot, borrowing the stored value.
+#[derive(Serialize)]
+enum SlotRef<'a, T> {
+    /// Slot holding a value.
+    Occupied { version: usize, value: &'a T },
+    /// Slot in the free list.
+    Vacant { version: usize, next: usize },
+}
+
+/// Deserialized form of a slot.
+#[derive(Deserialize)]
+enum SlotOwned<T> {
+    /// Slot holding a value.
+    Occupied { version: usize, value: T },
+    /// Slot in the free list.
+    Vacant { version: usize, next: usize },
+}
+
+/// Serialized form of an arena, borrowing its slots.
+#[derive(Serialize)]
+struct ArenaRef<'a, T> {
+    /// All slots in index order.
+    slots: Vec<SlotRef<'a, T>>,
+    /// Index of the first free slot.
+    head: usize,
+    /// Version of slots grown past the end.
+    floor: usize,
+}
+
+/// Deserialized form of an arena.
+#[derive(Deserialize)]
+struct ArenaOwned<T> {
+    /// All slots in index order.
+    slots: Vec<SlotOwned<T>>,
+    /// Index of the first free slot.
+    head: usize,
+    /// Version of slots grown past the end, zero for arenas never compacted.
+    #[serde(default)]
+    floor: usize,
+}
+
+impl Serialize for Key {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (self.index, self.version).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Key {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (index, version) = <(usize, usize)>::deserialize(deserializer)?;
+        if version & 1 == 0 {
+            return Err(D::Error::custom("key version must be odd"));
+        }
+        Ok(Key { index, version })
+    }
+}
+
+impl<T: Serialize, K> Serialize for Arena<T, K> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let slots = self
+            .slots
+            .iter()
+            .map(|slot| unsafe {
+                if slot.empty() {
+                    SlotRef::Vacant {
+                        version: slot.version,
+                        next: slot.container.next,
+                    }
+                } else {
+                    SlotRef::Occupied {
+                        version: slot.version,
+                        value: &*slot.container.data,
+                    }
+                }
+            })
+            .collect();
+        ArenaRef {
+            slots,
+            head: self.head,
+            floor: self.floor,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de, T: Deserialize<'de>, K: KeyType> Deserialize<'de> for Arena<T, K> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let ArenaOwned { slots, head, floor } = ArenaOwned::deserialize(deserializer)?;
+        if floor & 1 == 1 {
+            return Err(D::Error::custom("odd version floor"));
+        }
+        let len = slots.len();
+
+        // Build the slots first so values are dropped correctly on error.
+        let mut count = 0;
+        let mut vacant = 0;
+        let mut built = Vec::with_capacity(len);
+        for slot in slots {
+            match slot {
+                SlotOwned::Occupied { version, value } => {
+                    if version & 1 == 0 {
+                        return Err(D::Error::custom("occupied slot with even version"));
+                    }
+                    count += 1;
+                    built.push(Slot {
+                        container: Container {
+                            data: ManuallyDrop::new(value),
+                        },
+                        version,
+                    });
+                }
+                SlotOwned::Vacant { version, next } => {
+                    if version & 1 == 1 {
+                        return Err(D::Error::custom("vacant slot with odd version"));
+                    }
+                    vacant += 1;
+                    built.push(Slot {
+                        container: Container { next },
+                        version,
+                    });
+                }
+            }
+        }
+
+        // The free list must visit every vacant slot exactly once and end
+        // past the last slot.
+        let mut visited = 0;
+        let mut cursor = head;
+        while cursor < len {
+            let slot = &built[cursor];
+            if !slot.empty() || visited == vacant {
+                return Err(D::Error::custom("corrupted free list"));
+            }
+            visited += 1;
+            cursor = unsafe { slot.container.next };
+        }
+        if visited != vacant {
+            return Err(D::Error::custom(
+                "free list does not cover all vacant slots",
+            ));
+        }
+
+        Ok(Arena {
+            slots: built,
+            head,
+            count,
+            floor,
+            _marker: PhantomData,
+        })
+    }
+}
@@ -0,0 +1,126 @@
+//! Concurrent read-mostly arena.
+//!
+//! [`SyncArena`] publishes the arena as immutable snapshots. Readers only lock
+//! to grab the current snapshot, then read from it without holding any lock.
+//! Writers serialize among themselves, apply their changes to a copy of the
+//! latest snapshot and publish the copy by swapping it in, so readers are only
+//! blocked for the swap.
+
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+
+use crate::{Arena, Key, KeyType};
+
+/// Arena shareable across threads, optimized for concurrent reads.
+///
+/// Reads never block on other reads and never observe a partially applied
+/// write. Writes are exclusive and require `T: Clone` because each write
+/// modifies a copy of the arena.
+pub struct SyncArena<T, K = Key> {
+    /// Latest published snapshot.
+    current: RwLock<Arc<Arena<T, K>>>,
+    /// Lock serializing writers.
+    writer: Mutex<()>,
+}
+
+impl<T> SyncArena<T> {
+    /// Create a new empty concurrent arena.
+    pub fn new() -> Self {
+        Self::from_arena(Arena::new())
+    }
+}
+
+impl<T, K: KeyType> SyncArena<T, K> {
+    /// Create a concurrent arena publishing the given arena.
+    pub fn from_arena(arena: Arena<T, K>) -> Self {
+        Self {
+            current: RwLock::new(Arc::new(arena)),
+            writer: Mutex::new(()),
+        }
+    }
+
+    /// Returns the latest snapshot of the arena.
+    ///
+    /// The snapshot stays valid and unchanged for as long as it is held, even
+    /// if the arena is written to concurrently.
+    pub fn snapshot(&self) -> Arc<Arena<T, K>> {
+        self.current
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Returns the number of elements in the latest snapshot.
+    pub fn len(&self) -> usize {
+        self.snapshot().len()
+    }
+
+    /// Returns true if the latest snapshot is empty.
+    pub fn is_empty(&self) -> bool {
+        self.snapshot().is_empty()
+    }
+
+    /// Returns true if the latest snapshot contains the given key.
+    pub fn contains_key(&self, key: K) -> bool {
+        self.snapshot().contains_key(key)
+    }
+
+    /// Calls `f` with the value corresponding to the key in the latest snapshot.
+    pub fn with<R>(&self, key: K, f: impl FnOnce(&T) -> R) -> Option<R> {
+        self.snapshot().get(key).map(f)
+    }
+}
+
+impl<T: Clone, K: KeyType> SyncArena<T, K> {
+    /// Returns a clone of the value corresponding to the key in the latest snapshot.
+    pub fn get_cloned(&self, key: K) -> Option<T> {
+        self.with(key, T::clone)
+    }
+
+    /// Apply a batch of modifications and publish them as a single snapshot.
+    ///
+    /// Writers are serialized. `f` runs on a copy of the latest snapshot
+    /// without blocking readers, which keep seeing the previous snapshot
+    /// until the copy is published. If `f` panics nothing is published.
+    pub fn write<R>(&self, f: impl FnOnce(&mut Arena<T, K>) -> R) -> R {
+        // A writer panicking in `f` published nothing, so the lock state is
+        // still consistent.
+        let _writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut arena = Arena::clone(&self.snapshot());
+        let result = f(&mut arena);
+        *self.current.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(arena);
+        result
+    }
+
+    /// Insert a value into the arena, returning a key to access it.
+    pub fn insert(&self, value: T) -> K {
+        self.write(|arena| arena.insert(value))
+    }
+
+    /// Remove the value associated with the given key, returning it if it exists.
+    pub fn remove(&self, key: K) -> Option<T> {
+        self.write(|arena| arena.remove(key))
+    }
+
+    /// Consume the concurrent arena, returning the latest snapshot as an arena.
+    ///
+    /// Clones the arena if a snapshot is still held elsewhere.
+    pub fn into_inner(self) -> Arena<T, K> {
+        let current = self
+            .current
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner);
+        Arc::unwrap_or_clone(current)
+    }
+}
+
+impl<T, K: KeyType> Default for SyncArena<T, K> {
+    fn default() -> Self {
+        Self::from_arena(Arena::with_key())
+    }
+}
+
+impl<T, K: KeyType> From<Arena<T, K>> for SyncArena<T, K> {
+    fn from(arena: Arena<T, K>) -> Self {
+        Self::from_arena(arena)
+    }
+}
@@ -1,4 +1,8 @@
-use crate::Arena;
+use std::{boxed::Box, format, vec, vec::Vec};
+
+use crate::{Arena, Entry, Key, KeyType, SecondaryMap};
+#[cfg(feature = "std")]
+use crate::{SparseSecondaryMap, SyncArena};
 
 #[test]
 fn new_default() {
@@ -744,3 +748,665 @@ fn box_clone_and_drop() {
     assert_eq!(arena.len(), 1);
     assert_eq!(cloned.len(), 1);
 }
+
+#[test]
+fn secondary_insert_get() {
+    let mut arena: Arena<i32> = Arena::new();
+    let k1 = arena.insert(10);
+    let k2 = arena.insert(20);
+
+    let mut map: SecondaryMap<&str> = SecondaryMap::new();
+    assert!(map.is_empty());
+    assert_eq!(map.insert(k1, "a"), None);
+    assert_eq!(map.insert(k1, "b"), Some("a"));
+    assert_eq!(map.len(), 1);
+
+    assert_eq!(map.get(k1), Some(&"b"));
+    assert_eq!(map.get(k2), None);
+    assert!(map.contains_key(k1));
+    assert!(!map.contains_key(k2));
+
+    map[k1] = "c";
+    assert_eq!(map[k1], "c");
+}
+
+#[test]
+fn secondary_stale_keys() {
+    let mut arena: Arena<i32> = Arena::new();
+    let k1 = arena.insert(10);
+
+    let mut map: SecondaryMap<i32> = SecondaryMap::new();
+    map.insert(k1, 1);
+
+    arena.remove(k1);
+    let k2 = arena.insert(20);
+    assert_eq!(k1.index(), k2.index());
+
+    // Data attached to the removed element is not visible through the new key.
+    assert_eq!(map.get(k2), None);
+
+    // Inserting with the new key replaces the stale entry.
+    assert_eq!(map.insert(k2, 2), None);
+    assert_eq!(map.len(), 1);
+    assert_eq!(map.get(k1), None);
+    assert_eq!(map.get(k2), Some(&2));
+
+    // Stale keys can no longer insert or remove.
+    assert_eq!(map.insert(k1, 3), None);
+    assert_eq!(map.remove(k1), None);
+    assert_eq!(map.get(k2), Some(&2));
+}
+
+#[test]
+fn secondary_remove_retain_iter() {
+    let mut arena: Arena<i32> = Arena::new();
+    let keys: Vec<_> = (0..5).map(|i| arena.insert(i)).collect();
+
+    let mut map: SecondaryMap<i32> = keys.iter().map(|&k| (k, arena[k] * 10)).collect();
+    assert_eq!(map.len(), 5);
+
+    assert_eq!(map.remove(keys[0]), Some(0));
+    assert_eq!(map.remove(keys[0]), None);
+    assert_eq!(map.len(), 4);
+
+    map.retain(|_, v| *v != 20);
+    assert_eq!(map.len(), 3);
+
+    let mut values: Vec<i32> = map.values().copied().collect();
+    values.sort();
+    assert_eq!(values, vec![10, 30, 40]);
+
+    for (k, v) in map.iter_mut() {
+        *v += arena[k];
+    }
+    assert_eq!(map[keys[4]], 44);
+
+    map.clear();
+    assert!(map.is_empty());
+    assert_eq!(map.iter().count(), 0);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn sparse_secondary_insert_get_remove() {
+    let mut arena: Arena<i32> = Arena::new();
+    let k1 = arena.insert(10);
+    let k2 = arena.insert(20);
+
+    let mut map: SparseSecondaryMap<i32> = SparseSecondaryMap::new();
+    assert_eq!(map.insert(k2, 2), None);
+    assert_eq!(map.insert(k2, 3), Some(2));
+    assert_eq!(map.len(), 1);
+    assert_eq!(map.get(k1), None);
+    assert_eq!(map[k2], 3);
+
+    assert_eq!(map.remove(k1), None);
+    assert_eq!(map.remove(k2), Some(3));
+    assert!(map.is_empty());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn sparse_secondary_stale_keys() {
+    let mut arena: Arena<i32> = Arena::new();
+    let k1 = arena.insert(10);
+
+    let mut map: SparseSecondaryMap<i32> = SparseSecondaryMap::new();
+    map.insert(k1, 1);
+
+    arena.remove(k1);
+    let k2 = arena.insert(20);
+
+    assert_eq!(map.get(k2), None);
+    assert_eq!(map.remove(k2), None);
+    assert_eq!(map.insert(k2, 2), None);
+    assert_eq!(map.len(), 1);
+    assert_eq!(map.insert(k1, 3), None);
+    assert_eq!(map.remove(k1), None);
+    assert_eq!(map.get(k2), Some(&2));
+
+    map.retain(|k, _| k != k2);
+    assert!(map.is_empty());
+}
+
+crate::new_key_type! {
+    /// Test key for typed arenas.
+    struct NodeKey;
+    /// Second test key, distinct from `NodeKey`.
+    struct EdgeKey;
+}
+
+#[test]
+fn typed_key_roundtrip() {
+    let mut arena: Arena<i32, NodeKey> = Arena::with_key();
+    let k: NodeKey = arena.insert(10);
+    assert_eq!(arena[k], 10);
+    assert_eq!(NodeKey::from_key(k.key()), k);
+
+    let raw: Key = k.key();
+    assert_eq!(raw.index(), 0);
+    assert_eq!(raw.version(), 1);
+
+    let keys: Vec<NodeKey> = arena.keys().collect();
+    assert_eq!(keys, vec![k]);
+
+    assert_eq!(arena.remove(k), Some(10));
+    assert!(!arena.contains_key(k));
+}
+
+#[test]
+fn typed_key_distinct_types() {
+    let mut nodes: Arena<i32, NodeKey> = Arena::with_capacity_and_key(4);
+    let mut edges: Arena<i32, EdgeKey> = Arena::default();
+    let n = nodes.insert(1);
+    let e = edges.insert(2);
+
+    // Same slot position, but the keys are not interchangeable.
+    assert_eq!(n.key(), e.key());
+    assert_eq!(nodes.get(n), Some(&1));
+    assert_eq!(edges.get(e), Some(&2));
+    assert_eq!(nodes.get(NodeKey::from_key(e.key())), Some(&1));
+}
+
+#[test]
+fn typed_key_secondary_map() {
+    let mut arena: Arena<i32, NodeKey> = Arena::with_key();
+    let k1 = arena.insert(10);
+    let k2 = arena.insert_with_key(|k| k.key().index() as i32);
+
+    let mut map: SecondaryMap<&str, NodeKey> = SecondaryMap::with_key();
+    map.insert(k1, "a");
+    assert_eq!(map.get(k1), Some(&"a"));
+    assert_eq!(map.get(k2), None);
+
+    #[cfg(feature = "std")]
+    {
+        let sparse: SparseSecondaryMap<i32, NodeKey> = arena.iter().map(|(k, v)| (k, *v)).collect();
+        assert_eq!(sparse[k2], 1);
+    }
+}
+
+#[test]
+fn compact_dense_prefix() {
+    let mut arena: Arena<i32> = Arena::new();
+    let keys: Vec<_> = (0..6).map(|i| arena.insert(i)).collect();
+    arena.remove(keys[0]);
+    arena.remove(keys[2]);
+    arena.remove(keys[3]);
+
+    let remap = arena.compact();
+    assert_eq!(arena.len(), 3);
+    assert_eq!(remap.len(), 3);
+
+    let moved: Vec<_> = [1, 4, 5].iter().map(|&i| remap[keys[i]]).collect();
+    let mut indices: Vec<_> = moved.iter().map(|k| k.index()).collect();
+    indices.sort();
+    assert_eq!(indices, vec![0, 1, 2]);
+    for (&i, &k) in [1, 4, 5].iter().zip(&moved) {
+        assert_eq!(arena[k], i);
+    }
+
+    // Removed keys are absent from the remap table.
+    assert_eq!(remap.get(keys[0]), None);
+}
+
+#[test]
+fn compact_invalidates_moved_keys() {
+    let mut arena: Arena<i32> = Arena::new();
+    let k1 = arena.insert(10);
+    let k2 = arena.insert(20);
+    arena.remove(k1);
+
+    let remap = arena.compact();
+    let new_k2 = remap[k2];
+    assert_eq!(new_k2.index(), 0);
+    assert_eq!(arena.get(new_k2), Some(&20));
+
+    // The old key pointed at a truncated slot.
+    assert_eq!(arena.get(k2), None);
+    // The old key of the removed element must not alias the moved element.
+    assert_eq!(arena.get(k1), None);
+
+    // Growing the truncated slot back must not revive the old key.
+    let k3 = arena.insert(30);
+    assert_eq!(k3.index(), k2.index());
+    assert_eq!(arena.get(k1), None);
+    assert_eq!(arena.get(k2), None);
+    assert_eq!(arena.get(k3), Some(&30));
+}
+
+#[test]
+fn compact_invalidates_removed_tail_keys() {
+    let mut arena: Arena<i32> = Arena::new();
+    let k1 = arena.insert(10);
+    let k2 = arena.insert(20);
+    let k3 = arena.insert(30);
+    arena.remove(k3);
+
+    arena.compact();
+    let k4 = arena.insert(40);
+    assert_eq!(k4.index(), k3.index());
+    assert_eq!(arena.get(k3), None);
+    assert_eq!(arena.get(k4), Some(&40));
+
+    // Reserved slots grown past the end also start above the floor.
+    arena.remove(k4);
+    arena.compact();
+    let reserved = arena.reserve_slot();
+    let k5 = reserved.key();
+    reserved.fill(50);
+    assert_eq!(arena.get(k3), None);
+    assert_eq!(arena.get(k4), None);
+    assert_eq!(arena.get(k5), Some(&50));
+    assert_eq!(arena.get(k1), Some(&10));
+    assert_eq!(arena.get(k2), Some(&20));
+}
+
+#[test]
+fn insert_with_key_after_compact() {
+    let mut arena: Arena<i32> = Arena::new();
+    arena.insert(10);
+    let k2 = arena.insert(20);
+    arena.remove(k2);
+    arena.compact();
+
+    let mut given = None;
+    let k3 = arena.insert_with_key(|key| {
+        given = Some(key);
+        30
+    });
+    // The closure sees the key the value is stored under.
+    assert_eq!(given, Some(k3));
+    assert_eq!(k3.index(), k2.index());
+    assert_eq!(arena.get(k2), None);
+    assert_eq!(arena.get(k3), Some(&30));
+}
+
+#[test]
+fn clone_from_keeps_stale_keys_invalid() {
+    let mut arena: Arena<i32> = Arena::new();
+    arena.insert(10);
+    let k2 = arena.insert(20);
+    arena.remove(k2);
+    arena.compact();
+
+    let mut cloner: Arena<i32> = Arena::new();
+    cloner.clone_from(&arena);
+    let k3 = cloner.insert(30);
+    assert_eq!(k3.index(), k2.index());
+    assert_eq!(cloner.get(k2), None);
+    assert_eq!(cloner.get(k3), Some(&30));
+}
+
+#[test]
+fn compact_untouched_keys_stay_valid() {
+    let mut arena: Arena<i32> = Arena::new();
+    let k1 = arena.insert(10);
+    let k2 = arena.insert(20);
+    let k3 = arena.insert(30);
+    arena.remove(k3);
+
+    let remap = arena.compact();
+    assert_eq!(remap[k1], k1);
+    assert_eq!(remap[k2], k2);
+    assert_eq!(arena.get(k1), Some(&10));
+    assert_eq!(arena.capacity(), 2);
+
+    // Insertion continues after the compacted prefix.
+    let k4 = arena.insert(40);
+    assert_eq!(k4.index(), 2);
+    assert_eq!(arena.len(), 3);
+}
+
+#[test]
+fn compact_drops_nothing() {
+    use std::rc::Rc;
+
+    let tracker = Rc::new(());
+    let mut arena: Arena<Rc<()>> = Arena::new();
+    let keys: Vec<_> = (0..4).map(|_| arena.insert(tracker.clone())).collect();
+    arena.remove(keys[1]);
+    assert_eq!(Rc::strong_count(&tracker), 4);
+
+    let remap = arena.compact();
+    assert_eq!(Rc::strong_count(&tracker), 4);
+    assert_eq!(remap.len(), 3);
+
+    drop(arena);
+    assert_eq!(Rc::strong_count(&tracker), 1);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_roundtrip_preserves_keys() {
+    use std::string::{String, ToString};
+
+    let mut arena: Arena<String> = Arena::new();
+    let k1 = arena.insert("a".to_string());
+    let k2 = arena.insert("b".to_string());
+    let k3 = arena.insert("c".to_string());
+    arena.remove(k1);
+    arena.remove(k3);
+
+    let json = serde_json::to_string(&arena).unwrap();
+    let mut restored: Arena<String> = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored, arena);
+    assert_eq!(restored.len(), 1);
+    assert_eq!(restored.get(k2), Some(&"b".to_string()));
+    assert_eq!(restored.get(k1), None);
+
+    // The free list is restored, so insertions reuse slots in the same order.
+    let a = arena.insert("d".to_string());
+    let b = restored.insert("d".to_string());
+    assert_eq!(a, b);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_roundtrip_preserves_version_floor() {
+    let mut arena: Arena<i32> = Arena::new();
+    arena.insert(1);
+    let k2 = arena.insert(2);
+    arena.remove(k2);
+    arena.compact();
+
+    let json = serde_json::to_string(&arena).unwrap();
+    let mut restored: Arena<i32> = serde_json::from_str(&json).unwrap();
+    let k3 = restored.insert(3);
+    assert_eq!(k3.index(), k2.index());
+    assert_eq!(restored.get(k2), None);
+
+    let json = r#"{"slots":[],"head":0,"floor":1}"#;
+    assert!(serde_json::from_str::<Arena<i32>>(json).is_err());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_key_roundtrip() {
+    let mut arena: Arena<i32> = Arena::new();
+    let k = arena.insert(1);
+    let json = serde_json::to_string(&k).unwrap();
+    let restored: Key = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored, k);
+
+    assert!(serde_json::from_str::<Key>("[0,2]").is_err());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_rejects_corrupted_free_list() {
+    // Vacant slot not reachable from head.
+    let json = r#"{"slots":[{"Vacant":{"version":2,"next":1}}],"head":1}"#;
+    assert!(serde_json::from_str::<Arena<i32>>(json).is_err());
+
+    // Free list loop.
+    let json = r#"{"slots":[{"Vacant":{"version":2,"next":0}}],"head":0}"#;
+    assert!(serde_json::from_str::<Arena<i32>>(json).is_err());
+
+    // Occupied slot with an even version.
+    let json = r#"{"slots":[{"Occupied":{"version":2,"value":5}}],"head":1}"#;
+    assert!(serde_json::from_str::<Arena<i32>>(json).is_err());
+
+    let json = r#"{"slots":[{"Occupied":{"version":3,"value":5}}],"head":1}"#;
+    let arena = serde_json::from_str::<Arena<i32>>(json).unwrap();
+    assert_eq!(arena.values().copied().collect::<Vec<_>>(), vec![5]);
+}
+
+#[test]
+fn entry_occupied() {
+    let mut arena: Arena<i32> = Arena::new();
+    let k = arena.insert(10);
+
+    match arena.entry(k) {
+        Entry::Occupied(mut entry) => {
+            assert_eq!(entry.key(), k);
+            assert_eq!(*entry.get(), 10);
+            *entry.get_mut() += 1;
+            assert_eq!(entry.insert(20), 11);
+        }
+        Entry::Vacant(_) => panic!("expected occupied entry"),
+    }
+    assert_eq!(arena[k], 20);
+
+    let (key, value) = arena.entry(k).and_modify(|v| *v += 1).or_insert(0);
+    assert_eq!(key, k);
+    assert_eq!(*value, 21);
+
+    if let Entry::Occupied(entry) = arena.entry(k) {
+        assert_eq!(entry.remove(), 21);
+    }
+    assert!(arena.is_empty());
+}
+
+#[test]
+fn entry_vacant() {
+    let mut arena: Arena<i32> = Arena::new();
+    let stale = arena.insert(10);
+    arena.remove(stale);
+
+    let entry = arena.entry(stale).and_modify(|_| panic!("not occupied"));
+    assert_eq!(entry.key(), stale);
+    assert!(matches!(entry, Entry::Vacant(_)));
+
+    let (fresh, value) = arena.entry(stale).or_insert_with(|| 30);
+    *value += 1;
+    assert_ne!(fresh, stale);
+    assert_eq!(arena[fresh], 31);
+    assert_eq!(arena.get(stale), None);
+}
+
+#[test]
+fn get_disjoint_mut_distinct() {
+    let mut arena: Arena<i32> = Arena::new();
+    let k1 = arena.insert(10);
+    let k2 = arena.insert(20);
+    let k3 = arena.insert(30);
+
+    let [a, b] = arena.get_disjoint_mut([k1, k3]).unwrap();
+    std::mem::swap(a, b);
+    assert_eq!(arena[k1], 30);
+    assert_eq!(arena[k3], 10);
+
+    let [a, b, c] = arena.get_disjoint_mut([k3, k2, k1]).unwrap();
+    *a += *b + *c;
+    assert_eq!(arena[k3], 60);
+}
+
+#[test]
+fn get_disjoint_mut_rejects_invalid() {
+    let mut arena: Arena<i32> = Arena::new();
+    let k1 = arena.insert(10);
+    let k2 = arena.insert(20);
+
+    assert!(arena.get_disjoint_mut([k1, k1]).is_none());
+
+    arena.remove(k2);
+    assert!(arena.get_disjoint_mut([k1, k2]).is_none());
+
+    let k3 = arena.insert(30);
+    assert_eq!(k3.index(), k2.index());
+    assert!(arena.get_disjoint_mut([k2, k3]).is_none());
+    assert!(arena.get_disjoint_mut([k1, k3]).is_some());
+}
+
+#[test]
+fn reserve_slot_fill() {
+    let mut arena: Arena<(usize, i32)> = Arena::new();
+    let reserved = arena.reserve_slot();
+    let key = reserved.key();
+    assert_eq!(reserved.fill((key.index(), 10)), key);
+
+    assert_eq!(arena.len(), 1);
+    assert_eq!(arena[key], (0, 10));
+}
+
+#[test]
+fn reserve_slot_not_visible_until_filled() {
+    let mut arena: Arena<i32> = Arena::new();
+    let k1 = arena.insert(10);
+    let reserved = arena.reserve_slot();
+    let key = reserved.key();
+    drop(reserved);
+
+    assert_eq!(arena.len(), 1);
+    assert_eq!(arena.get(key), None);
+    assert_eq!(arena.iter().count(), 1);
+    assert_eq!(arena.get(k1), Some(&10));
+}
+
+#[test]
+fn reserve_slot_rollback_on_drop() {
+    let mut arena: Arena<i32> = Arena::new();
+    let k1 = arena.insert(10);
+    arena.remove(k1);
+
+    let key = arena.reserve_slot().key();
+    assert_eq!(key.index(), k1.index());
+
+    // The slot returns to the free list, and the rolled back key stays dead.
+    let k2 = arena.insert(20);
+    assert_eq!(k2.index(), k1.index());
+    assert_ne!(k2, key);
+    assert_eq!(arena.get(key), None);
+    assert_eq!(arena.get(k2), Some(&20));
+    assert_eq!(arena.len(), 1);
+}
+
+#[test]
+fn reserve_slot_rollback_appended_slot() {
+    let mut arena: Arena<i32> = Arena::new();
+    let key = arena.reserve_slot().key();
+    assert_eq!(key.index(), 0);
+    assert!(arena.is_empty());
+
+    let k = arena.insert(10);
+    assert_eq!(k.index(), 0);
+    assert_ne!(k, key);
+    assert_eq!(arena.get(key), None);
+}
+
+#[test]
+fn reserve_slots_fill_many() {
+    let mut arena: Arena<i32> = Arena::new();
+    let stale = arena.insert(0);
+    arena.remove(stale);
+
+    let reserved = arena.reserve_slots(3);
+    let keys = reserved.keys().to_vec();
+    assert_eq!(reserved.fill_many([1, 2, 3]), Ok(keys.clone()));
+
+    assert_eq!(arena.len(), 3);
+    let values: Vec<i32> = keys.iter().map(|&k| arena[k]).collect();
+    assert_eq!(values, vec![1, 2, 3]);
+}
+
+#[test]
+fn reserve_slots_fill_many_all_or_nothing() {
+    let mut arena: Arena<i32> = Arena::new();
+    let k1 = arena.insert(10);
+
+    let reserved = arena.reserve_slots(2);
+    let keys = reserved.keys().to_vec();
+    assert_eq!(reserved.fill_many([1, 2, 3]), Err(vec![1, 2, 3]));
+
+    assert_eq!(arena.len(), 1);
+    assert!(keys.iter().all(|&k| arena.get(k).is_none()));
+    assert_eq!(arena.get(k1), Some(&10));
+
+    // Released slots are reused in reservation order.
+    let k2 = arena.insert(20);
+    let k3 = arena.insert(30);
+    assert_eq!(k2.index(), keys[0].index());
+    assert_eq!(k3.index(), keys[1].index());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn sync_arena_snapshots_are_stable() {
+    let arena: SyncArena<i32> = SyncArena::new();
+    let k1 = arena.insert(10);
+
+    let snapshot = arena.snapshot();
+    let k2 = arena.insert(20);
+    assert_eq!(arena.remove(k1), Some(10));
+
+    // The old snapshot is unaffected by later writes.
+    assert_eq!(snapshot.len(), 1);
+    assert_eq!(snapshot.get(k1), Some(&10));
+    assert_eq!(snapshot.get(k2), None);
+
+    assert_eq!(arena.len(), 1);
+    assert!(!arena.contains_key(k1));
+    assert_eq!(arena.get_cloned(k2), Some(20));
+    assert_eq!(arena.with(k2, |v| v * 2), Some(40));
+
+    let inner = arena.into_inner();
+    assert_eq!(inner.get(k2), Some(&20));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn sync_arena_concurrent_reads() {
+    use std::{sync::Arc, thread};
+
+    let base: Arena<usize> = (0..100).collect();
+    let keys: Vec<_> = base.keys().collect();
+    let arena = Arc::new(SyncArena::from(base));
+
+    let readers: Vec<_> = (0..4)
+        .map(|_| {
+            let arena = arena.clone();
+            let keys = keys.clone();
+            thread::spawn(move || {
+                for _ in 0..100 {
+                    let snapshot = arena.snapshot();
+                    for &k in &keys {
+                        if let Some(&v) = snapshot.get(k) {
+                            assert_eq!(v, k.index());
+                        }
+                    }
+                }
+            })
+        })
+        .collect();
+
+    let writer = {
+        let arena = arena.clone();
+        let keys = keys.clone();
+        thread::spawn(move || {
+            for &k in keys.iter().step_by(2) {
+                arena.remove(k);
+            }
+        })
+    };
+
+    writer.join().unwrap();
+    for reader in readers {
+        reader.join().unwrap();
+    }
+    assert_eq!(arena.len(), 50);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn sync_arena_panicking_write_publishes_nothing() {
+    use std::panic::{AssertUnwindSafe, catch_unwind};
+
+    let arena: SyncArena<i32> = SyncArena::new();
+    let k1 = arena.insert(10);
+
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        arena.write(|inner| {
+            inner.remove(k1);
+            inner.insert(20);
+            panic!("write aborted");
+        })
+    }));
+    assert!(result.is_err());
+
+    // The half-applied batch was never published and writers still work.
+    assert_eq!(arena.len(), 1);
+    assert_eq!(arena.get_cloned(k1), Some(10));
+    let k2 = arena.insert(30);
+    assert_eq!(arena.get_cloned(k2), Some(30));
+}
@@ -0,0 +1,45 @@
+//! Benchmarks of the compilation stages on synthetic circuits.
+//!
+//! Run with `cargo bench -p vulcano-circuit --features bench`.
+
+use criterion::{BatchSize, BenchmarkId, Criterion, criterion_group, criterion_main};
+use vulcano_circuit::bench::{Stage, Workload};
+
+/// Circuit sizes, in gates.
+const SIZES: [usize; 3] = [1_000, 10_000, 100_000];
+
+/// Seed of the synthetic circuits.
+const SEED: u64 = 0x5eed;
+
+/// Stages measured on every size.
+const STAGES: [(&str, Stage); 9] = [
+    ("build", Stage::Build),
+    ("topological_order", Stage::TopologicalOrder),
+    ("live_ranges", Stage::LiveRanges),
+    ("wire_allocation", Stage::WireAllocation),
+    ("critical_path", Stage::CriticalPath),
+    ("schedule", Stage::Schedule),
+    ("reconcile_ownership", Stage::ReconcileOwnership),
+    ("dead_code_elimination", Stage::DeadCodeElimination),
+    ("constant_folding", Stage::ConstantFolding),
+];
+
+fn stages(c: &mut Criterion) {
+    for (name, stage) in STAGES {
+        let mut group = c.benchmark_group(name);
+        group.sample_size(10);
+        for size in SIZES {
+            group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, &size| {
+                b.iter_batched(
+                    || Workload::synthetic(size, SEED),
+                    |mut workload| workload.run(stage),
+                    BatchSize::LargeInput,
+                );
+            });
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, stages);
+criterion_main!(benches);
@@ -0,0 +1,380 @@
+//! Balanced Partitioning Analysis
+//!
+//! Splits the gates and clones of a circuit among `PARTIES` parties of
+//! approximately equal cost, weighted by the gate cost analysis, while
+//! keeping the cost of the values sent between parties low, weighted by the
+//! transfer cost of their types set in the analyzer. Unlike level
+//! packing, it splits connected circuits along cheap cuts, as needed when
+//! several parties evaluate one computation jointly.
+//!
+//! Operations are first ordered depth first, which keeps chains of dependent
+//! operations together, and cut into contiguous pieces of equal cost. Single
+//! operations are then moved to the party of their neighbours whenever that
+//! reduces the transfer cost and the receiving party stays within the
+//! balance bound.
+//!
+//! Operations placed on a device are pinned: each device gets a party of its
+//! own, in order of first use, holding every operation placed on it, and
+//! refinement never moves them.
+//!
+//! Each value consumed by a party other than its producer's becomes a
+//! message. Drops follow the party of the value they consume, or the first
+//! party for values produced by inputs and constants.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use crate::{
+    analyzer::{
+        Analysis, Analyzer,
+        analyses::{gate_costs::GateCosts, partitioning::PartitionMap},
+    },
+    circuit::{Circuit, Consumer, Operation, Producer},
+    error::{Error, Result},
+    gate::Gate,
+    handles::ValueId,
+    placement::Device,
+};
+
+/// Fraction by which a party may exceed its share of the total cost.
+const TOLERANCE: f64 = 0.05;
+
+/// Maximum number of refinement rounds over all operations.
+const REFINEMENT_ROUNDS: usize = 8;
+
+/// A value sent from the party producing it to the parties consuming it.
+pub(crate) struct Message {
+    /// The value sent.
+    pub value: ValueId,
+    /// Party producing the value.
+    pub from: usize,
+    /// Parties consuming the value, in increasing order.
+    pub to: Vec<usize>,
+    /// Cost of sending the value to every receiving party.
+    pub cost: f64,
+}
+
+/// Result of balanced partitioning analysis among `PARTIES` parties.
+pub(crate) struct BalancedPartitioning<const PARTIES: usize> {
+    /// Party of each gate, clone and drop.
+    assignment: HashMap<Operation, usize>,
+    /// Total gate cost of each party.
+    costs: Vec<f64>,
+    /// Values crossing parties, in dependency order of their producers.
+    messages: Vec<Message>,
+    /// Device of each party holding pinned operations.
+    devices: Vec<Option<Device>>,
+}
+
+impl<const PARTIES: usize> PartitionMap for BalancedPartitioning<PARTIES> {
+    fn partition(&self, op: Operation) -> Option<usize> {
+        self.assignment.get(&op).copied()
+    }
+
+    fn partition_count(&self) -> usize {
+        self.costs.len()
+    }
+
+    fn device(&self, partition: usize) -> Option<Device> {
+        self.devices.get(partition).copied().flatten()
+    }
+}
+
+impl<const PARTIES: usize> BalancedPartitioning<PARTIES> {
+    /// Iterate over the operations assigned to a party.
+    pub(crate) fn operations(&self, party: usize) -> impl Iterator<Item = Operation> + '_ {
+        self.assignment
+            .iter()
+            .filter(move |&(_, &p)| p == party)
+            .map(|(&op, _)| op)
+    }
+
+    /// Total gate cost of a party.
+    pub(crate) fn cost(&self, party: usize) -> f64 {
+        self.costs.get(party).copied().unwrap_or(0.0)
+    }
+
+    /// Values crossing parties.
+    pub(crate) fn messages(&self) -> &[Message] {
+        &self.messages
+    }
+
+    /// Number of values received by parties, counting each receiver once.
+    pub(crate) fn cut_size(&self) -> usize {
+        self.messages.iter().map(|m| m.to.len()).sum()
+    }
+
+    /// Total cost of the values sent between parties.
+    pub(crate) fn transfer_cost(&self) -> f64 {
+        self.messages.iter().map(|m| m.cost).sum()
+    }
+}
+
+/// Gates and clones of a circuit with the values connecting them.
+struct Graph {
+    /// Gates and clones in depth-first dependency order, with their cost.
+    ops: Vec<(Operation, f64)>,
+    /// Values consumed or produced by each operation that may cross parties.
+    touched: HashMap<Operation, Vec<ValueId>>,
+    /// Producer of each value produced by a gate or clone.
+    producer: HashMap<ValueId, Operation>,
+    /// Gates and clones consuming each value.
+    consumers: HashMap<ValueId, Vec<Operation>>,
+    /// Cost of sending each value to one party.
+    transfer: HashMap<ValueId, f64>,
+}
+
+impl Graph {
+    /// Build the graph of a circuit.
+    fn new<G: Gate>(
+        circuit: &Circuit<G>,
+        costs: &GateCosts,
+        transfer: impl Fn(G::Operand) -> f64,
+    ) -> Result<Self> {
+        let mut graph = Graph {
+            ops: Vec::new(),
+            touched: HashMap::new(),
+            producer: HashMap::new(),
+            consumers: HashMap::new(),
+            transfer: HashMap::new(),
+        };
+        for op in circuit.iter_scheduled()? {
+            let (cost, inputs, outputs) = match op {
+                Operation::Gate(id) => {
+                    let gate = circuit.gate_op(id)?;
+                    (
+                        costs.cost(id),
+                        gate.get_inputs().to_vec(),
+                        gate.get_outputs(),
+                    )
+                }
+                Operation::Clone(id) => {
+                    let clone = circuit.clone_op(id)?;
+                    (0.0, Vec::from([clone.get_input()]), clone.get_outputs())
+                }
+                _ => continue,
+            };
+
+            let mut touched = Vec::new();
+            for value in inputs {
+                if graph.producer.contains_key(&value) {
+                    touched.push(value);
+                }
+            }
+            for &value in outputs {
+                graph.producer.insert(value, op);
+                let consumers = circuit
+                    .value(value)?
+                    .get_uses()
+                    .iter()
+                    .filter(|usage| {
+                        matches!(usage.consumer, Consumer::Gate(_) | Consumer::Clone(_))
+                    })
+                    .map(|usage| Operation::from(usage.consumer))
+                    .collect();
+                graph.consumers.insert(value, consumers);
+                graph
+                    .transfer
+                    .insert(value, transfer(circuit.value(value)?.get_type()));
+                touched.push(value);
+            }
+            graph.touched.insert(op, touched);
+            graph.ops.push((op, cost));
+        }
+        graph.ops = graph.depth_first_order();
+        Ok(graph)
+    }
+
+    /// Order the operations depth first from the ones without consumers,
+    /// so that chains of dependent operations end up next to each other.
+    fn depth_first_order(&self) -> Vec<(Operation, f64)> {
+        let costs: HashMap<Operation, f64> = self.ops.iter().copied().collect();
+        let mut order = Vec::with_capacity(self.ops.len());
+        let mut visited = HashSet::new();
+        for &(sink, _) in &self.ops {
+            let produced = self.touched[&sink]
+                .iter()
+                .filter(|value| self.producer[value] == sink);
+            if produced
+                .clone()
+                .any(|value| !self.consumers[value].is_empty())
+            {
+                continue;
+            }
+            let mut stack = Vec::from([(sink, false)]);
+            while let Some((op, expanded)) = stack.pop() {
+                if expanded {
+                    order.push((op, costs[&op]));
+                    continue;
+                }
+                if !visited.insert(op) {
+                    continue;
+                }
+                stack.push((op, true));
+                for value in self.touched[&op].iter().rev() {
+                    let producer = self.producer[value];
+                    if producer != op && !visited.contains(&producer) {
+                        stack.push((producer, false));
+                    }
+                }
+            }
+        }
+        order
+    }
+
+    /// Parties other than the producer's consuming a value.
+    fn receivers(&self, value: ValueId, assignment: &HashMap<Operation, usize>) -> BTreeSet<usize> {
+        let from = assignment[&self.producer[&value]];
+        self.consumers[&value]
+            .iter()
+            .map(|op| assignment[op])
+            .filter(|&p| p != from)
+            .collect()
+    }
+
+    /// Transfer cost of the values touched by an operation.
+    fn local_cut(&self, op: Operation, assignment: &HashMap<Operation, usize>) -> f64 {
+        self.touched[&op]
+            .iter()
+            .map(|&value| self.receivers(value, assignment).len() as f64 * self.transfer[&value])
+            .sum()
+    }
+
+    /// Parties of the operations sharing a value with an operation.
+    fn neighbour_parties(
+        &self,
+        op: Operation,
+        assignment: &HashMap<Operation, usize>,
+    ) -> BTreeSet<usize> {
+        self.touched[&op]
+            .iter()
+            .flat_map(|value| self.consumers[value].iter().chain([&self.producer[value]]))
+            .map(|other| assignment[other])
+            .collect()
+    }
+}
+
+impl<const PARTIES: usize> Analysis for BalancedPartitioning<PARTIES> {
+    type Output = Self;
+
+    fn run<G: Gate>(circuit: &Circuit<G>, analyzer: &mut Analyzer<G>) -> Result<Self::Output> {
+        let parties = PARTIES.max(1);
+        let gate_costs = analyzer.get::<GateCosts>(circuit)?;
+        let graph = Graph::new(circuit, &gate_costs, |ty| analyzer.transfer_cost(ty))?;
+
+        // Step 1. Give each device a party of its own.
+        let mut devices = vec![None; parties];
+        let mut pinned = HashMap::new();
+        let mut device_count = 0;
+        for &(op, _) in &graph.ops {
+            let Some(device) = circuit.placement(op) else {
+                continue;
+            };
+            let party = match devices.iter().position(|&d| d == Some(device)) {
+                Some(party) => party,
+                None if device_count < parties => {
+                    devices[device_count] = Some(device);
+                    device_count += 1;
+                    device_count - 1
+                }
+                None => return Err(Error::TooManyDevices { parties }),
+            };
+            pinned.insert(op, party);
+        }
+
+        // Step 2. Cut the operations into contiguous pieces of equal cost,
+        // keeping pinned operations in the party of their device.
+        let total: f64 = graph.ops.iter().map(|(_, cost)| cost).sum();
+        let share = total / parties as f64;
+        let mut assignment = HashMap::new();
+        let mut costs = vec![0.0; parties];
+        let mut prefix = 0.0;
+        for &(op, cost) in &graph.ops {
+            let party = if let Some(&party) = pinned.get(&op) {
+                party
+            } else if share > 0.0 {
+                (((prefix + cost / 2.0) / share) as usize).min(parties - 1)
+            } else {
+                0
+            };
+            assignment.insert(op, party);
+            costs[party] += cost;
+            prefix += cost;
+        }
+
+        // Step 3. Move unpinned operations to neighbouring parties while it
+        // reduces transfer cost, or evens out costs without adding to it, and
+        // keeps parties within the balance bound.
+        let heaviest = graph.ops.iter().map(|&(_, c)| c).fold(0.0, f64::max);
+        let bound = share * (1.0 + TOLERANCE) + heaviest;
+        for _ in 0..REFINEMENT_ROUNDS {
+            let mut improved = false;
+            for &(op, cost) in &graph.ops {
+                if pinned.contains_key(&op) {
+                    continue;
+                }
+                let from = assignment[&op];
+                let before = graph.local_cut(op, &assignment);
+                let mut best: Option<(usize, f64)> = None;
+                for to in graph.neighbour_parties(op, &assignment) {
+                    if to == from || costs[to] + cost > bound {
+                        continue;
+                    }
+                    assignment.insert(op, to);
+                    let after = graph.local_cut(op, &assignment);
+                    let evens_out = cost > 0.0 && costs[to] + cost < costs[from];
+                    if (after < before || (after == before && evens_out))
+                        && best.is_none_or(|(_, cut)| after < cut)
+                    {
+                        best = Some((to, after));
+                    }
+                }
+                let to = best.map_or(from, |(to, _)| to);
+                assignment.insert(op, to);
+                if to != from {
+                    costs[from] -= cost;
+                    costs[to] += cost;
+                    improved = true;
+                }
+            }
+            if !improved {
+                break;
+            }
+        }
+
+        // Step 4. Record the values crossing parties.
+        let mut messages = Vec::new();
+        for &(op, _) in &graph.ops {
+            for &value in &graph.touched[&op] {
+                if graph.producer[&value] != op {
+                    continue;
+                }
+                let to = graph.receivers(value, &assignment);
+                if !to.is_empty() {
+                    messages.push(Message {
+                        value,
+                        from: assignment[&op],
+                        cost: to.len() as f64 * graph.transfer[&value],
+                        to: to.into_iter().collect(),
+                    });
+                }
+            }
+        }
+
+        // Step 5. Drops go with the producer of their value.
+        for (drop_id, drop) in circuit.all_drops() {
+            let party = match circuit.value(drop.get_input())?.get_producer() {
+                Producer::Gate(id) => assignment[&Operation::Gate(id)],
+                Producer::Clone(id) => assignment[&Operation::Clone(id)],
+                Producer::Input(_) | Producer::Constant(_) => 0,
+            };
+            assignment.insert(Operation::Drop(drop_id), party);
+        }
+
+        Ok(BalancedPartitioning {
+            assignment,
+            costs,
+            messages,
+            devices,
+        })
+    }
+}
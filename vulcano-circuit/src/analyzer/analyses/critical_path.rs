@@ -0,0 +1,124 @@
+//! Critical Path Analysis
+//!
+//! Computes the most expensive chain of dependent gates, weighted by the
+//! gate cost analysis. Its cost bounds the latency of any parallel schedule,
+//! and the slack of a gate tells how much it can be delayed without
+//! lengthening the circuit.
+
+use std::collections::HashMap;
+
+use crate::{
+    analyzer::{Analysis, Analyzer, analyses::gate_costs::GateCosts},
+    circuit::{Circuit, Operation},
+    error::Result,
+    gate::Gate,
+};
+
+/// Result of critical path analysis.
+pub(crate) struct CriticalPath {
+    /// Cost of each operation.
+    cost: HashMap<Operation, f64>,
+    /// Cost of the longest chain ending at each operation, itself included.
+    finish: HashMap<Operation, f64>,
+    /// Cost of the longest chain starting at each operation, itself included.
+    tail: HashMap<Operation, f64>,
+    /// Operations on the critical path, in dependency order.
+    path: Vec<Operation>,
+    /// Cost of the critical path.
+    length: f64,
+}
+
+impl CriticalPath {
+    /// Cost of the critical path.
+    pub(crate) fn length(&self) -> f64 {
+        self.length
+    }
+
+    /// Operations on the critical path, in dependency order.
+    pub(crate) fn path(&self) -> &[Operation] {
+        &self.path
+    }
+
+    /// How much an operation can be delayed without lengthening the circuit.
+    pub(crate) fn slack(&self, op: Operation) -> Option<f64> {
+        let longest_through = self.finish.get(&op)? + self.tail.get(&op)? - self.cost.get(&op)?;
+        Some(self.length - longest_through)
+    }
+}
+
+impl Analysis for CriticalPath {
+    type Output = Self;
+
+    fn run<G: Gate>(circuit: &Circuit<G>, analyzer: &mut Analyzer<G>) -> Result<Self::Output> {
+        let costs = analyzer.get::<GateCosts>(circuit)?;
+        let schedule: Vec<Operation> = circuit.iter_scheduled()?.collect();
+
+        let cost: HashMap<Operation, f64> = schedule
+            .iter()
+            .map(|&op| match op {
+                Operation::Gate(id) => (op, costs.cost(id)),
+                _ => (op, 0.0),
+            })
+            .collect();
+
+        // Forward pass: longest chain ending at each operation.
+        let mut finish: HashMap<Operation, f64> = HashMap::new();
+        let mut best_predecessor: HashMap<Operation, Operation> = HashMap::new();
+        let mut successors: HashMap<Operation, Vec<Operation>> = HashMap::new();
+        for &op in &schedule {
+            let inputs = match op {
+                Operation::Gate(id) => circuit.gate_op(id)?.get_inputs().to_vec(),
+                Operation::Clone(id) => Vec::from([circuit.clone_op(id)?.get_input()]),
+                Operation::Drop(id) => Vec::from([circuit.drop_op(id)?.get_input()]),
+                Operation::Output(id) => Vec::from([circuit.output_op(id)?.get_input()]),
+                Operation::Input(_) | Operation::Constant(_) => Vec::new(),
+            };
+            let mut start = 0.0;
+            for value in inputs {
+                let pred = Operation::from(circuit.value(value)?.get_producer());
+                successors.entry(pred).or_default().push(op);
+                if !best_predecessor.contains_key(&op) || finish[&pred] > start {
+                    start = finish[&pred];
+                    best_predecessor.insert(op, pred);
+                }
+            }
+            finish.insert(op, start + cost[&op]);
+        }
+
+        // Backward pass: longest chain starting at each operation.
+        let mut tail: HashMap<Operation, f64> = HashMap::new();
+        for &op in schedule.iter().rev() {
+            let after = successors
+                .get(&op)
+                .into_iter()
+                .flatten()
+                .map(|s| tail[s])
+                .fold(0.0, f64::max);
+            tail.insert(op, after + cost[&op]);
+        }
+
+        // Walk back from the operation finishing last.
+        let mut path = Vec::new();
+        let mut length = 0.0;
+        if let Some(&end) = schedule
+            .iter()
+            .max_by(|a, b| finish[*a].total_cmp(&finish[*b]))
+        {
+            length = finish[&end];
+            let mut current = Some(end);
+            while let Some(op) = current {
+                path.push(op);
+                current = best_predecessor.get(&op).copied();
+            }
+            path.reverse();
+        }
+
+        Ok(CriticalPath {
+            cost,
+            finish,
+            tail,
+            path,
+            length,
+        })
+    }
+}
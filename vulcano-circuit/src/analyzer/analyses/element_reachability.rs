@@ -1,7 +1,8 @@
 //! Element Reachability Analysis
 //!
 //! Computes which values and operations are reachable from circuit outputs.
-//! An element is reachable if it contributes (directly or transitively) to an output.
+//! An element is reachable if it contributes (directly or transitively) to an output
+//! or to an observed value.
 
 use std::collections::HashSet;
 
@@ -60,6 +61,13 @@ impl Analysis for ElementReachability {
             }
         }
 
+        // Observed values are kept for inspection.
+        for value_id in circuit.observed_values() {
+            if values.insert(value_id) {
+                worklist.push(value_id);
+            }
+        }
+
         // Walk backwards through producers.
         while let Some(value_id) = worklist.pop() {
             let value = circuit.value(value_id)?;
@@ -68,6 +76,9 @@ impl Analysis for ElementReachability {
                 Producer::Input(input_id) => {
                     operations.insert(Operation::Input(input_id));
                 }
+                Producer::Constant(constant_id) => {
+                    operations.insert(Operation::Constant(constant_id));
+                }
                 Producer::Gate(gate_id) => {
                     operations.insert(Operation::Gate(gate_id));
                     let gate = circuit.gate_op(gate_id)?;
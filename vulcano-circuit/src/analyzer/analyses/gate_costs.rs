@@ -0,0 +1,55 @@
+//! Gate Cost Analysis
+//!
+//! Assigns a cost to each gate from the analyzer profile. Gates without
+//! measurements, or all of them when there is no profile, cost 1.
+
+use vulcano_arena::SecondaryMap;
+
+use crate::{
+    analyzer::{Analysis, Analyzer},
+    circuit::{Circuit, Operation},
+    error::Result,
+    gate::Gate,
+    handles::GateId,
+};
+
+/// Cost of gates without measurements.
+const DEFAULT_COST: f64 = 1.0;
+
+/// Result of gate cost analysis.
+pub(crate) struct GateCosts {
+    /// Cost of each gate.
+    costs: SecondaryMap<f64, GateId>,
+}
+
+impl GateCosts {
+    /// Get the cost of a gate.
+    pub(crate) fn cost(&self, gate: GateId) -> f64 {
+        self.costs.get(gate).copied().unwrap_or(DEFAULT_COST)
+    }
+
+    /// Total cost of all gates.
+    pub(crate) fn total(&self) -> f64 {
+        self.costs.values().sum()
+    }
+}
+
+impl Analysis for GateCosts {
+    type Output = Self;
+
+    fn run<G: Gate>(circuit: &Circuit<G>, analyzer: &mut Analyzer<G>) -> Result<Self::Output> {
+        let profile = analyzer.profile();
+        let costs = circuit
+            .all_gates()
+            .map(|(id, gate)| {
+                let cost = profile
+                    .and_then(|p| {
+                        p.latency(circuit.provenance(Operation::Gate(id)), gate.get_gate())
+                    })
+                    .unwrap_or(DEFAULT_COST);
+                (id, cost)
+            })
+            .collect();
+        Ok(GateCosts { costs })
+    }
+}
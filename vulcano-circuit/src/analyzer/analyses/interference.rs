@@ -0,0 +1,123 @@
+//! Interference Analysis
+//!
+//! Builds the interference graph of the circuit values: two values interfere
+//! if their live ranges overlap, so they cannot share the same wire.
+//! The graph can be queried for adjacency or exported to DOT, so external
+//! register allocators can color it.
+
+use std::{
+    collections::{BTreeSet, HashSet},
+    fmt::Write,
+};
+
+use vulcano_arena::{KeyType, SecondaryMap};
+
+use crate::{
+    analyzer::{Analysis, Analyzer, analyses::live_ranges::LiveRanges},
+    circuit::Circuit,
+    error::Result,
+    gate::Gate,
+    handles::ValueId,
+};
+
+/// Result of interference analysis.
+pub(crate) struct Interference {
+    /// Neighbors of each value.
+    adjacency: SecondaryMap<HashSet<ValueId>, ValueId>,
+    /// Number of edges.
+    edges: usize,
+}
+
+impl Interference {
+    /// Check if two values interfere.
+    pub(crate) fn interferes(&self, a: ValueId, b: ValueId) -> bool {
+        self.adjacency.get(a).is_some_and(|n| n.contains(&b))
+    }
+
+    /// Iterate over the values interfering with the given one.
+    pub(crate) fn neighbors(&self, value: ValueId) -> impl Iterator<Item = ValueId> + '_ {
+        self.adjacency.get(value).into_iter().flatten().copied()
+    }
+
+    /// Number of values interfering with the given one.
+    pub(crate) fn degree(&self, value: ValueId) -> usize {
+        self.adjacency.get(value).map_or(0, HashSet::len)
+    }
+
+    /// Iterate over all values in the graph.
+    pub(crate) fn values(&self) -> impl Iterator<Item = ValueId> + '_ {
+        self.adjacency.keys()
+    }
+
+    /// Number of values in the graph.
+    pub(crate) fn value_count(&self) -> usize {
+        self.adjacency.len()
+    }
+
+    /// Number of interference edges.
+    pub(crate) fn edge_count(&self) -> usize {
+        self.edges
+    }
+
+    /// Export the graph in DOT format, with values sorted by handle.
+    pub(crate) fn to_dot(&self) -> String {
+        let sort_key = |v: &ValueId| (v.key().index(), v.key().version());
+
+        let mut values: Vec<ValueId> = self.values().collect();
+        values.sort_by_key(sort_key);
+
+        let mut dot = String::from("graph interference {\n");
+        for &value in &values {
+            let _ = writeln!(dot, "    \"{}\";", value);
+        }
+        for &value in &values {
+            let mut neighbors: Vec<ValueId> = self
+                .neighbors(value)
+                .filter(|n| sort_key(n) > sort_key(&value))
+                .collect();
+            neighbors.sort_by_key(sort_key);
+            for neighbor in neighbors {
+                let _ = writeln!(dot, "    \"{}\" -- \"{}\";", value, neighbor);
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+impl Analysis for Interference {
+    type Output = Self;
+
+    fn run<G: Gate>(circuit: &Circuit<G>, analyzer: &mut Analyzer<G>) -> Result<Self::Output> {
+        let live_ranges = analyzer.get::<LiveRanges>(circuit)?;
+
+        let mut ranges: Vec<_> = live_ranges.iter().collect();
+        ranges.sort_by_key(|(v, r)| (r.start, v.key().index()));
+
+        let mut adjacency: SecondaryMap<HashSet<ValueId>, ValueId> =
+            ranges.iter().map(|&(v, _)| (v, HashSet::new())).collect();
+        let mut edges = 0;
+
+        // Sweep values by start of range, keeping the live ones ordered by end.
+        // Every active value started before the current one and has not ended,
+        // so it overlaps with it: each comparison yields an edge.
+        let mut active: BTreeSet<(usize, usize)> = BTreeSet::new();
+        for (idx, &(value, range)) in ranges.iter().enumerate() {
+            while let Some(&(end, _)) = active.first() {
+                if end >= range.start {
+                    break;
+                }
+                active.pop_first();
+            }
+            for &(_, other) in &active {
+                let other = ranges[other].0;
+                adjacency[value].insert(other);
+                adjacency[other].insert(value);
+                edges += 1;
+            }
+            active.insert((range.end, idx));
+        }
+
+        Ok(Interference { adjacency, edges })
+    }
+}
@@ -0,0 +1,100 @@
+//! Live Range Analysis
+//!
+//! Computes the live range of each value over the circuit schedule, the
+//! order in which compiled programs run their operations.
+//! A value is live from the operation producing it to its last consumer.
+//! Unused values are live only at their producer.
+//! Observed values are live until the last operation.
+
+use std::collections::HashMap;
+
+use vulcano_arena::SecondaryMap;
+
+use crate::{
+    analyzer::{Analysis, Analyzer},
+    circuit::{Circuit, Operation},
+    error::Result,
+    gate::Gate,
+    handles::ValueId,
+};
+
+/// Live range of a value, as inclusive positions in the schedule.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct LiveRange {
+    /// Position of the producer.
+    pub start: usize,
+    /// Position of the last consumer.
+    pub end: usize,
+}
+
+impl LiveRange {
+    /// Check if two live ranges overlap.
+    ///
+    /// Ranges are inclusive: a value consumed by an operation overlaps with the
+    /// values produced by it, so inputs and outputs of a gate never alias.
+    pub(crate) fn overlaps(&self, other: &LiveRange) -> bool {
+        self.start <= other.end && other.start <= self.end
+    }
+}
+
+/// Result of live range analysis.
+pub(crate) struct LiveRanges {
+    /// Live range of each value.
+    ranges: SecondaryMap<LiveRange, ValueId>,
+    /// Number of operations in the order.
+    length: usize,
+}
+
+impl LiveRanges {
+    /// Get the live range of a value.
+    pub(crate) fn range(&self, value: ValueId) -> Option<LiveRange> {
+        self.ranges.get(value).copied()
+    }
+
+    /// Iterate over all values and their live ranges.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (ValueId, LiveRange)> + '_ {
+        self.ranges.iter().map(|(v, &r)| (v, r))
+    }
+
+    /// Number of operations the ranges are positioned in.
+    pub(crate) fn len(&self) -> usize {
+        self.length
+    }
+
+    /// Check if there are no operations.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+}
+
+impl Analysis for LiveRanges {
+    type Output = Self;
+
+    fn run<G: Gate>(circuit: &Circuit<G>, _analyzer: &mut Analyzer<G>) -> Result<Self::Output> {
+        let position: HashMap<Operation, usize> = circuit
+            .iter_scheduled()?
+            .enumerate()
+            .map(|(idx, op)| (op, idx))
+            .collect();
+        let length = position.len();
+
+        let mut ranges = SecondaryMap::with_key();
+        for (value_id, value) in circuit.all_values() {
+            let start = position[&Operation::from(value.get_producer())];
+            let end = value
+                .get_uses()
+                .iter()
+                .map(|u| position[&Operation::from(u.consumer)])
+                .max()
+                .unwrap_or(start);
+            let end = if circuit.is_observed(value_id) {
+                end.max(length.saturating_sub(1))
+            } else {
+                end
+            };
+            ranges.insert(value_id, LiveRange { start, end });
+        }
+
+        Ok(LiveRanges { ranges, length })
+    }
+}
@@ -0,0 +1,64 @@
+//! Maximum Live Values Analysis
+//!
+//! Counts the values alive at each position of the schedule with a sweep
+//! over live range starts and ends, without building the interference graph.
+//! The maximum is the number of wires an allocation needs: no
+//! allocation can use fewer, and linear scan allocation uses exactly that
+//! many, so it answers feasibility questions without allocating.
+
+use crate::{
+    analyzer::{Analysis, Analyzer, analyses::live_ranges::LiveRanges},
+    circuit::Circuit,
+    error::Result,
+    gate::Gate,
+};
+
+/// Result of maximum live values analysis.
+pub(crate) struct MaxLiveValues {
+    /// Number of values alive at each position.
+    live: Vec<usize>,
+}
+
+impl MaxLiveValues {
+    /// Maximum number of values alive at once.
+    pub(crate) fn max_live_values(&self) -> usize {
+        self.live.iter().copied().max().unwrap_or(0)
+    }
+
+    /// First position where the maximum is reached, if there are operations.
+    pub(crate) fn peak(&self) -> Option<usize> {
+        let max = self.max_live_values();
+        self.live.iter().position(|&count| count == max)
+    }
+
+    /// Number of values alive at each position of the schedule.
+    pub(crate) fn live_counts(&self) -> &[usize] {
+        &self.live
+    }
+}
+
+impl Analysis for MaxLiveValues {
+    type Output = Self;
+
+    fn run<G: Gate>(circuit: &Circuit<G>, analyzer: &mut Analyzer<G>) -> Result<Self::Output> {
+        let ranges = analyzer.get::<LiveRanges>(circuit)?;
+
+        // Step 1. Record where each range starts and ends. Ranges are
+        // inclusive, so a value stops being alive after its end.
+        let mut delta = vec![0isize; ranges.len() + 1];
+        for (_, range) in ranges.iter() {
+            delta[range.start] += 1;
+            delta[range.end + 1] -= 1;
+        }
+
+        // Step 2. Sweep the order accumulating the changes.
+        let mut live = Vec::with_capacity(ranges.len());
+        let mut count = 0isize;
+        for change in &delta[..ranges.len()] {
+            count += change;
+            live.push(count as usize);
+        }
+
+        Ok(MaxLiveValues { live })
+    }
+}
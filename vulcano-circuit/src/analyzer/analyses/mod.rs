@@ -2,6 +2,15 @@
 //!
 //! This module contains the analysis algorithms used to analyze the circuit.
 
+pub(crate) mod balanced_partitioning;
+pub(crate) mod critical_path;
 pub(crate) mod element_reachability;
+pub(crate) mod gate_costs;
+pub(crate) mod interference;
+pub(crate) mod live_ranges;
+pub(crate) mod max_live;
 pub(crate) mod ownership_issues;
+pub(crate) mod partitioning;
 pub(crate) mod topological_order;
+pub(crate) mod use_counts;
+pub(crate) mod wire_allocation;
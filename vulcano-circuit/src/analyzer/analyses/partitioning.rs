@@ -0,0 +1,138 @@
+//! Partitioning Analysis
+//!
+//! Splits the gates and clones of a circuit into partitions of at most
+//! `MAX_SIZE` operations, for execution on separate devices.
+//!
+//! Operations are grouped by level (longest distance from the circuit
+//! inputs). Whole levels are packed into a partition while they fit, and
+//! levels larger than the bound are chunked. Since operations in a level
+//! are independent, values only flow from lower to higher partitions, so
+//! partitions can run in index order.
+//!
+//! Drops follow the partition of the value they consume, or the first
+//! partition for values produced by inputs and constants. Device placements
+//! are not taken into account.
+
+use std::collections::HashMap;
+
+use crate::{
+    analyzer::{Analysis, Analyzer},
+    circuit::{Circuit, Operation, Producer},
+    error::Result,
+    gate::Gate,
+    placement::Device,
+};
+
+/// Result of partitioning analysis with at most `MAX_SIZE` operations per partition.
+pub(crate) struct Partitioning<const MAX_SIZE: usize> {
+    /// Partition of each gate, clone and drop.
+    assignment: HashMap<Operation, usize>,
+    /// Number of partitions.
+    count: usize,
+}
+
+/// Assignment of the gates, clones and drops of a circuit to partitions.
+pub(crate) trait PartitionMap {
+    /// Get the partition of an operation.
+    ///
+    /// Inputs, constants and outputs are not assigned to partitions.
+    fn partition(&self, op: Operation) -> Option<usize>;
+
+    /// Number of partitions.
+    fn partition_count(&self) -> usize;
+
+    /// Get the device a partition must run on, if it holds pinned operations.
+    fn device(&self, _partition: usize) -> Option<Device> {
+        None
+    }
+}
+
+impl<const MAX_SIZE: usize> PartitionMap for Partitioning<MAX_SIZE> {
+    fn partition(&self, op: Operation) -> Option<usize> {
+        self.assignment.get(&op).copied()
+    }
+
+    fn partition_count(&self) -> usize {
+        self.count
+    }
+}
+
+impl<const MAX_SIZE: usize> Partitioning<MAX_SIZE> {
+    /// Iterate over the operations assigned to a partition.
+    pub(crate) fn operations(&self, partition: usize) -> impl Iterator<Item = Operation> + '_ {
+        self.assignment
+            .iter()
+            .filter(move |&(_, &p)| p == partition)
+            .map(|(&op, _)| op)
+    }
+}
+
+impl<const MAX_SIZE: usize> Analysis for Partitioning<MAX_SIZE> {
+    type Output = Self;
+
+    fn run<G: Gate>(circuit: &Circuit<G>, _analyzer: &mut Analyzer<G>) -> Result<Self::Output> {
+        let max_size = MAX_SIZE.max(1);
+
+        // Step 1. Compute the level of gates and clones in schedule order.
+        let mut levels: HashMap<Operation, usize> = HashMap::new();
+        let mut by_level: Vec<Vec<Operation>> = Vec::new();
+        for op in circuit.iter_scheduled()? {
+            let inputs = match op {
+                Operation::Gate(id) => circuit.gate_op(id)?.get_inputs().to_vec(),
+                Operation::Clone(id) => Vec::from([circuit.clone_op(id)?.get_input()]),
+                _ => continue,
+            };
+            let mut level = 0;
+            for value in inputs {
+                let producer = Operation::from(circuit.value(value)?.get_producer());
+                if let Some(&l) = levels.get(&producer) {
+                    level = level.max(l + 1);
+                }
+            }
+            levels.insert(op, level);
+            if by_level.len() <= level {
+                by_level.resize_with(level + 1, Vec::new);
+            }
+            by_level[level].push(op);
+        }
+
+        // Step 2. Pack levels into bounded partitions.
+        let mut assignment = HashMap::new();
+        let mut current = 0;
+        let mut size = 0;
+        for level in by_level {
+            if size > 0 && size + level.len() > max_size {
+                current += 1;
+                size = 0;
+            }
+            for op in level {
+                if size == max_size {
+                    current += 1;
+                    size = 0;
+                }
+                assignment.insert(op, current);
+                size += 1;
+            }
+        }
+        let count = if assignment.is_empty() {
+            0
+        } else {
+            current + 1
+        };
+
+        // Step 3. Drops go with the producer of their value.
+        for (drop_id, drop) in circuit.all_drops() {
+            let partition = match circuit.value(drop.get_input())?.get_producer() {
+                Producer::Gate(id) => assignment[&Operation::Gate(id)],
+                Producer::Clone(id) => assignment[&Operation::Clone(id)],
+                Producer::Input(_) | Producer::Constant(_) => 0,
+            };
+            assignment.insert(Operation::Drop(drop_id), partition);
+        }
+
+        Ok(Partitioning {
+            assignment,
+            count: count.max(usize::from(circuit.drop_count() > 0)),
+        })
+    }
+}
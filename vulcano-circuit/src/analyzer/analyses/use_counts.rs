@@ -0,0 +1,99 @@
+//! Use Count Analysis
+//!
+//! Counts how many times each value is used, and by how many distinct
+//! operations the outputs of each gate are consumed (fan-out).
+//! Passes use it to find single-use values that can be rewritten in place.
+
+use std::collections::HashSet;
+
+use vulcano_arena::SecondaryMap;
+
+use crate::{
+    analyzer::{Analysis, Analyzer},
+    circuit::{Circuit, Operation},
+    error::Result,
+    gate::Gate,
+    handles::{GateId, Ownership, ValueId},
+};
+
+/// Use counts of a value.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct ValueUses {
+    /// Number of borrowing uses.
+    pub borrows: usize,
+    /// Number of moving uses.
+    pub moves: usize,
+}
+
+impl ValueUses {
+    /// Total number of uses.
+    pub(crate) fn total(&self) -> usize {
+        self.borrows + self.moves
+    }
+}
+
+/// Result of use count analysis.
+pub(crate) struct UseCounts {
+    /// Use counts of each value.
+    values: SecondaryMap<ValueUses, ValueId>,
+    /// Number of distinct operations consuming the outputs of each gate.
+    gates: SecondaryMap<usize, GateId>,
+}
+
+impl UseCounts {
+    /// Get the use counts of a value.
+    pub(crate) fn value_uses(&self, value: ValueId) -> ValueUses {
+        self.values.get(value).copied().unwrap_or_default()
+    }
+
+    /// Get the number of uses of a value.
+    pub(crate) fn use_count(&self, value: ValueId) -> usize {
+        self.value_uses(value).total()
+    }
+
+    /// Check if a value is used exactly once.
+    pub(crate) fn is_single_use(&self, value: ValueId) -> bool {
+        self.use_count(value) == 1
+    }
+
+    /// Check if a value is never used.
+    pub(crate) fn is_unused(&self, value: ValueId) -> bool {
+        self.use_count(value) == 0
+    }
+
+    /// Get the number of distinct operations consuming the outputs of a gate.
+    pub(crate) fn gate_fan_out(&self, gate: GateId) -> usize {
+        self.gates.get(gate).copied().unwrap_or(0)
+    }
+}
+
+impl Analysis for UseCounts {
+    type Output = Self;
+
+    fn run<G: Gate>(circuit: &Circuit<G>, _analyzer: &mut Analyzer<G>) -> Result<Self::Output> {
+        let mut values = SecondaryMap::with_key();
+        for (value_id, value) in circuit.all_values() {
+            let mut uses = ValueUses::default();
+            for usage in value.get_uses() {
+                match usage.mode {
+                    Ownership::Borrow => uses.borrows += 1,
+                    Ownership::Move => uses.moves += 1,
+                }
+            }
+            values.insert(value_id, uses);
+        }
+
+        let mut gates = SecondaryMap::with_key();
+        for (gate_id, gate) in circuit.all_gates() {
+            let mut consumers = HashSet::new();
+            for &output in gate.get_outputs() {
+                for usage in circuit.value(output)?.get_uses() {
+                    consumers.insert(Operation::from(usage.consumer));
+                }
+            }
+            gates.insert(gate_id, consumers.len());
+        }
+
+        Ok(UseCounts { values, gates })
+    }
+}
@@ -0,0 +1,286 @@
+//! Wire Allocation Analysis
+//!
+//! Assigns a wire (storage slot) to each value so that values alive at the
+//! same time never share a wire. The assignment is computed by a pluggable
+//! strategy, selected through the type parameter of the analysis, so each
+//! strategy result is cached independently.
+//!
+//! Available strategies:
+//! - [`Ssa`]: one wire per value, no reuse.
+//! - [`Greedy`]: degree-ordered coloring of the interference graph.
+//! - [`LinearScan`]: interval scan over live ranges, optimal on them.
+//! - [`Chaitin`]: simplify-select coloring with a wire budget, reporting the
+//!   values it would spill as hints.
+
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet},
+    marker::PhantomData,
+};
+
+use vulcano_arena::{KeyType, SecondaryMap};
+
+use crate::{
+    analyzer::{
+        Analysis, Analyzer,
+        analyses::{interference::Interference, live_ranges::LiveRanges},
+    },
+    circuit::Circuit,
+    error::{Error, Result},
+    gate::Gate,
+    handles::ValueId,
+};
+
+/// Wires assigned to values by a strategy.
+#[derive(Default)]
+pub(crate) struct WireAssignment {
+    /// Wire of each value.
+    pub wires: SecondaryMap<usize, ValueId>,
+    /// Values the strategy suggests to spill.
+    pub spill_hints: Vec<ValueId>,
+}
+
+/// A wire allocation strategy.
+pub(crate) trait AllocationStrategy: 'static {
+    /// Assign wires to the values of the circuit.
+    fn allocate<G: Gate>(
+        circuit: &Circuit<G>,
+        analyzer: &mut Analyzer<G>,
+    ) -> Result<WireAssignment>;
+}
+
+/// Result of wire allocation analysis using strategy `S`.
+pub(crate) struct WireAllocation<S: AllocationStrategy> {
+    /// Wire of each value.
+    wires: SecondaryMap<usize, ValueId>,
+    /// Number of wires used.
+    wire_count: usize,
+    /// Values the strategy suggests to spill.
+    spill_hints: Vec<ValueId>,
+    /// Strategy used.
+    _strategy: PhantomData<fn() -> S>,
+}
+
+impl<S: AllocationStrategy> WireAllocation<S> {
+    /// Get the wire assigned to a value.
+    pub(crate) fn wire(&self, value: ValueId) -> Option<usize> {
+        self.wires.get(value).copied()
+    }
+
+    /// Number of wires used.
+    pub(crate) fn wire_count(&self) -> usize {
+        self.wire_count
+    }
+
+    /// Values the strategy suggests to spill to reduce the wire count.
+    pub(crate) fn spill_hints(&self) -> &[ValueId] {
+        &self.spill_hints
+    }
+
+    /// Check that no value is read after its wire was overwritten.
+    ///
+    /// Simulates which value each wire holds along the circuit schedule,
+    /// independently of how the strategy chose the wires, so it catches
+    /// faulty custom strategies. Observed values must survive to the end.
+    pub(crate) fn verify<G: Gate>(&self, circuit: &Circuit<G>) -> Result<()> {
+        let wire = |value: ValueId| self.wire(value).ok_or(Error::UnallocatedValue(value));
+        let mut holds: HashMap<usize, ValueId> = HashMap::new();
+        let read = |holds: &HashMap<usize, ValueId>, value: ValueId| -> Result<()> {
+            let wire = wire(value)?;
+            match holds.get(&wire) {
+                Some(&held) if held == value => Ok(()),
+                _ => Err(Error::WireOverwritten { value, wire }),
+            }
+        };
+
+        for op in circuit.iter_scheduled()? {
+            let at = |e: Error| e.with_context(circuit.describe(op));
+            for value in circuit.consumed_values(op)? {
+                read(&holds, value).map_err(at)?;
+            }
+            for value in circuit.produced_values(op) {
+                holds.insert(wire(value).map_err(at)?, value);
+            }
+        }
+        for value in circuit.observed_values() {
+            read(&holds, value).map_err(|e| {
+                e.with_context(format!("observing {}", circuit.describe_value(value)))
+            })?;
+        }
+        Ok(())
+    }
+}
+
+impl<S: AllocationStrategy> Analysis for WireAllocation<S> {
+    type Output = Self;
+
+    fn run<G: Gate>(circuit: &Circuit<G>, analyzer: &mut Analyzer<G>) -> Result<Self::Output> {
+        let WireAssignment { wires, spill_hints } = S::allocate(circuit, analyzer)?;
+        let wire_count = wires.values().max().map_or(0, |&w| w + 1);
+        Ok(WireAllocation {
+            wires,
+            wire_count,
+            spill_hints,
+            _strategy: PhantomData,
+        })
+    }
+}
+
+/// Order values by handle, so allocations are reproducible.
+fn handle_order(value: &ValueId) -> usize {
+    value.key().index()
+}
+
+/// Assign each value the lowest wire not used by its allocated neighbors.
+fn select_wires(
+    interference: &Interference,
+    order: impl IntoIterator<Item = ValueId>,
+) -> SecondaryMap<usize, ValueId> {
+    let mut wires = SecondaryMap::with_key();
+    for value in order {
+        let taken: HashSet<usize> = interference
+            .neighbors(value)
+            .filter_map(|n| wires.get(n).copied())
+            .collect();
+        let wire = (0..).find(|w| !taken.contains(w)).unwrap_or_default();
+        wires.insert(value, wire);
+    }
+    wires
+}
+
+/// One wire per value.
+pub(crate) struct Ssa;
+
+impl AllocationStrategy for Ssa {
+    fn allocate<G: Gate>(
+        circuit: &Circuit<G>,
+        _analyzer: &mut Analyzer<G>,
+    ) -> Result<WireAssignment> {
+        let mut values: Vec<ValueId> = circuit.all_values().map(|(id, _)| id).collect();
+        values.sort_by_key(handle_order);
+        Ok(WireAssignment {
+            wires: values
+                .into_iter()
+                .enumerate()
+                .map(|(w, v)| (v, w))
+                .collect(),
+            spill_hints: Vec::new(),
+        })
+    }
+}
+
+/// Greedy coloring of the interference graph, highest degree first.
+pub(crate) struct Greedy;
+
+impl AllocationStrategy for Greedy {
+    fn allocate<G: Gate>(
+        circuit: &Circuit<G>,
+        analyzer: &mut Analyzer<G>,
+    ) -> Result<WireAssignment> {
+        let interference = analyzer.get::<Interference>(circuit)?;
+        let mut values: Vec<ValueId> = interference.values().collect();
+        values.sort_by_key(|v| (Reverse(interference.degree(*v)), handle_order(v)));
+        Ok(WireAssignment {
+            wires: select_wires(&interference, values),
+            spill_hints: Vec::new(),
+        })
+    }
+}
+
+/// Linear scan over live ranges, reusing the lowest freed wire.
+pub(crate) struct LinearScan;
+
+impl AllocationStrategy for LinearScan {
+    fn allocate<G: Gate>(
+        circuit: &Circuit<G>,
+        analyzer: &mut Analyzer<G>,
+    ) -> Result<WireAssignment> {
+        let live_ranges = analyzer.get::<LiveRanges>(circuit)?;
+        let mut ranges: Vec<_> = live_ranges.iter().collect();
+        ranges.sort_by_key(|(v, r)| (r.start, handle_order(v)));
+
+        let mut wires = SecondaryMap::with_capacity_and_key(ranges.len());
+        // Active values by end of range, and free wires, both as min-heaps.
+        let mut active: BinaryHeap<Reverse<(usize, usize)>> = BinaryHeap::new();
+        let mut free: BinaryHeap<Reverse<usize>> = BinaryHeap::new();
+        let mut next_wire = 0;
+
+        for (value, range) in ranges {
+            // Ranges are inclusive, so only ranges ending strictly before are expired.
+            while let Some(&Reverse((end, wire))) = active.peek() {
+                if end >= range.start {
+                    break;
+                }
+                active.pop();
+                free.push(Reverse(wire));
+            }
+            let wire = match free.pop() {
+                Some(Reverse(wire)) => wire,
+                None => {
+                    next_wire += 1;
+                    next_wire - 1
+                }
+            };
+            active.push(Reverse((range.end, wire)));
+            wires.insert(value, wire);
+        }
+
+        Ok(WireAssignment {
+            wires,
+            spill_hints: Vec::new(),
+        })
+    }
+}
+
+/// Chaitin-style simplify-select coloring with a budget of `WIRES` wires.
+///
+/// When no value can be simplified within the budget, the value with the
+/// highest degree is reported as a spill hint and optimistically colored.
+pub(crate) struct Chaitin<const WIRES: usize>;
+
+impl<const WIRES: usize> AllocationStrategy for Chaitin<WIRES> {
+    fn allocate<G: Gate>(
+        circuit: &Circuit<G>,
+        analyzer: &mut Analyzer<G>,
+    ) -> Result<WireAssignment> {
+        let interference = analyzer.get::<Interference>(circuit)?;
+
+        let mut degrees: SecondaryMap<usize, ValueId> = interference
+            .values()
+            .map(|v| (v, interference.degree(v)))
+            .collect();
+        let mut remaining: Vec<ValueId> = degrees.keys().collect();
+        remaining.sort_by_key(handle_order);
+
+        let mut stack = Vec::with_capacity(remaining.len());
+        let mut spill_hints = Vec::new();
+
+        // Simplify: remove values with fewer neighbors than wires first.
+        while !remaining.is_empty() {
+            let pick = match remaining.iter().position(|&v| degrees[v] < WIRES) {
+                Some(pos) => pos,
+                None => {
+                    let pos = (0..remaining.len())
+                        .max_by_key(|&i| (degrees[remaining[i]], Reverse(i)))
+                        .unwrap_or_default();
+                    spill_hints.push(remaining[pos]);
+                    pos
+                }
+            };
+            let value = remaining.remove(pick);
+            for neighbor in interference.neighbors(value) {
+                if let Some(degree) = degrees.get_mut(neighbor) {
+                    *degree = degree.saturating_sub(1);
+                }
+            }
+            degrees.remove(value);
+            stack.push(value);
+        }
+
+        // Select: color in reverse removal order.
+        Ok(WireAssignment {
+            wires: select_wires(&interference, stack.into_iter().rev()),
+            spill_hints,
+        })
+    }
+}
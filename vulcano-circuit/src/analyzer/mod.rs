@@ -2,6 +2,8 @@
 //!
 //! This module provides a framework for running analyses on circuits.
 //! Analyses are computed on-demand and cached for efficiency.
+//! Results are shared through `Arc`, so they can be sent to and read from
+//! other threads.
 
 pub(super) mod analyses;
 
@@ -9,26 +11,37 @@ use crate::{
     circuit::Circuit,
     error::{Error, Result},
     gate::Gate,
+    optimizer::budget::OptimizeBudget,
+    profile::Profile,
 };
 use std::{
     any::{Any, TypeId},
     collections::HashMap,
-    rc::Rc,
+    sync::Arc,
 };
 
 /// Trait for analyses that can be performed on circuits.
 pub(super) trait Analysis: 'static {
     /// The output type of the analysis.
-    type Output;
+    type Output: Send + Sync;
 
     /// Run the analysis on the given circuit.
     fn run<T: Gate>(circuit: &Circuit<T>, analyzer: &mut Analyzer<T>) -> Result<Self::Output>;
 }
 
+/// Cost of sending a value of the given type between devices.
+pub(super) type TransferCost<O> = Arc<dyn Fn(O) -> f64 + Send + Sync>;
+
 /// Manages and caches analyses on circuits.
 pub(super) struct Analyzer<T: Gate> {
     /// Cache mapping TypeId of analyses to their results.
-    cache: HashMap<TypeId, Rc<dyn Any>>,
+    cache: HashMap<TypeId, Arc<dyn Any + Send + Sync>>,
+    /// Measured gate latencies used as cost model, if any.
+    profile: Option<Profile>,
+    /// Cost of moving values between devices, if not uniform.
+    transfer_cost: Option<TransferCost<T::Operand>>,
+    /// Resources the running optimization may use, if limited.
+    budget: Option<OptimizeBudget>,
     /// Phantom data for the gate type.
     _marker: std::marker::PhantomData<T>,
 }
@@ -38,12 +51,69 @@ impl<T: Gate> Analyzer<T> {
     pub(super) fn new() -> Self {
         Self {
             cache: HashMap::new(),
+            profile: None,
+            transfer_cost: None,
+            budget: None,
             _marker: std::marker::PhantomData,
         }
     }
 
+    /// Set the profile used as cost model, invalidating all cached analyses.
+    pub(super) fn set_profile(&mut self, profile: Profile) {
+        self.profile = Some(profile);
+        self.invalidate_all();
+    }
+
+    /// Get the profile used as cost model.
+    pub(super) fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    /// Set the cost of moving a value of each type between devices,
+    /// invalidating all cached analyses.
+    ///
+    /// Without it, every value costs 1 to move.
+    pub(super) fn set_transfer_cost(
+        &mut self,
+        cost: impl Fn(T::Operand) -> f64 + Send + Sync + 'static,
+    ) {
+        self.transfer_cost = Some(Arc::new(cost));
+        self.invalidate_all();
+    }
+
+    /// Get the cost of moving a value of the given type between devices.
+    pub(super) fn transfer_cost(&self, operand: T::Operand) -> f64 {
+        self.transfer_cost
+            .as_ref()
+            .map_or(1.0, |cost| cost(operand))
+    }
+
+    /// Set the budget of optimizations using this analyzer.
+    pub(super) fn set_budget(&mut self, budget: OptimizeBudget) {
+        self.budget = Some(budget);
+    }
+
+    /// Get the budget of optimizations using this analyzer.
+    pub(super) fn budget(&self) -> Option<&OptimizeBudget> {
+        self.budget.as_ref()
+    }
+
+    /// Start measuring the budget, if any, from the given circuit.
+    pub(super) fn start_budget(&mut self, circuit: &Circuit<T>) {
+        if let Some(budget) = &mut self.budget {
+            budget.start(circuit);
+        }
+    }
+
+    /// Check if the budget, if any, is exhausted for the given circuit.
+    ///
+    /// Passes call this to stop early, leaving the circuit consistent.
+    pub(super) fn budget_exhausted(&self, circuit: &Circuit<T>) -> bool {
+        self.budget.as_ref().is_some_and(|b| b.exhausted(circuit))
+    }
+
     /// Get the result of an analysis, computing and caching it if necessary.
-    pub(super) fn get<A>(&mut self, circuit: &Circuit<T>) -> Result<Rc<A::Output>>
+    pub(super) fn get<A>(&mut self, circuit: &Circuit<T>) -> Result<Arc<A::Output>>
     where
         A: Analysis,
     {
@@ -56,10 +126,48 @@ impl<T: Gate> Analyzer<T> {
                 .map_err(|_| Error::AnalysisCacheTypeMismatch(key));
         }
 
-        let result = A::run(circuit, self)?;
-        let rc = Rc::new(result);
-        self.cache.insert(key, rc.clone());
-        Ok(rc)
+        let result = A::run(circuit, self).map_err(|e| {
+            e.with_context(format!("running analysis {}", std::any::type_name::<A>()))
+        })?;
+        let shared = Arc::new(result);
+        self.cache.insert(key, shared.clone());
+        Ok(shared)
+    }
+
+    /// Get the results of several analyses at once, computing the missing
+    /// ones concurrently when the `parallel` feature is enabled.
+    ///
+    /// Analyses are given as a tuple, such as `(LiveRanges, UseCounts)`.
+    pub(super) fn get_many<S>(&mut self, circuit: &Circuit<T>) -> Result<S::Output>
+    where
+        S: AnalysisSet<T>,
+    {
+        S::compute(self, circuit)
+    }
+
+    /// Create an analyzer sharing the cached results and configuration.
+    fn fork(&self) -> Self {
+        Self {
+            cache: self.cache.clone(),
+            profile: self.profile.clone(),
+            transfer_cost: self.transfer_cost.clone(),
+            budget: self.budget.clone(),
+            _marker: std::marker::PhantomData,
+        }
+    }
+
+    /// Add the results cached by a fork that are missing here.
+    fn absorb(&mut self, fork: Self) {
+        for (key, result) in fork.cache {
+            self.cache.entry(key).or_insert(result);
+        }
+    }
+
+    /// Get the TypeIds of the cached analyses.
+    ///
+    /// Passes that leave the circuit untouched return these as preserved.
+    pub(super) fn cached_analyses(&self) -> Vec<TypeId> {
+        self.cache.keys().copied().collect()
     }
 
     /// Invalidate all cached analyses.
@@ -78,3 +186,60 @@ impl<T: Gate> Default for Analyzer<T> {
         Self::new()
     }
 }
+
+/// A tuple of analyses computed together by [`Analyzer::get_many`].
+pub(super) trait AnalysisSet<T: Gate> {
+    /// Tuple of the results of the analyses.
+    type Output;
+
+    /// Compute the analyses, caching their results in the analyzer.
+    fn compute(analyzer: &mut Analyzer<T>, circuit: &Circuit<T>) -> Result<Self::Output>;
+}
+
+/// Implement [`AnalysisSet`] for a tuple of analyses.
+///
+/// With the `parallel` feature each analysis runs on a fork of the analyzer
+/// in the rayon pool, and the results of every fork, dependencies included,
+/// are merged back. Analyses both forks need may be computed twice.
+macro_rules! impl_analysis_set {
+    ($($analysis:ident $slot:ident),+) => {
+        impl<T, $($analysis),+> AnalysisSet<T> for ($($analysis,)+)
+        where
+            T: Gate,
+            Circuit<T>: Sync,
+            Analyzer<T>: Send,
+            $($analysis: Analysis,)+
+        {
+            type Output = ($(Arc<$analysis::Output>,)+);
+
+            #[cfg(not(feature = "parallel"))]
+            fn compute(analyzer: &mut Analyzer<T>, circuit: &Circuit<T>) -> Result<Self::Output> {
+                Ok(($(analyzer.get::<$analysis>(circuit)?,)+))
+            }
+
+            #[cfg(feature = "parallel")]
+            fn compute(analyzer: &mut Analyzer<T>, circuit: &Circuit<T>) -> Result<Self::Output> {
+                $(let mut $slot = (analyzer.fork(), None);)+
+                rayon::scope(|scope| {
+                    $(
+                        let $slot = &mut $slot;
+                        scope.spawn(move |_| $slot.1 = Some($slot.0.get::<$analysis>(circuit)));
+                    )+
+                });
+                $(
+                    let (fork, result) = $slot;
+                    result.ok_or(Error::AnalysisCacheInconsistentEntry(TypeId::of::<$analysis>()))??;
+                    analyzer.absorb(fork);
+                )+
+                // Read back from the cache, so results computed by several
+                // forks resolve to the one kept.
+                Ok(($(analyzer.get::<$analysis>(circuit)?,)+))
+            }
+        }
+    };
+}
+
+impl_analysis_set!(A a);
+impl_analysis_set!(A a, B b);
+impl_analysis_set!(A a, B b, C c);
+impl_analysis_set!(A a, B b, C c, D d);
@@ -0,0 +1,112 @@
+//! Operation annotations
+//!
+//! This module provides a side-table to attach arbitrary data to circuit
+//! operations (levels, scales, user tags, source locations...).
+//! Annotations are keyed by operation and by annotation type, so several
+//! independent annotations can coexist on the same operation.
+//!
+//! Passes must either preserve annotations or explicitly invalidate them.
+//! Removing an operation from a circuit drops all of its annotations.
+
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+};
+
+use crate::circuit::Operation;
+
+/// Side-table of typed annotations attached to circuit operations.
+#[derive(Default)]
+pub(super) struct Annotations {
+    /// Annotations of each operation, indexed by annotation type.
+    entries: HashMap<Operation, HashMap<TypeId, Box<dyn Any + Send + Sync>>>,
+}
+
+impl Annotations {
+    /// Create a new empty annotation table.
+    pub(super) fn new() -> Self {
+        Self {
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Attach an annotation to an operation, returning the previous one of the same type.
+    pub(super) fn insert<A: Any + Send + Sync>(
+        &mut self,
+        op: Operation,
+        annotation: A,
+    ) -> Option<A> {
+        self.entries
+            .entry(op)
+            .or_default()
+            .insert(TypeId::of::<A>(), Box::new(annotation))
+            .and_then(|old| old.downcast().ok())
+            .map(|old| *old)
+    }
+
+    /// Get the annotation of the given type attached to an operation.
+    pub(super) fn get<A: Any>(&self, op: Operation) -> Option<&A> {
+        self.entries
+            .get(&op)?
+            .get(&TypeId::of::<A>())?
+            .downcast_ref()
+    }
+
+    /// Get a mutable reference to the annotation of the given type attached to an operation.
+    pub(super) fn get_mut<A: Any>(&mut self, op: Operation) -> Option<&mut A> {
+        self.entries
+            .get_mut(&op)?
+            .get_mut(&TypeId::of::<A>())?
+            .downcast_mut()
+    }
+
+    /// Remove the annotation of the given type from an operation.
+    pub(super) fn remove<A: Any>(&mut self, op: Operation) -> Option<A> {
+        let annotations = self.entries.get_mut(&op)?;
+        let removed = annotations.remove(&TypeId::of::<A>());
+        if annotations.is_empty() {
+            self.entries.remove(&op);
+        }
+        removed.and_then(|a| a.downcast().ok()).map(|a| *a)
+    }
+
+    /// Remove all annotations attached to an operation.
+    pub(super) fn clear_operation(&mut self, op: Operation) {
+        self.entries.remove(&op);
+    }
+
+    /// Remove every annotation of the given type from all operations.
+    pub(super) fn invalidate<A: Any>(&mut self) {
+        let key = TypeId::of::<A>();
+        self.entries.retain(|_, annotations| {
+            annotations.remove(&key);
+            !annotations.is_empty()
+        });
+    }
+
+    /// Move all annotations from one operation to another.
+    ///
+    /// Annotations already present on the target are overwritten by the ones
+    /// of the same type coming from the source.
+    pub(super) fn transfer(&mut self, from: Operation, to: Operation) {
+        if from == to {
+            return;
+        }
+        if let Some(moved) = self.entries.remove(&from) {
+            self.entries.entry(to).or_default().extend(moved);
+        }
+    }
+
+    /// Iterate over all operations carrying an annotation of the given type.
+    pub(super) fn iter<A: Any>(&self) -> impl Iterator<Item = (Operation, &A)> {
+        let key = TypeId::of::<A>();
+        self.entries.iter().filter_map(move |(op, annotations)| {
+            Some((*op, annotations.get(&key)?.downcast_ref()?))
+        })
+    }
+
+    /// Returns true if no operation carries any annotation.
+    pub(super) fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
@@ -0,0 +1,289 @@
+//! Benchmark helpers
+//!
+//! This module exposes the compilation stages to benchmarks, so that their
+//! cost can be tracked across releases. Stages run on synthetic circuits of
+//! configurable size, generated deterministically from a seed.
+//!
+//! Synthetic circuits mix additions and multiplications that borrow their
+//! inputs with negations that consume them. Gates read recent values, which
+//! keeps live ranges short as in real workloads, and every value left unused
+//! becomes a circuit output.
+
+use std::time::{Duration, Instant};
+
+use crate::{
+    analyzer::{
+        Analyzer,
+        analyses::{
+            critical_path::CriticalPath,
+            live_ranges::LiveRanges,
+            topological_order::TopologicalOrder,
+            wire_allocation::{LinearScan, WireAllocation},
+        },
+    },
+    circuit::Circuit,
+    error::{Error, Result},
+    gate::Gate,
+    handles::{Ownership, ValueId},
+    optimizer::{
+        OptimizerPass,
+        passes::{
+            constant_folding::constant_folding, dead_code_elimination::dead_code_elimination,
+            reconcile_ownership::reconcile_ownership,
+        },
+    },
+};
+
+/// Number of circuit inputs of synthetic circuits.
+const INPUTS: usize = 16;
+
+/// Number of constants of synthetic circuits.
+const CONSTANTS: usize = 4;
+
+/// Number of most recent values gates read from.
+const WINDOW: usize = 32;
+
+/// Gates of synthetic circuits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SyntheticGate {
+    /// Adds two borrowed operands.
+    Add,
+    /// Multiplies two borrowed operands.
+    Mul,
+    /// Negates a consumed operand.
+    Neg,
+}
+
+impl Gate for SyntheticGate {
+    fn input_count(&self) -> usize {
+        match self {
+            SyntheticGate::Add | SyntheticGate::Mul => 2,
+            SyntheticGate::Neg => 1,
+        }
+    }
+
+    fn output_count(&self) -> usize {
+        1
+    }
+
+    type Operand = ();
+
+    type Constant = i64;
+
+    fn input_type(&self, idx: usize) -> Result<Self::Operand> {
+        if idx < self.input_count() {
+            Ok(())
+        } else {
+            Err(Error::InvalidInputIndex {
+                idx,
+                max: self.input_count(),
+            })
+        }
+    }
+
+    fn output_type(&self, idx: usize) -> Result<Self::Operand> {
+        if idx < self.output_count() {
+            Ok(())
+        } else {
+            Err(Error::InvalidOutputIndex {
+                idx,
+                max: self.output_count(),
+            })
+        }
+    }
+
+    fn access_mode(&self, idx: usize) -> Result<Ownership> {
+        self.input_type(idx)?;
+        Ok(match self {
+            SyntheticGate::Add | SyntheticGate::Mul => Ownership::Borrow,
+            SyntheticGate::Neg => Ownership::Move,
+        })
+    }
+
+    fn fold(&self, inputs: &[i64]) -> Option<Vec<i64>> {
+        match (self, inputs) {
+            (SyntheticGate::Add, [a, b]) => Some(vec![a.wrapping_add(*b)]),
+            (SyntheticGate::Mul, [a, b]) => Some(vec![a.wrapping_mul(*b)]),
+            (SyntheticGate::Neg, [a]) => Some(vec![a.wrapping_neg()]),
+            _ => None,
+        }
+    }
+}
+
+/// A compilation stage that can be benchmarked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// Generate the circuit and reconcile ownership.
+    Build,
+    /// Topological order analysis.
+    TopologicalOrder,
+    /// Live range analysis, over the schedule cached by the circuit.
+    LiveRanges,
+    /// Linear scan wire allocation, including the analyses it depends on.
+    WireAllocation,
+    /// Critical path analysis, including the analyses it depends on.
+    CriticalPath,
+    /// Deterministic schedule of the circuit.
+    Schedule,
+    /// Ownership reconciliation pass.
+    ReconcileOwnership,
+    /// Dead code elimination pass.
+    DeadCodeElimination,
+    /// Constant folding pass.
+    ConstantFolding,
+}
+
+/// A synthetic circuit to run stages on.
+pub struct Workload {
+    /// Number of gates.
+    gates: usize,
+    /// Seed the circuit was generated from.
+    seed: u64,
+    /// The circuit stages run on.
+    circuit: Circuit<SyntheticGate>,
+}
+
+impl Workload {
+    /// Generate a synthetic circuit with the given number of gates.
+    pub fn synthetic(gates: usize, seed: u64) -> Self {
+        Self {
+            gates,
+            seed,
+            circuit: generate(gates, seed),
+        }
+    }
+
+    /// Number of gates of the circuit.
+    pub fn gate_count(&self) -> usize {
+        self.circuit.gate_count()
+    }
+
+    /// Run a stage.
+    ///
+    /// Analyses run on a fresh analyzer, so nothing is cached between runs.
+    /// Passes replace the circuit with their result, so measuring a pass more
+    /// than once should use a fresh workload each time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the stage fails, which synthetic circuits never trigger.
+    pub fn run(&mut self, stage: Stage) {
+        if let Err(error) = self.try_run(stage) {
+            panic!("benchmark stage {:?} failed: {}", stage, error);
+        }
+    }
+
+    /// Run a stage and measure how long it took.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the stage fails, which synthetic circuits never trigger.
+    pub fn time(&mut self, stage: Stage) -> Duration {
+        let start = Instant::now();
+        self.run(stage);
+        start.elapsed()
+    }
+
+    /// Run a stage, returning its error if any.
+    fn try_run(&mut self, stage: Stage) -> Result<()> {
+        let mut analyzer = Analyzer::new();
+        match stage {
+            Stage::Build => {
+                let circuit = generate(self.gates, self.seed);
+                self.circuit = reconcile_ownership(circuit, &mut analyzer)?.0;
+            }
+            Stage::TopologicalOrder => {
+                analyzer.get::<TopologicalOrder>(&self.circuit)?;
+            }
+            Stage::LiveRanges => {
+                analyzer.get::<LiveRanges>(&self.circuit)?;
+            }
+            Stage::WireAllocation => {
+                analyzer.get::<WireAllocation<LinearScan>>(&self.circuit)?;
+            }
+            Stage::CriticalPath => {
+                analyzer.get::<CriticalPath>(&self.circuit)?;
+            }
+            Stage::Schedule => {
+                self.circuit.compute_schedule()?;
+            }
+            Stage::ReconcileOwnership => self.apply(reconcile_ownership, &mut analyzer)?,
+            Stage::DeadCodeElimination => self.apply(dead_code_elimination, &mut analyzer)?,
+            Stage::ConstantFolding => self.apply(constant_folding, &mut analyzer)?,
+        }
+        Ok(())
+    }
+
+    /// Run a pass, replacing the circuit with its result.
+    fn apply(
+        &mut self,
+        pass: OptimizerPass<SyntheticGate>,
+        analyzer: &mut Analyzer<SyntheticGate>,
+    ) -> Result<()> {
+        let circuit = std::mem::take(&mut self.circuit);
+        self.circuit = pass(circuit, analyzer)?.0;
+        Ok(())
+    }
+}
+
+/// Generate a synthetic circuit.
+fn generate(gates: usize, seed: u64) -> Circuit<SyntheticGate> {
+    let mut rng = SplitMix64(seed);
+    let mut circuit = Circuit::new();
+    // Values that can still be read: negations consume their input.
+    let mut readable: Vec<ValueId> = Vec::with_capacity(INPUTS + CONSTANTS + gates);
+    for _ in 0..INPUTS {
+        readable.push(circuit.add_input(()).1);
+    }
+    for value in 0..CONSTANTS {
+        readable.push(circuit.add_constant(value as i64 + 1, ()).1);
+    }
+
+    let mut produced = Vec::with_capacity(gates);
+    for _ in 0..gates {
+        let kind = rng.next() % 4;
+        let first = rng.recent(readable.len());
+        let (gate, inputs) = match kind {
+            0 => (SyntheticGate::Neg, vec![readable.remove(first)]),
+            kind => {
+                let second = readable[rng.recent(readable.len())];
+                let gate = match kind {
+                    1 => SyntheticGate::Mul,
+                    _ => SyntheticGate::Add,
+                };
+                (gate, vec![readable[first], second])
+            }
+        };
+        // Synthetic gates always type check.
+        if let Ok((_, outputs)) = circuit.add_gate(gate, inputs) {
+            readable.extend(&outputs);
+            produced.extend(outputs);
+        }
+    }
+
+    for value in produced {
+        if circuit.value(value).is_ok_and(|v| v.get_uses().is_empty()) {
+            circuit.add_output(value);
+        }
+    }
+    circuit
+}
+
+/// SplitMix64 generator: small, fast and reproducible across platforms.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    /// Next pseudo-random number.
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Pseudo-random index among the most recent of `len` values.
+    fn recent(&mut self, len: usize) -> usize {
+        len - 1 - (self.next() as usize % len.min(WINDOW))
+    }
+}
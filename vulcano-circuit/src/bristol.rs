@@ -0,0 +1,526 @@
+//! Bristol Fashion circuits
+//!
+//! This module reads and writes circuits in the Bristol Fashion format, used by the
+//! standard MPC and FHE benchmark circuits (AES, SHA, ...). A file starts with
+//! three header lines:
+//!
+//! ```text
+//! <gates> <wires>
+//! <input values> <bits of each input value>...
+//! <output values> <bits of each output value>...
+//! ```
+//!
+//! followed by one gate per line: `<in> <out> <input wires>... <output wires>... <op>`.
+//! Circuit inputs are the first wires and circuit outputs the last ones.
+//! Supported operations are `AND`, `XOR`, `INV`, `MAND` (several ANDs at
+//! once), `EQW` (wire copy) and `EQ` (constant bit).
+//!
+//! Gates are read as [`BoolGate`], and can be mapped onto any other gate set.
+//! Conversely, circuits whose gates and constants map onto [`BoolGate`] and
+//! bits can be written as Bristol Fashion or as a simple JSON netlist. Every
+//! circuit input and output is written as a value of one bit.
+
+use std::{collections::HashMap, fmt::Write};
+
+use crate::{
+    circuit::{Circuit, Operation, Producer},
+    error::{Error, Result},
+    gate::{BooleanGates, Gate},
+    handles::{Ownership, ValueId},
+};
+
+/// Boolean gates of the Bristol Fashion format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(super) enum BoolGate {
+    /// Conjunction of two bits.
+    And,
+    /// Exclusive disjunction of two bits.
+    Xor,
+    /// Negation of a bit.
+    Inv,
+}
+
+impl Gate for BoolGate {
+    fn input_count(&self) -> usize {
+        match self {
+            BoolGate::And | BoolGate::Xor => 2,
+            BoolGate::Inv => 1,
+        }
+    }
+
+    fn output_count(&self) -> usize {
+        1
+    }
+
+    type Operand = ();
+
+    type Constant = bool;
+
+    fn input_type(&self, idx: usize) -> Result<Self::Operand> {
+        if idx < self.input_count() {
+            Ok(())
+        } else {
+            Err(Error::InvalidInputIndex {
+                idx,
+                max: self.input_count(),
+            })
+        }
+    }
+
+    fn output_type(&self, idx: usize) -> Result<Self::Operand> {
+        if idx < self.output_count() {
+            Ok(())
+        } else {
+            Err(Error::InvalidOutputIndex {
+                idx,
+                max: self.output_count(),
+            })
+        }
+    }
+
+    fn access_mode(&self, idx: usize) -> Result<Ownership> {
+        self.input_type(idx).map(|_| Ownership::Borrow)
+    }
+
+    fn fold(&self, inputs: &[bool]) -> Option<Vec<bool>> {
+        match (self, inputs) {
+            (BoolGate::And, [a, b]) => Some(vec![a & b]),
+            (BoolGate::Xor, [a, b]) => Some(vec![a ^ b]),
+            (BoolGate::Inv, [a]) => Some(vec![!a]),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> Option<&'static str> {
+        Some(match self {
+            BoolGate::And => "AND",
+            BoolGate::Xor => "XOR",
+            BoolGate::Inv => "INV",
+        })
+    }
+}
+
+impl BooleanGates for BoolGate {
+    fn and() -> Self {
+        BoolGate::And
+    }
+
+    fn xor() -> Self {
+        BoolGate::Xor
+    }
+
+    fn not() -> Self {
+        BoolGate::Inv
+    }
+}
+
+impl Circuit<BoolGate> {
+    /// Read a circuit in the Bristol Fashion format.
+    pub(super) fn from_bristol(text: &str) -> Result<Self> {
+        Self::from_bristol_with(text, (), |gate| gate)
+    }
+
+    /// Write the circuit in the Bristol Fashion format.
+    pub(super) fn to_bristol(&self) -> Result<String> {
+        self.to_bristol_with(|gate| Some(*gate), |bit| Some(*bit))
+    }
+
+    /// Write the circuit as a JSON netlist.
+    pub(super) fn to_json_netlist(&self) -> Result<String> {
+        self.to_json_netlist_with(|gate| Some(*gate), |bit| Some(*bit))
+    }
+}
+
+impl<G: Gate> Circuit<G>
+where
+    G::Constant: From<bool>,
+{
+    /// Read a circuit in the Bristol Fashion format, mapping each gate onto
+    /// the gate set of the circuit.
+    ///
+    /// All wires get the given operand type, and mapped gates must accept it.
+    pub(super) fn from_bristol_with<F>(text: &str, bit_type: G::Operand, mut map: F) -> Result<Self>
+    where
+        F: FnMut(BoolGate) -> G,
+    {
+        let lines = text
+            .lines()
+            .enumerate()
+            .map(|(idx, line)| (idx + 1, line.split_whitespace().collect::<Vec<_>>()))
+            .filter(|(_, tokens)| !tokens.is_empty())
+            .collect::<Vec<_>>();
+        let [
+            (_, header),
+            (input_line, inputs),
+            (output_line, outputs),
+            body @ ..,
+        ] = &lines[..]
+        else {
+            return Err(Error::InvalidBristolLine(text.lines().count() + 1));
+        };
+
+        let [gates, wires] = numbers(1, header)?[..] else {
+            return Err(Error::InvalidBristolLine(1));
+        };
+        let input_bits = value_bits(*input_line, inputs)?;
+        let output_bits = value_bits(*output_line, outputs)?;
+        if input_bits
+            .checked_add(output_bits)
+            .is_none_or(|bits| bits > wires)
+        {
+            return Err(Error::InvalidBristolLine(*output_line));
+        }
+        if body.len() != gates {
+            return Err(Error::InvalidBristolLine(1));
+        }
+        // Wires are inputs or gate outputs, and each gate writes fewer wires
+        // than its line has tokens, so this bounds what the body can use.
+        let reachable = body.iter().try_fold(input_bits, |bound, (_, tokens)| {
+            bound.checked_add(tokens.len())
+        });
+        if reachable.is_none_or(|bound| wires > bound) {
+            return Err(Error::InvalidBristolLine(1));
+        }
+
+        let mut circuit = Circuit::new();
+        let mut values: Vec<Option<ValueId>> = vec![None; wires];
+        for value in values.iter_mut().take(input_bits) {
+            *value = Some(circuit.add_input(bit_type).1);
+        }
+
+        for (line, tokens) in body {
+            let line = *line;
+            let Some((op, operands)) = tokens.split_last() else {
+                return Err(Error::InvalidBristolLine(line));
+            };
+            let [ins, outs, ref wires @ ..] = numbers(line, operands)?[..] else {
+                return Err(Error::InvalidBristolLine(line));
+            };
+            if ins.checked_add(outs) != Some(wires.len())
+                || wires.iter().any(|&w| w >= values.len())
+            {
+                return Err(Error::InvalidBristolLine(line));
+            }
+            let (ins, outs) = wires.split_at(ins);
+
+            let read = |wire: usize| values[wire].ok_or(Error::InvalidBristolLine(line));
+            let results = match (*op, ins.len(), outs.len()) {
+                ("AND" | "XOR", 2, 1) | ("INV", 1, 1) => {
+                    let gate = match *op {
+                        "AND" => BoolGate::And,
+                        "XOR" => BoolGate::Xor,
+                        _ => BoolGate::Inv,
+                    };
+                    let inputs = ins.iter().map(|&w| read(w)).collect::<Result<_>>()?;
+                    circuit.add_gate(map(gate), inputs)?.1
+                }
+                ("MAND", n, k) if n == 2 * k => {
+                    let mut results = Vec::with_capacity(k);
+                    for i in 0..k {
+                        let inputs = vec![read(ins[i])?, read(ins[k + i])?];
+                        results.extend(circuit.add_gate(map(BoolGate::And), inputs)?.1);
+                    }
+                    results
+                }
+                ("EQW", 1, 1) => vec![read(ins[0])?],
+                ("EQ", 1, 1) => {
+                    // The input is the constant bit itself, not a wire.
+                    let bit = match ins[0] {
+                        0 => false,
+                        1 => true,
+                        _ => return Err(Error::InvalidBristolLine(line)),
+                    };
+                    vec![circuit.add_constant(bit.into(), bit_type).1]
+                }
+                _ => return Err(Error::InvalidBristolLine(line)),
+            };
+            if results.len() != outs.len() {
+                return Err(Error::InvalidBristolLine(line));
+            }
+            for (&wire, value) in outs.iter().zip(results) {
+                values[wire] = Some(value);
+            }
+        }
+
+        for value in &values[wires - output_bits..] {
+            let value = value.ok_or(Error::InvalidBristolLine(*output_line))?;
+            circuit.add_output(value);
+        }
+        Ok(circuit)
+    }
+}
+
+/// Parse all tokens of a line as numbers.
+fn numbers(line: usize, tokens: &[&str]) -> Result<Vec<usize>> {
+    tokens
+        .iter()
+        .map(|t| t.parse().map_err(|_| Error::InvalidBristolLine(line)))
+        .collect()
+}
+
+/// Total bits of an input or output header line.
+fn value_bits(line: usize, tokens: &[&str]) -> Result<usize> {
+    let numbers = numbers(line, tokens)?;
+    match numbers.split_first() {
+        Some((&count, widths)) if count == widths.len() => widths
+            .iter()
+            .try_fold(0usize, |bits, &width| bits.checked_add(width))
+            .ok_or(Error::InvalidBristolLine(line)),
+        _ => Err(Error::InvalidBristolLine(line)),
+    }
+}
+
+impl<G: Gate> Circuit<G> {
+    /// Write the circuit in the Bristol Fashion format, classifying each gate
+    /// and constant.
+    ///
+    /// Fails on the first gate or constant the classifiers reject.
+    pub(super) fn to_bristol_with<F, C>(&self, gates: F, constants: C) -> Result<String>
+    where
+        F: FnMut(&G) -> Option<BoolGate>,
+        C: FnMut(&G::Constant) -> Option<bool>,
+    {
+        let netlist = self.netlist(gates, constants)?;
+        let mut text = String::new();
+        let ones = |n: usize| " 1".repeat(n);
+        // Writing into a string cannot fail.
+        let _ = writeln!(text, "{} {}", netlist.lines.len(), netlist.wires);
+        let _ = writeln!(text, "{}{}", netlist.inputs, ones(netlist.inputs));
+        let _ = writeln!(text, "{}{}", netlist.outputs, ones(netlist.outputs));
+        let _ = writeln!(text);
+        for line in &netlist.lines {
+            let operands = match line.op {
+                NetOp::Constant(bit) => vec![usize::from(bit)],
+                _ => line.inputs.clone(),
+            };
+            let _ = write!(text, "{} {}", operands.len(), line.outputs.len());
+            for wire in operands.iter().chain(&line.outputs) {
+                let _ = write!(text, " {}", wire);
+            }
+            let _ = writeln!(text, " {}", line.op.name());
+        }
+        Ok(text)
+    }
+
+    /// Write the circuit as a JSON netlist, classifying each gate and
+    /// constant.
+    ///
+    /// The netlist lists the input and output wires and the gates in
+    /// execution order, using the operation names of the Bristol Fashion
+    /// format. Fails on the first gate or constant the classifiers reject.
+    pub(super) fn to_json_netlist_with<F, C>(&self, gates: F, constants: C) -> Result<String>
+    where
+        F: FnMut(&G) -> Option<BoolGate>,
+        C: FnMut(&G::Constant) -> Option<bool>,
+    {
+        let netlist = self.netlist(gates, constants)?;
+        let list = |wires: &mut dyn Iterator<Item = usize>| {
+            wires.map(|w| w.to_string()).collect::<Vec<_>>().join(", ")
+        };
+        let mut text = String::new();
+        // Writing into a string cannot fail.
+        let _ = writeln!(text, "{{");
+        let _ = writeln!(text, "  \"wires\": {},", netlist.wires);
+        let _ = writeln!(text, "  \"inputs\": [{}],", list(&mut (0..netlist.inputs)));
+        let _ = writeln!(
+            text,
+            "  \"outputs\": [{}],",
+            list(&mut (netlist.wires - netlist.outputs..netlist.wires))
+        );
+        let _ = writeln!(text, "  \"gates\": [");
+        for (idx, line) in netlist.lines.iter().enumerate() {
+            let _ = write!(text, "    {{\"op\": \"{}\"", line.op.name());
+            if let NetOp::Constant(bit) = line.op {
+                let _ = write!(text, ", \"value\": {}", u8::from(bit));
+            }
+            let _ = write!(
+                text,
+                ", \"inputs\": [{}], \"outputs\": [{}]}}",
+                list(&mut line.inputs.iter().copied()),
+                list(&mut line.outputs.iter().copied())
+            );
+            let separator = if idx + 1 < netlist.lines.len() {
+                ","
+            } else {
+                ""
+            };
+            let _ = writeln!(text, "{}", separator);
+        }
+        let _ = writeln!(text, "  ]");
+        let _ = writeln!(text, "}}");
+        Ok(text)
+    }
+
+    /// Lower the circuit to numbered wires.
+    ///
+    /// Inputs take the first wires and outputs the last ones. A value
+    /// produced by a gate or constant is written straight into the first
+    /// output wire it feeds; any other output gets a copy. Clones share the
+    /// wire of their input and drops are omitted.
+    fn netlist<F, C>(&self, mut gates: F, mut constants: C) -> Result<Netlist>
+    where
+        F: FnMut(&G) -> Option<BoolGate>,
+        C: FnMut(&G::Constant) -> Option<bool>,
+    {
+        let inputs = self.input_count();
+        let outputs = self.output_count();
+
+        // Claim output wires for values that can be produced in place.
+        let mut claimed: HashMap<ValueId, usize> = HashMap::new();
+        let mut copies = Vec::new();
+        for (slot, (_, output)) in self.all_outputs().enumerate() {
+            let value = output.get_input();
+            let in_place = matches!(
+                self.value(value)?.get_producer(),
+                Producer::Gate(_) | Producer::Constant(_)
+            );
+            if in_place && !claimed.contains_key(&value) {
+                claimed.insert(value, slot);
+            } else {
+                copies.push((value, slot));
+            }
+        }
+
+        // Wires are assigned in execution order; output wires are only known
+        // once all other wires are, so they are patched afterwards.
+        let mut wires: HashMap<ValueId, Wire> = self
+            .all_inputs()
+            .enumerate()
+            .map(|(idx, (_, input))| (input.get_output(), Wire::Internal(idx)))
+            .collect();
+        let mut next = inputs;
+        let mut assign = |value: ValueId| match claimed.get(&value) {
+            Some(&slot) => Wire::Output(slot),
+            None => {
+                next += 1;
+                Wire::Internal(next - 1)
+            }
+        };
+        let unsupported =
+            |op: Operation| Error::UnsupportedExport(op).with_context(self.describe(op));
+        let mut lines = Vec::new();
+        for op in self.iter_scheduled()? {
+            match op {
+                Operation::Constant(id) => {
+                    let constant = self.constant_op(id)?;
+                    if self.parameter(id).is_some() {
+                        return Err(unsupported(op));
+                    }
+                    let bit = constants(constant.get_value()).ok_or_else(|| unsupported(op))?;
+                    let wire = assign(constant.get_output());
+                    wires.insert(constant.get_output(), wire);
+                    lines.push((NetOp::Constant(bit), Vec::new(), vec![wire]));
+                }
+                Operation::Gate(id) => {
+                    let gate = self.gate_op(id)?;
+                    let kind = gates(gate.get_gate()).ok_or_else(|| unsupported(op))?;
+                    if gate.get_inputs().len() != kind.input_count()
+                        || gate.get_outputs().len() != kind.output_count()
+                    {
+                        return Err(unsupported(op));
+                    }
+                    let ins = gate
+                        .get_inputs()
+                        .iter()
+                        .map(|v| wires.get(v).copied().ok_or(Error::ValueNotFound(*v)))
+                        .collect::<Result<Vec<_>>>()?;
+                    let mut outs = Vec::with_capacity(gate.get_outputs().len());
+                    for &value in gate.get_outputs() {
+                        let wire = assign(value);
+                        wires.insert(value, wire);
+                        outs.push(wire);
+                    }
+                    lines.push((NetOp::Gate(kind), ins, outs));
+                }
+                Operation::Clone(id) => {
+                    let clone = self.clone_op(id)?;
+                    let input = clone.get_input();
+                    let wire = wires.get(&input).copied();
+                    let wire = wire.ok_or(Error::ValueNotFound(input))?;
+                    for &value in clone.get_outputs() {
+                        wires.insert(value, wire);
+                    }
+                }
+                Operation::Input(_) | Operation::Drop(_) | Operation::Output(_) => {}
+            }
+        }
+        for (value, slot) in copies {
+            let wire = wires.get(&value).copied();
+            let wire = wire.ok_or(Error::ValueNotFound(value))?;
+            lines.push((NetOp::Copy, vec![wire], vec![Wire::Output(slot)]));
+        }
+
+        let resolve = |wire: Wire| match wire {
+            Wire::Internal(idx) => idx,
+            Wire::Output(slot) => next + slot,
+        };
+        Ok(Netlist {
+            inputs,
+            outputs,
+            wires: next + outputs,
+            lines: lines
+                .into_iter()
+                .map(|(op, ins, outs)| NetLine {
+                    op,
+                    inputs: ins.into_iter().map(resolve).collect(),
+                    outputs: outs.into_iter().map(resolve).collect(),
+                })
+                .collect(),
+        })
+    }
+}
+
+/// Wire of a value while lowering a circuit.
+#[derive(Clone, Copy)]
+enum Wire {
+    /// Input or intermediate wire, by number.
+    Internal(usize),
+    /// Wire of the circuit output at the given position.
+    Output(usize),
+}
+
+/// Operation of a netlist line.
+#[derive(Clone, Copy)]
+enum NetOp {
+    /// A boolean gate.
+    Gate(BoolGate),
+    /// Copy of a wire.
+    Copy,
+    /// Constant bit.
+    Constant(bool),
+}
+
+impl NetOp {
+    /// Name of the operation in the Bristol Fashion format.
+    fn name(&self) -> &'static str {
+        match self {
+            NetOp::Gate(BoolGate::And) => "AND",
+            NetOp::Gate(BoolGate::Xor) => "XOR",
+            NetOp::Gate(BoolGate::Inv) => "INV",
+            NetOp::Copy => "EQW",
+            NetOp::Constant(_) => "EQ",
+        }
+    }
+}
+
+/// Line of a netlist.
+struct NetLine {
+    /// Operation performed.
+    op: NetOp,
+    /// Wires read.
+    inputs: Vec<usize>,
+    /// Wires written.
+    outputs: Vec<usize>,
+}
+
+/// A circuit lowered to numbered wires.
+struct Netlist {
+    /// Number of input wires, numbered first.
+    inputs: usize,
+    /// Number of output wires, numbered last.
+    outputs: usize,
+    /// Total number of wires.
+    wires: usize,
+    /// Lines in execution order.
+    lines: Vec<NetLine>,
+}
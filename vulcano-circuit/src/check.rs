@@ -0,0 +1,85 @@
+//! Circuit checks
+//!
+//! This module collects every wiring problem of a circuit at once, instead
+//! of failing on the first one as scheduling and analyses do, so that tools
+//! can report all of them together. Each problem is an error wrapped with a
+//! description of the operation at fault.
+
+use crate::{
+    circuit::{Circuit, Operation},
+    error::{Error, Result},
+    gate::Gate,
+    handles::{Ownership, ValueId},
+};
+
+impl<G: Gate> Circuit<G> {
+    /// Find every problem in the circuit.
+    ///
+    /// Reports inputs never used, values never moved, values moved more
+    /// than once, gates with missing, dangling or mistyped inputs, and
+    /// dependency cycles. An empty list means the circuit is well formed.
+    pub(super) fn check(&self) -> Vec<Error> {
+        let mut problems = Vec::new();
+
+        // Step 1. Operations referring to values that do not fit them.
+        for op in self.all_operations() {
+            if let Err(problem) = self.check_operation(op) {
+                problems.push(problem.with_context(self.describe(op)));
+            }
+        }
+
+        // Step 2. Values not moved exactly once.
+        for (id, value) in self.all_values() {
+            let moves = value
+                .get_uses()
+                .iter()
+                .filter(|u| u.mode == Ownership::Move)
+                .count();
+            let producer = Operation::from(value.get_producer());
+            let problem = match (producer, moves) {
+                (Operation::Input(input), _) if value.get_uses().is_empty() => {
+                    Error::UnusedInput(input)
+                }
+                (_, 0) => Error::UnconsumedValue(id),
+                (_, 1) => continue,
+                (_, moves) => Error::OverconsumedValue { value: id, moves },
+            };
+            problems.push(problem.with_context(self.describe(producer)));
+        }
+
+        // Step 3. Dependency cycles. Other scheduling failures come from
+        // dangling values, already reported above.
+        if let Err(problem) = self.compute_schedule()
+            && matches!(problem.root_cause(), Error::CycleDetected(_))
+        {
+            problems.push(problem);
+        }
+
+        problems
+    }
+
+    /// Check the values consumed by an operation.
+    fn check_operation(&self, op: Operation) -> Result<()> {
+        let inputs = self.consumed_values(op)?;
+        let existing = |value: ValueId| self.value(value).map(|value| value.get_type());
+        let Operation::Gate(id) = op else {
+            return inputs
+                .into_iter()
+                .try_for_each(|value| existing(value).map(drop));
+        };
+
+        let gate = self.gate_op(id)?.get_gate();
+        if inputs.len() != gate.input_count() {
+            return Err(Error::WrongInputCount {
+                expected: gate.input_count(),
+                got: inputs.len(),
+            });
+        }
+        for (port, value) in inputs.into_iter().enumerate() {
+            if existing(value)? != gate.input_type(port)? {
+                return Err(Error::TypeMismatch { gate: id, port });
+            }
+        }
+        Ok(())
+    }
+}
@@ -4,14 +4,28 @@
 //! Values are defined exactly once and consumed exactly once.
 //! Values can be borrowed any number of times before being consumed.
 
+use std::{
+    collections::{BTreeSet, HashMap, HashSet, VecDeque, hash_map::Entry},
+    fmt,
+    panic::Location,
+    sync::OnceLock,
+};
+
 use crate::{
-    error::{Error, Result},
+    annotations::Annotations,
+    error::{CycleStep, Error, Result},
     gate::Gate,
-    handles::{CloneId, DropId, GateId, InputId, OutputId, Ownership, PortId, ValueId},
+    handles::{CloneId, ConstantId, DropId, GateId, InputId, OutputId, Ownership, PortId, ValueId},
+    origin::{Origin, Origins},
+    provenance::{Provenance, ProvenanceId},
 };
 
 use vulcano_arena::Arena;
 
+/// Output ports of an operation whose values are observed, kept as an
+/// annotation of the producer.
+struct ObservedPorts(BTreeSet<usize>);
+
 /// A gate operation: user-defined computation.
 pub(super) struct GateOperation<G: Gate> {
     /// The gate descriptor.
@@ -77,6 +91,26 @@ impl DropOperation {
     }
 }
 
+/// Constant operation: compile-time known value, produces one value.
+pub(super) struct ConstantOperation<G: Gate> {
+    /// The constant value.
+    value: G::Constant,
+    /// The output value.
+    output: ValueId,
+}
+
+impl<G: Gate> ConstantOperation<G> {
+    /// Get the constant value.
+    pub(super) fn get_value(&self) -> &G::Constant {
+        &self.value
+    }
+
+    /// Get the output value.
+    pub(super) fn get_output(&self) -> ValueId {
+        self.output
+    }
+}
+
 /// Input operation: external circuit input, produces one value.
 pub(super) struct InputOperation {
     /// The output value.
@@ -154,6 +188,16 @@ pub(super) struct Value<G: Gate> {
 }
 
 impl<G: Gate> Value<G> {
+    /// Create a new unused value from a producer and port.
+    fn new(producer: Producer, port: PortId, value_type: G::Operand) -> Self {
+        Self {
+            producer,
+            port,
+            uses: Vec::new(),
+            value_type,
+        }
+    }
+
     /// Get the producer of this value.
     pub(super) fn get_producer(&self) -> Producer {
         self.producer
@@ -208,6 +252,8 @@ impl<G: Gate> Value<G> {
 pub(super) enum Producer {
     /// External circuit input.
     Input(InputId),
+    /// Compile-time constant.
+    Constant(ConstantId),
     /// Produced by a gate.
     Gate(GateId),
     /// Produced by a clone.
@@ -220,6 +266,7 @@ impl TryFrom<Operation> for Producer {
     fn try_from(value: Operation) -> Result<Self> {
         match value {
             Operation::Input(id) => Ok(Producer::Input(id)),
+            Operation::Constant(id) => Ok(Producer::Constant(id)),
             Operation::Gate(id) => Ok(Producer::Gate(id)),
             Operation::Clone(id) => Ok(Producer::Clone(id)),
             _ => Err(Error::BadOperationConversion(value)),
@@ -232,6 +279,8 @@ impl TryFrom<Operation> for Producer {
 pub(super) enum Operation {
     /// Circuit input.
     Input(InputId),
+    /// A constant.
+    Constant(ConstantId),
     /// A gate computation.
     Gate(GateId),
     /// A clone operation.
@@ -242,6 +291,19 @@ pub(super) enum Operation {
     Output(OutputId),
 }
 
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operation::Input(id) => write!(f, "{}", id),
+            Operation::Constant(id) => write!(f, "{}", id),
+            Operation::Gate(id) => write!(f, "{}", id),
+            Operation::Clone(id) => write!(f, "{}", id),
+            Operation::Drop(id) => write!(f, "{}", id),
+            Operation::Output(id) => write!(f, "{}", id),
+        }
+    }
+}
+
 impl From<Consumer> for Operation {
     fn from(consumer: Consumer) -> Self {
         match consumer {
@@ -257,6 +319,7 @@ impl From<Producer> for Operation {
     fn from(producer: Producer) -> Self {
         match producer {
             Producer::Input(id) => Operation::Input(id),
+            Producer::Constant(id) => Operation::Constant(id),
             Producer::Gate(id) => Operation::Gate(id),
             Producer::Clone(id) => Operation::Clone(id),
         }
@@ -266,46 +329,298 @@ impl From<Producer> for Operation {
 /// A circuit in Linear SSA form.
 pub(super) struct Circuit<G: Gate> {
     /// All gates, indexed by GateId.
-    gates: Arena<GateOperation<G>>,
+    gates: Arena<GateOperation<G>, GateId>,
     /// All clones, indexed by CloneId.
-    clones: Arena<CloneOperation>,
+    clones: Arena<CloneOperation, CloneId>,
     /// All drops, indexed by DropId.
-    drops: Arena<DropOperation>,
+    drops: Arena<DropOperation, DropId>,
+    /// Constants, indexed by ConstantId.
+    constants: Arena<ConstantOperation<G>, ConstantId>,
     /// Circuit inputs, indexed by InputId.
-    inputs: Arena<InputOperation>,
+    inputs: Arena<InputOperation, InputId>,
     /// Circuit outputs, indexed by OutputId.
-    outputs: Arena<OutputOperation>,
+    outputs: Arena<OutputOperation, OutputId>,
     /// All values, indexed by ValueId.
-    values: Arena<Value<G>>,
+    values: Arena<Value<G>, ValueId>,
+    /// Annotations attached to operations.
+    annotations: Annotations,
+    /// Cached schedule, cleared on any structural change.
+    schedule: OnceLock<Vec<Operation>>,
+    /// Provenance id for the next gate added.
+    next_provenance: usize,
+    /// Number of the next module instance inlined.
+    next_instance: usize,
 }
 
 impl<G: Gate> Circuit<G> {
     /// Create a new empty circuit.
     pub(super) fn new() -> Self {
         Self {
-            gates: Arena::new(),
-            clones: Arena::new(),
-            drops: Arena::new(),
-            values: Arena::new(),
-            inputs: Arena::new(),
-            outputs: Arena::new(),
+            gates: Arena::with_key(),
+            clones: Arena::with_key(),
+            drops: Arena::with_key(),
+            constants: Arena::with_key(),
+            values: Arena::with_key(),
+            inputs: Arena::with_key(),
+            outputs: Arena::with_key(),
+            annotations: Annotations::new(),
+            schedule: OnceLock::new(),
+            next_provenance: 0,
+            next_instance: 0,
         }
     }
 
-    /// Create a new value from a producer and port.
-    fn create_value(&mut self, producer: Producer, port: PortId, ty: G::Operand) -> ValueId {
-        let id_key = self.values.insert(Value {
-            producer,
-            port,
-            uses: Vec::new(),
-            value_type: ty,
-        });
-        ValueId::new(id_key)
+    /// Drop the cached schedule after a structural change.
+    fn invalidate_schedule(&mut self) {
+        self.schedule.take();
+    }
+
+    /// Iterate over all operations in a valid dependency order.
+    ///
+    /// Inputs and constants come first, then gates and clones after the
+    /// operations producing their inputs, then drops and outputs. Operations
+    /// borrowing a value come before the one moving it whenever the borrows
+    /// do not depend on the move.
+    /// Ties are broken by handle order, so the schedule is deterministic.
+    /// The schedule is computed on first use and cached until the circuit changes.
+    pub(super) fn iter_scheduled(&self) -> Result<impl Iterator<Item = Operation> + '_> {
+        if self.schedule.get().is_none() {
+            let schedule = self.compute_schedule()?;
+            let _ = self.schedule.set(schedule);
+        }
+        Ok(self.schedule.get().into_iter().flatten().copied())
+    }
+
+    /// Compute the dependency order used by `iter_scheduled`.
+    pub(super) fn compute_schedule(&self) -> Result<Vec<Operation>> {
+        let mut schedule = Vec::with_capacity(self.all_operations().count());
+        schedule.extend(self.all_inputs().map(|(id, _)| Operation::Input(id)));
+        schedule.extend(self.all_constants().map(|(id, _)| Operation::Constant(id)));
+
+        // Number of inputs of each gate and clone not produced yet.
+        let mut pending: HashMap<Operation, usize> = self
+            .all_gates()
+            .map(|(id, g)| (Operation::Gate(id), g.inputs.len()))
+            .chain(self.all_clones().map(|(id, _)| (Operation::Clone(id), 1)))
+            .collect();
+
+        // Number of borrows not scheduled yet of the values each gate and
+        // clone moves, and the moves waiting on each borrowing operation.
+        let mut borrows: HashMap<Operation, usize> = HashMap::new();
+        let mut moves_after: HashMap<Operation, Vec<Operation>> = HashMap::new();
+        for (_, value) in self.all_values() {
+            let uses = value
+                .get_uses()
+                .iter()
+                .map(|usage| (Operation::from(usage.consumer), usage.mode))
+                .filter(|(consumer, _)| pending.contains_key(consumer));
+            let (movers, borrowers): (Vec<_>, Vec<_>) =
+                uses.partition(|&(_, mode)| mode == Ownership::Move);
+            for &(mover, _) in &movers {
+                for &(borrower, _) in borrowers.iter().filter(|&&(b, _)| b != mover) {
+                    *borrows.entry(mover).or_default() += 1;
+                    moves_after.entry(borrower).or_default().push(mover);
+                }
+            }
+        }
+
+        let is_ready = |op: &Operation,
+                        pending: &HashMap<Operation, usize>,
+                        borrows: &HashMap<Operation, usize>| {
+            pending[op] == 0 && borrows.get(op).is_none_or(|&count| count == 0)
+        };
+        let mut ready: VecDeque<Operation> = self
+            .all_gates()
+            .map(|(id, _)| Operation::Gate(id))
+            .chain(self.all_clones().map(|(id, _)| Operation::Clone(id)))
+            .filter(|op| is_ready(op, &pending, &borrows))
+            .collect();
+
+        // Release the consumers of the values produced by an operation, and
+        // the moves waiting on its borrows.
+        let release = |op: Operation,
+                       pending: &mut HashMap<Operation, usize>,
+                       borrows: &mut HashMap<Operation, usize>,
+                       ready: &mut VecDeque<Operation>|
+         -> Result<()> {
+            for value in self.produced_values(op) {
+                for usage in self.value(value)?.get_uses() {
+                    let consumer = Operation::from(usage.consumer);
+                    if let Some(count) = pending.get_mut(&consumer) {
+                        *count -= 1;
+                        if is_ready(&consumer, pending, borrows) {
+                            ready.push_back(consumer);
+                        }
+                    }
+                }
+            }
+            for &mover in moves_after.get(&op).into_iter().flatten() {
+                if let Some(count) = borrows.get_mut(&mover)
+                    && *count > 0
+                {
+                    *count -= 1;
+                    if is_ready(&mover, pending, borrows) {
+                        ready.push_back(mover);
+                    }
+                }
+            }
+            Ok(())
+        };
+
+        for &op in &schedule {
+            release(op, &mut pending, &mut borrows, &mut ready)?;
+        }
+        let mut scheduled = 0;
+        loop {
+            while let Some(op) = ready.pop_front() {
+                schedule.push(op);
+                scheduled += 1;
+                release(op, &mut pending, &mut borrows, &mut ready)?;
+            }
+            // A borrow depending on the move of the same value cannot come
+            // first. Schedule the move anyway and leave the conflict to
+            // ownership reconciliation.
+            let Some(mover) = self
+                .all_gates()
+                .map(|(id, _)| Operation::Gate(id))
+                .chain(self.all_clones().map(|(id, _)| Operation::Clone(id)))
+                .find(|op| pending[op] == 0 && borrows.get(op).is_some_and(|&count| count > 0))
+            else {
+                break;
+            };
+            borrows.insert(mover, 0);
+            ready.push_back(mover);
+        }
+        if scheduled != pending.len() {
+            let stuck = pending
+                .into_iter()
+                .filter(|&(_, count)| count > 0)
+                .map(|(op, _)| op)
+                .collect();
+            return Err(Error::CycleDetected(self.find_cycle(&stuck)?));
+        }
+
+        schedule.extend(self.all_drops().map(|(id, _)| Operation::Drop(id)));
+        schedule.extend(self.all_outputs().map(|(id, _)| Operation::Output(id)));
+        Ok(schedule)
+    }
+
+    /// Find a dependency cycle among the given operations.
+    ///
+    /// Returns a shortest cycle through one of its operations, or an empty
+    /// sequence if the operations contain no cycle.
+    pub(super) fn find_cycle(&self, among: &HashSet<Operation>) -> Result<Vec<CycleStep>> {
+        // Producers of the values consumed by an operation, within the set.
+        let producers = |op: Operation| -> Result<Vec<Operation>> {
+            let mut producers = Vec::new();
+            for value in self.consumed_values(op)? {
+                let producer = Operation::from(self.value(value)?.get_producer());
+                if among.contains(&producer) && !producers.contains(&producer) {
+                    producers.push(producer);
+                }
+            }
+            Ok(producers)
+        };
+
+        // Step 1. Depth-first search towards producers until reaching an
+        // operation on the current path. Roots are tried in handle order so
+        // the reported cycle does not depend on the hash of the set.
+        let mut start = None;
+        let mut done: HashSet<Operation> = HashSet::new();
+        let roots = self.all_operations().filter(|op| among.contains(op));
+        'search: for root in roots {
+            if done.contains(&root) {
+                continue;
+            }
+            let mut path: Vec<(Operation, Vec<Operation>)> = Vec::from([(root, producers(root)?)]);
+            let mut on_path = HashSet::from([root]);
+            while let Some((op, pending)) = path.last_mut() {
+                let op = *op;
+                match pending.pop() {
+                    Some(next) if on_path.contains(&next) => {
+                        start = Some(next);
+                        break 'search;
+                    }
+                    Some(next) if !done.contains(&next) => {
+                        on_path.insert(next);
+                        path.push((next, producers(next)?));
+                    }
+                    Some(_) => {}
+                    None => {
+                        on_path.remove(&op);
+                        done.insert(op);
+                        path.pop();
+                    }
+                }
+            }
+        }
+        let Some(start) = start else {
+            return Ok(Vec::new());
+        };
+
+        // Step 2. Shortest path from the start back to itself, walking from
+        // consumers to producers.
+        let mut reached: HashMap<Operation, Operation> = HashMap::new();
+        let mut queue = VecDeque::from([start]);
+        'bfs: while let Some(op) = queue.pop_front() {
+            for producer in producers(op)? {
+                if producer == start {
+                    reached.insert(start, op);
+                    break 'bfs;
+                }
+                if let Entry::Vacant(entry) = reached.entry(producer) {
+                    entry.insert(op);
+                    queue.push_back(producer);
+                }
+            }
+        }
+
+        // Each operation was reached from one of its consumers, so following
+        // the walk from the start gives the cycle in dependency order.
+        let mut cycle = Vec::from([start]);
+        let mut op = reached[&start];
+        while op != start {
+            cycle.push(op);
+            op = reached[&op];
+        }
+
+        // Step 3. Describe each operation with the value it feeds to the next.
+        let mut steps = Vec::with_capacity(cycle.len());
+        for (idx, &op) in cycle.iter().enumerate() {
+            let next = cycle[(idx + 1) % cycle.len()];
+            let consumed = self.consumed_values(next)?;
+            let value = self
+                .produced_values(op)
+                .find(|value| consumed.contains(value))
+                .ok_or(Error::BadOperationConversion(op))?;
+            let name = match op {
+                Operation::Gate(id) => self.gate_op(id)?.get_gate().name(),
+                _ => None,
+            };
+            steps.push(CycleStep {
+                operation: op,
+                name,
+                value,
+                description: self.describe(op),
+            });
+        }
+        Ok(steps)
+    }
+
+    /// Get the annotations attached to operations.
+    pub(super) fn annotations(&self) -> &Annotations {
+        &self.annotations
+    }
+
+    /// Get a mutable reference to the annotations attached to operations.
+    pub(super) fn annotations_mut(&mut self) -> &mut Annotations {
+        &mut self.annotations
     }
 
     /// Record the use of a value.
     fn record_use(&mut self, value: ValueId, consumer: Consumer, port: PortId, mode: Ownership) {
-        if let Some(val) = self.values.get_mut(value.key()) {
+        self.invalidate_schedule();
+        if let Some(val) = self.values.get_mut(value) {
             val.uses.push(Usage {
                 consumer,
                 port,
@@ -317,7 +632,7 @@ impl<G: Gate> Circuit<G> {
     /// Get all move usages of a value.
     pub(super) fn get_move_uses(&self, value: ValueId) -> Vec<Usage> {
         self.values
-            .get(value.key())
+            .get(value)
             .map(|v| {
                 v.uses
                     .iter()
@@ -329,7 +644,8 @@ impl<G: Gate> Circuit<G> {
     }
 
     /// Rewire a use from one value to another.
-    /// Finds the usage matching (consumer, port) on old_value and moves it to new_value.
+    /// Finds the usage matching (consumer, port) on old_value and moves it to new_value,
+    /// then points the consumer's input port to new_value.
     pub(super) fn rewire_use(
         &mut self,
         old_value: ValueId,
@@ -337,45 +653,138 @@ impl<G: Gate> Circuit<G> {
         consumer: Consumer,
         port: PortId,
     ) {
-        // Remove usage from old value.
-        let mut usage = None;
-        if let Some(old_val) = self.values.get_mut(old_value.key())
-            && let Some(pos) = old_val
-                .uses
-                .iter()
-                .position(|u| u.consumer == consumer && u.port == port)
-        {
-            usage = Some(old_val.uses.remove(pos));
+        self.invalidate_schedule();
+
+        // Move the usage from the old value to the new one.
+        let Some([old_val, new_val]) = self.values.get_disjoint_mut([old_value, new_value]) else {
+            return;
+        };
+        let Some(pos) = old_val
+            .uses
+            .iter()
+            .position(|u| u.consumer == consumer && u.port == port)
+        else {
+            return;
+        };
+        new_val.uses.push(old_val.uses.remove(pos));
+
+        // Update the consumer side.
+        let input = match consumer {
+            Consumer::Gate(id) => self
+                .gates
+                .get_mut(id)
+                .and_then(|g| g.inputs.get_mut(port.index())),
+            Consumer::Clone(id) => self.clones.get_mut(id).map(|c| &mut c.input),
+            Consumer::Drop(id) => self.drops.get_mut(id).map(|d| &mut d.input),
+            Consumer::Output(id) => self.outputs.get_mut(id).map(|o| &mut o.input),
+        };
+        if let Some(input) = input {
+            *input = new_value;
         }
+    }
 
-        // Add usage to new value.
-        if let Some(u) = usage
-            && let Some(new_val) = self.values.get_mut(new_value.key())
-        {
-            new_val.uses.push(u);
+    /// Swap two input ports of a gate.
+    pub(super) fn swap_gate_inputs(&mut self, id: GateId, a: PortId, b: PortId) -> Result<()> {
+        let gate = self.gates.get_mut(id).ok_or(Error::GateNotFound(id))?;
+        let max = gate.inputs.len();
+        for port in [a, b] {
+            if port.index() >= max {
+                return Err(Error::InvalidInputIndex {
+                    idx: port.index(),
+                    max,
+                });
+            }
+        }
+        let (value_a, value_b) = (gate.inputs[a.index()], gate.inputs[b.index()]);
+        if value_a == value_b {
+            return Ok(());
+        }
+        gate.inputs.swap(a.index(), b.index());
+
+        // Renumber the ports of the recorded uses.
+        let consumer = Consumer::Gate(id);
+        for (value, from, to) in [(value_a, a, b), (value_b, b, a)] {
+            if let Some(usage) = self.values.get_mut(value).and_then(|v| {
+                v.uses
+                    .iter_mut()
+                    .find(|u| u.consumer == consumer && u.port == from)
+            }) {
+                usage.port = to;
+            }
+        }
+        Ok(())
+    }
+
+    /// Rewire every use of a value to another value.
+    pub(super) fn replace_all_uses(&mut self, old_value: ValueId, new_value: ValueId) {
+        let uses: Vec<Usage> = self
+            .values
+            .get(old_value)
+            .map(|v| v.uses.clone())
+            .unwrap_or_default();
+        for usage in uses {
+            self.rewire_use(old_value, new_value, usage.consumer, usage.port);
+        }
+    }
+
+    /// Remove the uses a consumer made of the given values.
+    fn forget_uses(&mut self, values: &[ValueId], consumer: Consumer) {
+        self.invalidate_schedule();
+        for &value in values {
+            if let Some(val) = self.values.get_mut(value) {
+                val.uses.retain(|u| u.consumer != consumer);
+            }
         }
     }
 
     /// Create a circuit input.
     pub(super) fn add_input(&mut self, value_type: G::Operand) -> (InputId, ValueId) {
-        // Reserve input slot to get key
-        let input_key = self.inputs.reserve();
-        let input_id = InputId::new(input_key);
+        self.invalidate_schedule();
+
+        // Reserve input slot to get its id.
+        let slot = self.inputs.reserve_slot();
+        let input_id = slot.key();
 
-        let value_id = self.create_value(Producer::Input(input_id), PortId::new(0), value_type);
+        let value_id = self.values.insert(Value::new(
+            Producer::Input(input_id),
+            PortId::new(0),
+            value_type,
+        ));
 
         // Fill input slot
-        let _ = self
-            .inputs
-            .fill(input_key, InputOperation { output: value_id });
+        slot.fill(InputOperation { output: value_id });
 
         (input_id, value_id)
     }
 
+    /// Create a constant.
+    pub(super) fn add_constant(
+        &mut self,
+        value: G::Constant,
+        value_type: G::Operand,
+    ) -> (ConstantId, ValueId) {
+        self.invalidate_schedule();
+
+        let slot = self.constants.reserve_slot();
+        let constant_id = slot.key();
+
+        let value_id = self.values.insert(Value::new(
+            Producer::Constant(constant_id),
+            PortId::new(0),
+            value_type,
+        ));
+
+        slot.fill(ConstantOperation {
+            value,
+            output: value_id,
+        });
+
+        (constant_id, value_id)
+    }
+
     /// Mark a value as a circuit output.
     pub(super) fn add_output(&mut self, value: ValueId) -> OutputId {
-        let output_key = self.outputs.insert(OutputOperation { input: value });
-        let output_id = OutputId::new(output_key);
+        let output_id = self.outputs.insert(OutputOperation { input: value });
 
         self.record_use(
             value,
@@ -386,12 +795,223 @@ impl<G: Gate> Circuit<G> {
         output_id
     }
 
+    /// Get the origins of an operation, if any were recorded.
+    pub(super) fn origins(&self, op: Operation) -> Option<&Origins> {
+        self.annotations.get::<Origins>(op)
+    }
+
+    /// Describe an operation for diagnostics.
+    ///
+    /// Includes the gate name and the recorded origins when available.
+    /// Never fails: stale operations are described by their handle alone.
+    pub(super) fn describe(&self, op: Operation) -> String {
+        let mut description = op.to_string();
+        if let Operation::Gate(id) = op
+            && let Some(name) = self.gates.get(id).and_then(|g| g.gate.name())
+        {
+            description.push_str(&format!(" ({})", name));
+        }
+        if let Some(origins) = self.origins(op)
+            && !origins.is_empty()
+        {
+            description.push_str(&format!(" from {}", origins));
+        }
+        description
+    }
+
+    /// Describe a value for diagnostics by the operation producing it.
+    ///
+    /// Never fails: stale values are described by their handle alone.
+    pub(super) fn describe_value(&self, value: ValueId) -> String {
+        match self.values.get(value) {
+            Some(val) => format!("{} of {}", value, self.describe(val.producer.into())),
+            None => value.to_string(),
+        }
+    }
+
+    /// Add the origins and provenance of one operation to another.
+    ///
+    /// Passes use this so that operations derived from or merged with others
+    /// keep track of every place they come from.
+    pub(super) fn inherit_origins(&mut self, from: Operation, to: Operation) {
+        if let Some(inherited) = self.origins(from).cloned() {
+            match self.annotations.get_mut::<Origins>(to) {
+                Some(origins) => origins.merge(&inherited),
+                None => {
+                    self.annotations.insert(to, inherited);
+                }
+            }
+        }
+        if let Some(inherited) = self.provenance(from).cloned() {
+            match self.annotations.get_mut::<Provenance>(to) {
+                Some(provenance) => provenance.merge(&inherited),
+                None => {
+                    self.annotations.insert(to, inherited);
+                }
+            }
+        }
+    }
+
+    /// Tag an operation with a name to find it by later.
+    ///
+    /// Tags are recorded as labels among the origins, so they survive passes
+    /// that renumber, merge or derive operations.
+    pub(super) fn tag(&mut self, op: Operation, tag: impl Into<String>) {
+        let label = Origin::Label(tag.into());
+        match self.annotations.get_mut::<Origins>(op) {
+            Some(origins) => origins.insert(label),
+            None => {
+                self.annotations.insert(op, Origins::single(label));
+            }
+        }
+    }
+
+    /// Find the operations carrying a tag or label.
+    pub(super) fn find_by_tag(&self, tag: &str) -> Vec<Operation> {
+        self.all_operations()
+            .filter(|&op| {
+                self.origins(op)
+                    .is_some_and(|origins| origins.labels().any(|label| label == tag))
+            })
+            .collect()
+    }
+
+    /// Mark a value as observed, so that it is kept alive until the end of
+    /// the circuit for inspection.
+    ///
+    /// Observed values count as reachable and their live range extends to the
+    /// last operation, so their storage is never reused.
+    pub(super) fn observe(&mut self, value: ValueId) -> Result<()> {
+        let value = self.value(value)?;
+        let (producer, port) = (Operation::from(value.get_producer()), value.get_port());
+        match self.annotations.get_mut::<ObservedPorts>(producer) {
+            Some(ports) => {
+                ports.0.insert(port.index());
+            }
+            None => {
+                self.annotations
+                    .insert(producer, ObservedPorts(BTreeSet::from([port.index()])));
+            }
+        }
+        Ok(())
+    }
+
+    /// Stop observing a value.
+    pub(super) fn unobserve(&mut self, value: ValueId) -> Result<()> {
+        let value = self.value(value)?;
+        let (producer, port) = (Operation::from(value.get_producer()), value.get_port());
+        if let Some(ports) = self.annotations.get_mut::<ObservedPorts>(producer) {
+            ports.0.remove(&port.index());
+            if ports.0.is_empty() {
+                self.annotations.remove::<ObservedPorts>(producer);
+            }
+        }
+        Ok(())
+    }
+
+    /// Check if a value is observed.
+    pub(super) fn is_observed(&self, value: ValueId) -> bool {
+        self.values.get(value).is_some_and(|v| {
+            self.annotations
+                .get::<ObservedPorts>(Operation::from(v.producer))
+                .is_some_and(|ports| ports.0.contains(&v.port.index()))
+        })
+    }
+
+    /// Iterate over all observed values.
+    pub(super) fn observed_values(&self) -> impl Iterator<Item = ValueId> + '_ {
+        self.annotations
+            .iter::<ObservedPorts>()
+            .flat_map(move |(op, ports)| {
+                self.produced_values(op)
+                    .enumerate()
+                    .filter(|(idx, _)| ports.0.contains(idx))
+                    .map(|(_, value)| value)
+            })
+    }
+
     /// Add a gate.
+    ///
+    /// The caller location is recorded as the origin of the gate and attached
+    /// to any error raised while adding it.
+    #[track_caller]
     pub(super) fn add_gate(
         &mut self,
         gate: G,
         inputs: Vec<ValueId>,
     ) -> Result<(GateId, Vec<ValueId>)> {
+        self.add_gate_at(gate, inputs, Location::caller(), None)
+    }
+
+    /// Add a gate with a user label recorded alongside the caller location.
+    #[track_caller]
+    pub(super) fn add_labeled_gate(
+        &mut self,
+        gate: G,
+        inputs: Vec<ValueId>,
+        label: impl Into<String>,
+    ) -> Result<(GateId, Vec<ValueId>)> {
+        self.add_gate_at(gate, inputs, Location::caller(), Some(label.into()))
+    }
+
+    /// Add a gate and record its origins.
+    fn add_gate_at(
+        &mut self,
+        gate: G,
+        inputs: Vec<ValueId>,
+        location: &'static Location<'static>,
+        label: Option<String>,
+    ) -> Result<(GateId, Vec<ValueId>)> {
+        let (gate_id, outputs) =
+            self.insert_gate(gate, inputs)
+                .map_err(|source| Error::Located {
+                    location,
+                    source: Box::new(source),
+                })?;
+
+        let mut origins = Origins::single(Origin::Location(location));
+        if let Some(label) = label {
+            origins.insert(Origin::Label(label));
+        }
+        self.annotations.insert(Operation::Gate(gate_id), origins);
+        self.mint_provenance(Operation::Gate(gate_id));
+
+        Ok((gate_id, outputs))
+    }
+
+    /// Add a gate derived by a pass from operations already in the circuit.
+    ///
+    /// No origin or provenance is recorded: the gate does not come from the
+    /// user program, so passes attach the origins of the operations it is
+    /// derived from with [`Circuit::inherit_origins`].
+    pub(super) fn add_derived_gate(
+        &mut self,
+        gate: G,
+        inputs: Vec<ValueId>,
+    ) -> Result<(GateId, Vec<ValueId>)> {
+        self.insert_gate(gate, inputs)
+    }
+
+    /// Take the number of a new module instance.
+    ///
+    /// Numbers are never reused, even once an instance has been removed.
+    pub(super) fn take_instance_number(&mut self) -> usize {
+        let instance = self.next_instance;
+        self.next_instance += 1;
+        instance
+    }
+
+    /// Give an operation a fresh provenance id, as a gate of the program.
+    pub(super) fn mint_provenance(&mut self, op: Operation) {
+        let provenance = ProvenanceId::new(self.next_provenance);
+        self.next_provenance += 1;
+        self.annotations.insert(op, Provenance::single(provenance));
+    }
+
+    /// Validate and insert a gate, recording the uses of its inputs.
+    fn insert_gate(&mut self, gate: G, inputs: Vec<ValueId>) -> Result<(GateId, Vec<ValueId>)> {
+        self.invalidate_schedule();
+
         let expected = gate.input_count();
         if inputs.len() != expected {
             return Err(Error::WrongInputCount {
@@ -407,81 +1027,85 @@ impl<G: Gate> Circuit<G> {
             output_types.push(gate.output_type(p)?);
         }
 
+        // Reserve the gate slot. The reservation is released on any early return.
+        let slot = self.gates.reserve_slot();
+        let gate_id = slot.key();
+
         // Pre-compute access modes and validate input types.
         let mut access_modes = Vec::with_capacity(inputs.len());
-
-        let gate_key = self.gates.reserve();
-        let gate_id = GateId::new(gate_key);
-
         for (idx, &v) in inputs.iter().enumerate() {
-            let expected_ty = match gate.input_type(idx) {
-                Ok(ty) => ty,
-                Err(e) => {
-                    self.gates.remove(gate_key);
-                    return Err(e);
-                }
-            };
-            let actual_ty = match self.values.get(v.key()) {
-                Some(val) => val.value_type,
-                None => {
-                    self.gates.remove(gate_key);
-                    return Err(Error::ValueNotFound(v));
-                }
-            };
+            let expected_ty = gate.input_type(idx)?;
+            let actual_ty = self
+                .values
+                .get(v)
+                .ok_or(Error::ValueNotFound(v))?
+                .value_type;
             if expected_ty != actual_ty {
-                self.gates.remove(gate_key);
+                // Release the reservation to describe the mismatched input.
+                drop(slot);
+                let gate = gate
+                    .name()
+                    .map_or(String::new(), |name| format!(" {}", name));
+                let context = format!("adding gate{} on {}", gate, self.describe_value(v));
                 return Err(Error::TypeMismatch {
                     gate: gate_id,
                     port: idx,
-                });
-            }
-            match gate.access_mode(idx) {
-                Ok(mode) => access_modes.push(mode),
-                Err(e) => {
-                    self.gates.remove(gate_key);
-                    return Err(e);
                 }
+                .with_context(context));
             }
+            access_modes.push(gate.access_mode(idx)?);
         }
 
         // Create output values.
-        let mut outputs = Vec::with_capacity(output_count);
-        for (p, ty) in output_types.into_iter().enumerate() {
-            let value_id = self.create_value(Producer::Gate(gate_id), PortId::new(p), ty);
-            outputs.push(value_id);
-        }
+        let outputs: Vec<ValueId> = output_types
+            .into_iter()
+            .enumerate()
+            .map(|(p, ty)| {
+                self.values
+                    .insert(Value::new(Producer::Gate(gate_id), PortId::new(p), ty))
+            })
+            .collect();
+
+        slot.fill(GateOperation {
+            gate,
+            inputs: inputs.clone(),
+            outputs: outputs.clone(),
+        });
 
         // Record input uses.
-        for (idx, (&v, mode)) in inputs.iter().zip(access_modes).enumerate() {
+        for (idx, (v, mode)) in inputs.into_iter().zip(access_modes).enumerate() {
             let port = PortId::new(idx);
             self.record_use(v, Consumer::Gate(gate_id), port, mode);
         }
 
-        let _ = self.gates.fill(
-            gate_key,
-            GateOperation {
-                gate,
-                inputs,
-                outputs: outputs.clone(),
-            },
-        );
-
         Ok((gate_id, outputs))
     }
 
     /// Clone a value into N copies.
-    pub(super) fn add_clone(&mut self, input: ValueId, count: usize) -> (CloneId, Vec<ValueId>) {
-        let clone_key = self.clones.reserve();
-        let clone_id = CloneId::new(clone_key);
-
+    pub(super) fn add_clone(
+        &mut self,
+        input: ValueId,
+        count: usize,
+    ) -> Result<(CloneId, Vec<ValueId>)> {
         // Clone preserves the input's type.
-        let ty = self.values.get(input.key()).map(|v| v.value_type).unwrap(); // FIXME: handle error?
+        let ty = self.value(input)?.get_type();
+
+        let slot = self.clones.reserve_slot();
+        let clone_id = slot.key();
 
         // Create outputs.
         let outputs: Vec<_> = (0..count)
-            .map(|p| self.create_value(Producer::Clone(clone_id), PortId::new(p), ty))
+            .map(|p| {
+                self.values
+                    .insert(Value::new(Producer::Clone(clone_id), PortId::new(p), ty))
+            })
             .collect();
 
+        slot.fill(CloneOperation {
+            input,
+            outputs: outputs.clone(),
+        });
+
         // Clone borrows the input.
         self.record_use(
             input,
@@ -490,21 +1114,12 @@ impl<G: Gate> Circuit<G> {
             Ownership::Borrow,
         );
 
-        let _ = self.clones.fill(
-            clone_key,
-            CloneOperation {
-                input,
-                outputs: outputs.clone(),
-            },
-        );
-
-        (clone_id, outputs)
+        Ok((clone_id, outputs))
     }
 
     /// Drop a value.
     pub(super) fn add_drop(&mut self, input: ValueId) -> DropId {
-        let drop_key = self.drops.insert(DropOperation { input });
-        let drop_id = DropId::new(drop_key);
+        let drop_id = self.drops.insert(DropOperation { input });
 
         // Drop moves the input.
         self.record_use(
@@ -519,62 +1134,137 @@ impl<G: Gate> Circuit<G> {
 
     /// Get a gate by id.
     pub(super) fn gate_op(&self, id: GateId) -> Result<&GateOperation<G>> {
-        self.gates.get(id.key()).ok_or(Error::GateNotFound(id))
+        self.gates.get(id).ok_or(Error::GateNotFound(id))
     }
 
     /// Get a clone by id.
     pub(super) fn clone_op(&self, id: CloneId) -> Result<&CloneOperation> {
-        self.clones.get(id.key()).ok_or(Error::CloneNotFound(id))
+        self.clones.get(id).ok_or(Error::CloneNotFound(id))
     }
 
     /// Get a drop by id.
     pub(super) fn drop_op(&self, id: DropId) -> Result<&DropOperation> {
-        self.drops.get(id.key()).ok_or(Error::DropNotFound(id))
+        self.drops.get(id).ok_or(Error::DropNotFound(id))
+    }
+
+    /// Get a constant by id.
+    pub(super) fn constant_op(&self, id: ConstantId) -> Result<&ConstantOperation<G>> {
+        self.constants.get(id).ok_or(Error::ConstantNotFound(id))
+    }
+
+    /// Replace the value of a constant.
+    pub(super) fn set_constant_value(&mut self, id: ConstantId, value: G::Constant) -> Result<()> {
+        let constant = self
+            .constants
+            .get_mut(id)
+            .ok_or(Error::ConstantNotFound(id))?;
+        constant.value = value;
+        Ok(())
     }
 
     /// Get a input by id.
     pub(super) fn input_op(&self, id: InputId) -> Result<&InputOperation> {
-        self.inputs.get(id.key()).ok_or(Error::InputNotFound(id))
+        self.inputs.get(id).ok_or(Error::InputNotFound(id))
     }
 
     /// Get a output by id.
     pub(super) fn output_op(&self, id: OutputId) -> Result<&OutputOperation> {
-        self.outputs.get(id.key()).ok_or(Error::OutputNotFound(id))
+        self.outputs.get(id).ok_or(Error::OutputNotFound(id))
     }
 
     /// Get a value by id.
     pub(super) fn value(&self, id: ValueId) -> Result<&Value<G>> {
-        self.values.get(id.key()).ok_or(Error::ValueNotFound(id))
+        self.values.get(id).ok_or(Error::ValueNotFound(id))
     }
 
-    /// Remove a gate by id (does not update cross-references).
+    /// Remove a gate by id (does not update cross-references, drops its annotations).
     pub(super) fn remove_gate_unchecked(&mut self, id: GateId) {
-        self.gates.remove(id.key());
+        self.invalidate_schedule();
+        self.gates.remove(id);
+        self.annotations.clear_operation(Operation::Gate(id));
     }
 
-    /// Remove a clone by id (does not update cross-references).
+    /// Remove a clone by id (does not update cross-references, drops its annotations).
     pub(super) fn remove_clone_unchecked(&mut self, id: CloneId) {
-        self.clones.remove(id.key());
+        self.invalidate_schedule();
+        self.clones.remove(id);
+        self.annotations.clear_operation(Operation::Clone(id));
     }
 
-    /// Remove a drop by id (does not update cross-references).
+    /// Remove a drop by id (does not update cross-references, drops its annotations).
     pub(super) fn remove_drop_unchecked(&mut self, id: DropId) {
-        self.drops.remove(id.key());
+        self.invalidate_schedule();
+        self.drops.remove(id);
+        self.annotations.clear_operation(Operation::Drop(id));
     }
 
-    /// Remove an input by id (does not update cross-references).
+    /// Remove a gate together with its output values, detaching it from its inputs.
+    ///
+    /// The outputs must no longer be used: rewire their uses first.
+    pub(super) fn remove_gate(&mut self, id: GateId) -> Result<()> {
+        self.invalidate_schedule();
+        let gate = self.gates.remove(id).ok_or(Error::GateNotFound(id))?;
+        self.annotations.clear_operation(Operation::Gate(id));
+        self.forget_uses(&gate.inputs, Consumer::Gate(id));
+        for output in gate.outputs {
+            self.values.remove(output);
+        }
+        Ok(())
+    }
+
+    /// Remove a clone together with its output values, detaching it from its input.
+    ///
+    /// The outputs must no longer be used: rewire their uses first.
+    pub(super) fn remove_clone(&mut self, id: CloneId) -> Result<()> {
+        self.invalidate_schedule();
+        let clone = self.clones.remove(id).ok_or(Error::CloneNotFound(id))?;
+        self.annotations.clear_operation(Operation::Clone(id));
+        self.forget_uses(&[clone.input], Consumer::Clone(id));
+        for output in clone.outputs {
+            self.values.remove(output);
+        }
+        Ok(())
+    }
+
+    /// Remove a constant together with its output value.
+    ///
+    /// The output must no longer be used: rewire its uses first.
+    pub(super) fn remove_constant(&mut self, id: ConstantId) -> Result<()> {
+        self.invalidate_schedule();
+        let constant = self
+            .constants
+            .remove(id)
+            .ok_or(Error::ConstantNotFound(id))?;
+        self.annotations.clear_operation(Operation::Constant(id));
+        self.values.remove(constant.output);
+        Ok(())
+    }
+
+    /// Remove a constant by id (does not update cross-references, drops its annotations).
+    pub(super) fn remove_constant_unchecked(&mut self, id: ConstantId) {
+        self.invalidate_schedule();
+        self.constants.remove(id);
+        self.annotations.clear_operation(Operation::Constant(id));
+    }
+
+    /// Remove an input by id (does not update cross-references, drops its annotations).
     pub(super) fn remove_input_unchecked(&mut self, id: InputId) {
-        self.inputs.remove(id.key());
+        self.invalidate_schedule();
+        self.inputs.remove(id);
+        self.annotations.clear_operation(Operation::Input(id));
     }
 
-    /// Remove an output by id (does not update cross-references).
+    /// Remove an output by id (does not update cross-references, drops its annotations).
     pub(super) fn remove_output_unchecked(&mut self, id: OutputId) {
-        self.outputs.remove(id.key());
+        self.invalidate_schedule();
+        self.outputs.remove(id);
+        self.annotations.clear_operation(Operation::Output(id));
     }
 
     /// Remove a value by id (does not update cross-references).
     pub(super) fn remove_value_unchecked(&mut self, id: ValueId) {
-        self.values.remove(id.key());
+        self.invalidate_schedule();
+        self.values.remove(id);
     }
 
     /// Number of gates.
@@ -592,6 +1282,11 @@ impl<G: Gate> Circuit<G> {
         self.drops.len()
     }
 
+    /// Number of constants.
+    pub(super) fn constant_count(&self) -> usize {
+        self.constants.len()
+    }
+
     /// Number of circuit inputs.
     pub(super) fn input_count(&self) -> usize {
         self.inputs.len()
@@ -609,56 +1304,79 @@ impl<G: Gate> Circuit<G> {
 
     /// Iterate over all gates.
     pub(super) fn all_gates(&self) -> impl Iterator<Item = (GateId, &GateOperation<G>)> {
-        self.gates.iter().map(|(k, g)| (GateId::new(k), g))
+        self.gates.iter()
     }
 
     /// Iterate over all clones.
     pub(super) fn all_clones(&self) -> impl Iterator<Item = (CloneId, &CloneOperation)> {
-        self.clones.iter().map(|(k, c)| (CloneId::new(k), c))
+        self.clones.iter()
     }
 
     /// Iterate over all drops.
     pub(super) fn all_drops(&self) -> impl Iterator<Item = (DropId, &DropOperation)> {
-        self.drops.iter().map(|(k, d)| (DropId::new(k), d))
+        self.drops.iter()
+    }
+
+    /// Iterate over all constants.
+    pub(super) fn all_constants(
+        &self,
+    ) -> impl Iterator<Item = (ConstantId, &ConstantOperation<G>)> {
+        self.constants.iter()
     }
 
     /// Iterate over all circuit inputs.
     pub(super) fn all_inputs(&self) -> impl Iterator<Item = (InputId, &InputOperation)> {
-        self.inputs.iter().map(|(k, op)| (InputId::new(k), op))
+        self.inputs.iter()
     }
 
     /// Iterate over all circuit outputs.
     pub(super) fn all_outputs(&self) -> impl Iterator<Item = (OutputId, &OutputOperation)> {
-        self.outputs.iter().map(|(k, op)| (OutputId::new(k), op))
+        self.outputs.iter()
     }
 
     /// Iterate over all values.
     pub(super) fn all_values(&self) -> impl Iterator<Item = (ValueId, &Value<G>)> {
-        self.values.iter().map(|(k, v)| (ValueId::new(k), v))
+        self.values.iter()
     }
 
     /// Iterate over all operations in the circuit.
     pub(super) fn all_operations(&self) -> impl Iterator<Item = Operation> + '_ {
         self.all_inputs()
             .map(|(id, _)| Operation::Input(id))
+            .chain(self.all_constants().map(|(id, _)| Operation::Constant(id)))
             .chain(self.all_gates().map(|(id, _)| Operation::Gate(id)))
             .chain(self.all_clones().map(|(id, _)| Operation::Clone(id)))
             .chain(self.all_drops().map(|(id, _)| Operation::Drop(id)))
             .chain(self.all_outputs().map(|(id, _)| Operation::Output(id)))
     }
 
+    /// Values consumed by an operation.
+    pub(super) fn consumed_values(&self, op: Operation) -> Result<Vec<ValueId>> {
+        Ok(match op {
+            Operation::Input(_) | Operation::Constant(_) => Vec::new(),
+            Operation::Gate(id) => self.gate_op(id)?.get_inputs().to_vec(),
+            Operation::Clone(id) => Vec::from([self.clone_op(id)?.get_input()]),
+            Operation::Drop(id) => Vec::from([self.drop_op(id)?.get_input()]),
+            Operation::Output(id) => Vec::from([self.output_op(id)?.get_input()]),
+        })
+    }
+
     /// Iterate over values produced by an operation.
     pub(super) fn produced_values(&self, op: Operation) -> impl Iterator<Item = ValueId> {
         let (input_val, gate_vals, clone_vals): (Option<ValueId>, &[ValueId], &[ValueId]) = match op
         {
             Operation::Input(id) => {
-                let val = self.inputs.get(id.key()).map(|i| i.output);
+                let val = self.inputs.get(id).map(|i| i.output);
+                (val, &[], &[])
+            }
+            Operation::Constant(id) => {
+                let val = self.constants.get(id).map(|c| c.output);
                 (val, &[], &[])
             }
             Operation::Gate(id) => {
                 let vals = self
                     .gates
-                    .get(id.key())
+                    .get(id)
                     .map(|g| g.outputs.as_slice())
                     .unwrap_or(&[]);
                 (None, vals, &[])
@@ -666,7 +1384,7 @@ impl<G: Gate> Circuit<G> {
             Operation::Clone(id) => {
                 let vals = self
                     .clones
-                    .get(id.key())
+                    .get(id)
                     .map(|c| c.outputs.as_slice())
                     .unwrap_or(&[]);
                 (None, &[], vals)
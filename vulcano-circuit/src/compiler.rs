@@ -0,0 +1,106 @@
+//! Compilation pipeline
+//!
+//! This module provides a single entry point taking a circuit to an
+//! executable form: it optimizes the circuit, repairs ownership, orders the
+//! operations and assigns wires to values. One analyzer is shared by every
+//! stage, so analyses computed while optimizing are reused afterwards.
+//!
+//! Compilation is deterministic: every stage breaks ties by handle order
+//! rather than by hash iteration, so identical circuits compile to identical
+//! programs and plans can be cached and compared across runs.
+
+use std::sync::Arc;
+
+use crate::{
+    analyzer::analyses::wire_allocation::{AllocationStrategy, WireAllocation},
+    circuit::{Circuit, Operation},
+    error::Result,
+    gate::Gate,
+    optimizer::{
+        Optimizer, OptimizerPass, budget::OptimizeBudget,
+        passes::reconcile_ownership::reconcile_ownership,
+    },
+    profile::Profile,
+};
+
+/// A circuit ready for execution.
+pub(super) struct CompiledProgram<G: Gate, S: AllocationStrategy> {
+    /// The optimized circuit.
+    pub circuit: Circuit<G>,
+    /// Operations in execution order.
+    pub order: Vec<Operation>,
+    /// Wires assigned to the values of the circuit.
+    pub wires: Arc<WireAllocation<S>>,
+    /// Whether optimization stopped early because the budget was exhausted.
+    pub stopped_early: bool,
+}
+
+/// Runs the compilation stages on circuits.
+pub(super) struct Compiler<G: Gate> {
+    /// Optimizer running the configured passes.
+    optimizer: Optimizer<G>,
+}
+
+impl<G: Gate> Compiler<G> {
+    /// Create a compiler without optimization passes.
+    pub(super) fn new() -> Self {
+        Self {
+            optimizer: Optimizer::new(),
+        }
+    }
+
+    /// Set the profile used as cost model by passes and analyses.
+    pub(super) fn set_profile(&mut self, profile: Profile) {
+        self.optimizer.set_profile(profile);
+    }
+
+    /// Set the budget limiting optimization.
+    pub(super) fn set_budget(&mut self, budget: OptimizeBudget) {
+        self.optimizer.set_budget(budget);
+    }
+
+    /// Add an optimization pass.
+    pub(super) fn add_pass(&mut self, pass: OptimizerPass<G>) {
+        self.optimizer.add_pass(pass);
+    }
+
+    /// Compile a circuit, assigning wires with strategy `S`.
+    ///
+    /// Ownership is reconciled after the configured passes, and the wire
+    /// allocation is verified before it is returned.
+    pub(super) fn compile<S: AllocationStrategy>(
+        &mut self,
+        circuit: Circuit<G>,
+    ) -> Result<CompiledProgram<G, S>> {
+        // Analyses cached for a previous circuit do not apply to this one.
+        self.optimizer.analyzer_mut().invalidate_all();
+        let circuit = self.optimizer.optimize(circuit)?;
+        let stopped_early = self.optimizer.stopped_early();
+
+        let analyzer = self.optimizer.analyzer_mut();
+        let (circuit, preserved) = reconcile_ownership(circuit, analyzer)
+            .map_err(|e| e.with_context("reconciling ownership"))?;
+        analyzer.invalidate_except(&preserved);
+
+        // Live ranges and wires are computed over the circuit schedule, so
+        // the program runs its operations in that order.
+        let order = circuit.iter_scheduled()?.collect();
+        let wires = analyzer.get::<WireAllocation<S>>(&circuit)?;
+        wires
+            .verify(&circuit)
+            .map_err(|e| e.with_context("verifying wire allocation"))?;
+
+        Ok(CompiledProgram {
+            circuit,
+            order,
+            wires,
+            stopped_early,
+        })
+    }
+}
+
+impl<G: Gate> Default for Compiler<G> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,219 @@
+//! Circuit diffs
+//!
+//! This module compares two versions of a circuit, typically before and
+//! after an optimizer pass, and reports which operations were added,
+//! removed, changed or rewired.
+//!
+//! Operations are first matched by structural hash, which pairs every
+//! operation whose whole computation is unchanged. Matches then spread from
+//! consumers to producers: the operations feeding the same port of matched
+//! operations are matched when they are of the same kind, even if something
+//! upstream of them changed. Circuit outputs are matched by position.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Write,
+    hash::Hash,
+};
+
+use crate::{
+    circuit::{Circuit, Operation},
+    error::Result,
+    gate::Gate,
+};
+
+/// An input of a matched operation fed from a different place.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct Rewire {
+    /// The operation in the old circuit.
+    pub before: Operation,
+    /// The operation in the new circuit.
+    pub after: Operation,
+    /// The input port that changed.
+    pub port: usize,
+}
+
+/// Differences between two circuits.
+#[derive(Debug, Clone, Default)]
+pub(super) struct CircuitDiff {
+    /// Operations of the old circuit paired with their counterpart.
+    pub matched: Vec<(Operation, Operation)>,
+    /// Operations only in the new circuit.
+    pub added: Vec<Operation>,
+    /// Operations only in the old circuit.
+    pub removed: Vec<Operation>,
+    /// Matched gates or constants whose gate or value differ.
+    pub changed: Vec<(Operation, Operation)>,
+    /// Inputs of matched operations fed from a different place.
+    pub rewired: Vec<Rewire>,
+}
+
+impl CircuitDiff {
+    /// Returns true if both circuits are structurally equal.
+    pub(super) fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.changed.is_empty()
+            && self.rewired.is_empty()
+    }
+
+    /// Render the differences as text, one per line.
+    ///
+    /// Removed operations are prefixed with `-`, added ones with `+`, changed
+    /// ones with `~` and rewired ports with `>`.
+    pub(super) fn render<G: Gate>(&self, before: &Circuit<G>, after: &Circuit<G>) -> String {
+        let mut text = String::new();
+        // Writing into a string cannot fail.
+        for &op in &self.removed {
+            let _ = writeln!(text, "- {}", before.describe(op));
+        }
+        for &op in &self.added {
+            let _ = writeln!(text, "+ {}", after.describe(op));
+        }
+        for &(old, new) in &self.changed {
+            let _ = writeln!(
+                text,
+                "~ {} -> {}",
+                before.describe(old),
+                after.describe(new)
+            );
+        }
+        for rewire in &self.rewired {
+            let _ = writeln!(
+                text,
+                "> {} -> {}: port {}",
+                before.describe(rewire.before),
+                after.describe(rewire.after),
+                rewire.port
+            );
+        }
+        text
+    }
+}
+
+/// Compare two circuits.
+///
+/// Fails if either circuit contains a cycle or dangling references.
+pub(super) fn diff<G>(before: &Circuit<G>, after: &Circuit<G>) -> Result<CircuitDiff>
+where
+    G: Gate + Hash,
+    G::Operand: Hash,
+    G::Constant: Hash,
+{
+    let before_hashes = before.operation_hashes()?;
+    let after_hashes = after.operation_hashes()?;
+
+    // Pair operations with equal hashes, in arena order.
+    let mut candidates: HashMap<u64, VecDeque<Operation>> = HashMap::new();
+    for op in after.all_operations() {
+        candidates
+            .entry(after_hashes[&op])
+            .or_default()
+            .push_back(op);
+    }
+    let mut forward: HashMap<Operation, Operation> = HashMap::new();
+    let mut backward: HashMap<Operation, Operation> = HashMap::new();
+    for op in before.all_operations() {
+        if let Some(other) = candidates
+            .get_mut(&before_hashes[&op])
+            .and_then(VecDeque::pop_front)
+        {
+            forward.insert(op, other);
+            backward.insert(other, op);
+        }
+    }
+
+    // Outputs keep their position.
+    for ((old, _), (new, _)) in before.all_outputs().zip(after.all_outputs()) {
+        let (old, new) = (Operation::Output(old), Operation::Output(new));
+        if !forward.contains_key(&old) && !backward.contains_key(&new) {
+            forward.insert(old, new);
+            backward.insert(new, old);
+        }
+    }
+
+    // Spread matches from consumers to producers, port by port.
+    let mut worklist: Vec<(Operation, Operation)> = before
+        .all_operations()
+        .filter_map(|op| forward.get(&op).map(|&other| (op, other)))
+        .collect();
+    while let Some((old, new)) = worklist.pop() {
+        let old_inputs = before.consumed_values(old)?;
+        let new_inputs = after.consumed_values(new)?;
+        for (&old_value, &new_value) in old_inputs.iter().zip(&new_inputs) {
+            let old_producer = Operation::from(before.value(old_value)?.get_producer());
+            let new_producer = Operation::from(after.value(new_value)?.get_producer());
+            if forward.contains_key(&old_producer)
+                || backward.contains_key(&new_producer)
+                || !same_kind(before, old_producer, after, new_producer)?
+            {
+                continue;
+            }
+            forward.insert(old_producer, new_producer);
+            backward.insert(new_producer, old_producer);
+            worklist.push((old_producer, new_producer));
+        }
+    }
+
+    let mut report = CircuitDiff::default();
+    for old in before.all_operations() {
+        let Some(&new) = forward.get(&old) else {
+            report.removed.push(old);
+            continue;
+        };
+        report.matched.push((old, new));
+
+        let changed = match (old, new) {
+            (Operation::Gate(a), Operation::Gate(b)) => {
+                before.gate_op(a)?.get_gate() != after.gate_op(b)?.get_gate()
+            }
+            (Operation::Constant(_), Operation::Constant(_)) => {
+                before_hashes[&old] != after_hashes[&new]
+            }
+            _ => false,
+        };
+        if changed {
+            report.changed.push((old, new));
+        }
+
+        let old_inputs = before.consumed_values(old)?;
+        let new_inputs = after.consumed_values(new)?;
+        for (port, (&old_value, &new_value)) in old_inputs.iter().zip(&new_inputs).enumerate() {
+            let old_source = before.value(old_value)?;
+            let new_source = after.value(new_value)?;
+            let old_producer = Operation::from(old_source.get_producer());
+            let new_producer = Operation::from(new_source.get_producer());
+            if forward.get(&old_producer) != Some(&new_producer)
+                || old_source.get_port() != new_source.get_port()
+            {
+                report.rewired.push(Rewire {
+                    before: old,
+                    after: new,
+                    port,
+                });
+            }
+        }
+    }
+    report.added = after
+        .all_operations()
+        .filter(|op| !backward.contains_key(op))
+        .collect();
+
+    Ok(report)
+}
+
+/// Returns true if two operations can stand for each other: both are of the
+/// same kind and gates take as many inputs.
+fn same_kind<G: Gate>(
+    before: &Circuit<G>,
+    old: Operation,
+    after: &Circuit<G>,
+    new: Operation,
+) -> Result<bool> {
+    Ok(match (old, new) {
+        (Operation::Gate(a), Operation::Gate(b)) => {
+            before.gate_op(a)?.get_inputs().len() == after.gate_op(b)?.get_inputs().len()
+        }
+        _ => std::mem::discriminant(&old) == std::mem::discriminant(&new),
+    })
+}
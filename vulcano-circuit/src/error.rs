@@ -2,13 +2,33 @@
 //!
 //! These errors are returned when callers attempt invalid operations.
 
-use std::any::TypeId;
+use std::{any::TypeId, panic::Location};
 
 use crate::{
     circuit::Operation,
-    handles::{CloneId, DropId, GateId, InputId, OutputId, ValueId},
+    handles::{CloneId, ConstantId, DropId, GateId, InputId, OutputId, ValueId},
 };
 
+/// An operation on a dependency cycle and the value it feeds to the next.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct CycleStep {
+    /// The operation.
+    pub operation: Operation,
+    /// Name of the gate, for gates that have one.
+    pub name: Option<&'static str>,
+    /// Value produced by the operation and consumed by the next one.
+    pub value: ValueId,
+    /// Description of the operation, with its name and origins, captured
+    /// when the cycle was found.
+    pub description: String,
+}
+
+impl std::fmt::Display for CycleStep {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} -[{}]->", self.description, self.value)
+    }
+}
+
 /// Errors that can occur in this crate.
 #[derive(Debug)]
 pub(super) enum Error {
@@ -18,6 +38,8 @@ pub(super) enum Error {
     CloneNotFound(CloneId),
     /// Drop not found.
     DropNotFound(DropId),
+    /// Constant not found.
+    ConstantNotFound(ConstantId),
     /// Value not found.
     ValueNotFound(ValueId),
     /// Input not found.
@@ -38,24 +60,89 @@ pub(super) enum Error {
     /// Tried to convert an invalid operation.
     BadOperationConversion(Operation),
 
-    /// Cycle detected in circuit during topological sort.
-    CycleDetected(Vec<Operation>),
+    /// Cycle detected in circuit during topological sort, as a sequence of
+    /// operations each feeding the next and the last feeding the first.
+    CycleDetected(Vec<CycleStep>),
 
     /// Analysis cache missing entry.
     AnalysisCacheInconsistentEntry(TypeId),
     /// Analysis cache type mismatch.
     AnalysisCacheTypeMismatch(TypeId),
+
+    /// Traced operator without a gate implementing it.
+    MissingOperatorGate(&'static str),
+
+    /// Traced operator applied to values of different tracers.
+    ForeignTracedValue(&'static str),
+
+    /// Malformed line in a profile, by line number.
+    InvalidProfileLine(usize),
+
+    /// Malformed line in a Bristol Fashion circuit, by line number.
+    InvalidBristolLine(usize),
+
+    /// Operation that cannot be written in the requested format.
+    UnsupportedExport(Operation),
+
+    /// Rewrite rule using a pattern variable its left-hand side does not bind.
+    UnboundPatternVariable(usize),
+
+    /// Binding for a parameter the circuit does not have.
+    UnknownParameter(String),
+
+    /// Circuit input whose value is never used.
+    UnusedInput(InputId),
+    /// Value never moved by any operation.
+    UnconsumedValue(ValueId),
+    /// Value moved by more than one operation.
+    OverconsumedValue { value: ValueId, moves: usize },
+
+    /// Operations placed on more devices than there are parties.
+    TooManyDevices { parties: usize },
+
+    /// Value without a wire in a wire allocation.
+    UnallocatedValue(ValueId),
+
+    /// Value read from a wire after another value was written to it.
+    WireOverwritten { value: ValueId, wire: usize },
+
+    /// Error raised by a builder call at the given source location.
+    Located {
+        location: &'static Location<'static>,
+        source: Box<Error>,
+    },
+    /// Error annotated with a description of what was being done.
+    Context { context: String, source: Box<Error> },
+}
+
+impl Error {
+    /// Wrap the error with a description of what was being done when it happened.
+    pub(super) fn with_context(self, context: impl Into<String>) -> Self {
+        Error::Context {
+            context: context.into(),
+            source: Box::new(self),
+        }
+    }
+
+    /// Get the innermost error, skipping locations and contexts.
+    pub(super) fn root_cause(&self) -> &Error {
+        match self {
+            Error::Located { source, .. } | Error::Context { source, .. } => source.root_cause(),
+            _ => self,
+        }
+    }
 }
 
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Error::GateNotFound(id) => write!(f, "gate not found: {:?}", id),
-            Error::CloneNotFound(id) => write!(f, "clone not found: {:?}", id),
-            Error::DropNotFound(id) => write!(f, "drop not found: {:?}", id),
-            Error::ValueNotFound(id) => write!(f, "value not found: {:?}", id),
-            Error::InputNotFound(id) => write!(f, "input not found: {:?}", id),
-            Error::OutputNotFound(id) => write!(f, "output not found: {:?}", id),
+            Error::GateNotFound(id) => write!(f, "gate not found: {}", id),
+            Error::CloneNotFound(id) => write!(f, "clone not found: {}", id),
+            Error::DropNotFound(id) => write!(f, "drop not found: {}", id),
+            Error::ConstantNotFound(id) => write!(f, "constant not found: {}", id),
+            Error::ValueNotFound(id) => write!(f, "value not found: {}", id),
+            Error::InputNotFound(id) => write!(f, "input not found: {}", id),
+            Error::OutputNotFound(id) => write!(f, "output not found: {}", id),
             Error::WrongInputCount { expected, got } => {
                 write!(f, "wrong input count: expected {}, got {}", expected, got)
             }
@@ -66,7 +153,7 @@ impl std::fmt::Display for Error {
                 write!(f, "invalid output index: {} (max {})", idx, max)
             }
             Error::TypeMismatch { gate, port } => {
-                write!(f, "type mismatch at gate {:?} port {}", gate, port)
+                write!(f, "type mismatch at {} port {}", gate, port)
             }
             Error::WrongInputTypeCount { expected, got } => {
                 write!(
@@ -76,10 +163,17 @@ impl std::fmt::Display for Error {
                 )
             }
             Error::BadOperationConversion(op) => {
-                write!(f, "bad operation conversion: {:?}", op)
+                write!(f, "bad operation conversion: {}", op)
             }
-            Error::CycleDetected(ops) => {
-                write!(f, "cycle detected involving {} operations", ops.len())
+            Error::CycleDetected(steps) => {
+                write!(f, "cycle detected")?;
+                for (i, step) in steps.iter().enumerate() {
+                    write!(f, "{}{}", if i == 0 { ": " } else { " " }, step)?;
+                }
+                if let Some(first) = steps.first() {
+                    write!(f, " {}", first.operation)?;
+                }
+                Ok(())
             }
             Error::AnalysisCacheInconsistentEntry(id) => {
                 write!(f, "analysis cache inconsistent: {:?}", id)
@@ -87,11 +181,47 @@ impl std::fmt::Display for Error {
             Error::AnalysisCacheTypeMismatch(id) => {
                 write!(f, "analysis cache type mismatch: {:?}", id)
             }
+            Error::MissingOperatorGate(op) => write!(f, "no gate implements operator {}", op),
+            Error::ForeignTracedValue(op) => {
+                write!(f, "operator {} applied to values of another tracer", op)
+            }
+            Error::InvalidProfileLine(line) => write!(f, "invalid profile line {}", line),
+            Error::InvalidBristolLine(line) => write!(f, "invalid bristol circuit line {}", line),
+            Error::UnsupportedExport(op) => write!(f, "cannot export operation: {}", op),
+            Error::UnboundPatternVariable(var) => {
+                write!(f, "rewrite rule uses unbound pattern variable {}", var)
+            }
+            Error::UnknownParameter(name) => write!(f, "unknown parameter {}", name),
+            Error::UnusedInput(id) => write!(f, "input {} is never used", id),
+            Error::UnconsumedValue(value) => write!(f, "value {} is never consumed", value),
+            Error::OverconsumedValue { value, moves } => {
+                write!(f, "value {} is consumed {} times", value, moves)
+            }
+            Error::TooManyDevices { parties } => {
+                write!(f, "operations placed on more than {} devices", parties)
+            }
+            Error::UnallocatedValue(value) => write!(f, "value {} has no wire", value),
+            Error::WireOverwritten { value, wire } => {
+                write!(
+                    f,
+                    "value {} read from wire {} after it was overwritten",
+                    value, wire
+                )
+            }
+            Error::Located { location, source } => write!(f, "{} (at {})", source, location),
+            Error::Context { context, source } => write!(f, "{}: {}", context, source),
         }
     }
 }
 
-impl std::error::Error for Error {}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Located { source, .. } | Error::Context { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
 
 /// Result type alias for this crate.
 pub(super) type Result<T> = std::result::Result<T, Error>;
@@ -18,6 +18,9 @@ pub(super) trait Gate: Eq + Copy {
     /// The type descriptor for operands (e.g., ciphertext, plaintext).
     type Operand: Eq + Copy;
 
+    /// Compile-time known value of an operand (e.g., a plaintext scalar).
+    type Constant: Clone;
+
     /// Returns the operand type at the given input index.
     fn input_type(&self, idx: usize) -> Result<Self::Operand>;
 
@@ -27,6 +30,18 @@ pub(super) trait Gate: Eq + Copy {
     /// Returns the access mode for the input at the given index.
     fn access_mode(&self, idx: usize) -> Result<Ownership>;
 
+    /// Evaluate the gate on constant inputs.
+    ///
+    /// Returns one constant per output, or `None` if the gate cannot be folded.
+    fn fold(&self, _inputs: &[Self::Constant]) -> Option<Vec<Self::Constant>> {
+        None
+    }
+
+    /// Human readable name of the gate, used in diagnostics.
+    fn name(&self) -> Option<&'static str> {
+        None
+    }
+
     /// Returns an iterator over all input types.
     fn input_types(&self) -> Result<impl Iterator<Item = Self::Operand>> {
         (0..self.input_count())
@@ -51,3 +66,126 @@ pub(super) trait Gate: Eq + Copy {
             .map(|v| v.into_iter())
     }
 }
+
+/// Algebraic identities declared by a gate.
+///
+/// Identities only apply to gates with two inputs and one output, and are
+/// used by the algebraic simplification pass.
+pub(super) trait GateIdentities: Gate {
+    /// Returns true if `a op b == b op a`.
+    fn is_commutative(&self) -> bool {
+        false
+    }
+
+    /// Returns true if `(a op b) op c == a op (b op c)`.
+    fn is_associative(&self) -> bool {
+        false
+    }
+
+    /// Returns the constant `e` such that `x op e == x`.
+    ///
+    /// For commutative gates it is also a left identity.
+    fn identity(&self) -> Option<Self::Constant> {
+        None
+    }
+
+    /// Returns the constant `z` such that `x op z == z`.
+    ///
+    /// For commutative gates it is also a left annihilator.
+    fn annihilator(&self) -> Option<Self::Constant> {
+        None
+    }
+}
+
+/// Rotation structure declared by a gate.
+///
+/// Rotations take one input and produce one output of the same type.
+/// Additions and slot-wise products take two inputs and produce one output.
+pub(super) trait RotationGates: Gate {
+    /// Returns the offset of the gate if it is a rotation.
+    fn rotation_offset(&self) -> Option<i64>;
+
+    /// Returns the same rotation gate with a different offset.
+    fn with_rotation_offset(&self, offset: i64) -> Self;
+
+    /// Returns true if the gate adds its inputs.
+    fn is_addition(&self) -> bool;
+
+    /// Returns true if the gate multiplies its inputs slot-wise, so that
+    /// rotating the product equals multiplying the rotated inputs.
+    fn is_slotwise_product(&self) -> bool {
+        false
+    }
+}
+
+/// Evaluation keys declared by a gate, for schemes with key switching.
+pub(super) trait EvaluationKeyGates: Gate {
+    /// Returns the degree of the ciphertext the gate relinearizes, if it does.
+    fn relinearization_degree(&self) -> Option<usize> {
+        None
+    }
+
+    /// Returns the offset of the rotation key the gate needs, if any.
+    fn rotation_key(&self) -> Option<i64> {
+        None
+    }
+
+    /// Returns true if the gate needs the conjugation key.
+    fn needs_conjugation_key(&self) -> bool {
+        false
+    }
+}
+
+/// Level structure declared by a gate, for leveled schemes.
+///
+/// Levels count how much of the modulus chain a value has used: circuit
+/// inputs and constants start at level 0 and each gate adds its cost to the
+/// highest level among its operands.
+pub(super) trait LeveledGates: Gate {
+    /// Returns the number of levels the gate consumes.
+    fn level_cost(&self) -> usize;
+
+    /// Returns the gate switching an operand of the given type down one
+    /// level, or `None` for types without levels.
+    ///
+    /// Mod switch gates take one input, produce one output of the same type
+    /// and cost one level.
+    fn mod_switch(operand: Self::Operand) -> Option<Self>;
+}
+
+/// Arithmetic gates used to record operator expressions.
+///
+/// Binary operations take two inputs and produce one output, negation
+/// takes one input and produces one output.
+pub(super) trait ArithmeticGates: Gate {
+    /// Gate adding two operands.
+    fn add() -> Self;
+
+    /// Gate multiplying two operands.
+    fn mul() -> Self;
+
+    /// Gate subtracting the second operand from the first, if any.
+    fn sub() -> Option<Self> {
+        None
+    }
+
+    /// Gate negating an operand, if any.
+    fn neg() -> Option<Self> {
+        None
+    }
+}
+
+/// Boolean gates used by the composite constructions of the standard library.
+///
+/// Binary operations take two inputs and produce one output, negation
+/// takes one input and produces one output.
+pub(super) trait BooleanGates: Gate {
+    /// Gate computing the conjunction of two bits.
+    fn and() -> Self;
+
+    /// Gate computing the exclusive disjunction of two bits.
+    fn xor() -> Self;
+
+    /// Gate negating a bit.
+    fn not() -> Self;
+}
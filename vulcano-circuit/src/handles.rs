@@ -1,107 +1,61 @@
 //! Handles used throughout the crate
 //!
 //! This module defines strongly-typed indices for circuit elements.
-//! Each handle wraps a generational key and prevents accidental mixing.
+//! Each handle wraps a generational key and prevents accidental mixing:
+//! arenas are typed by their handle, so a handle only indexes its own arena.
 
-use vulcano_arena::Key;
+use std::fmt;
 
-/// Handle identifying a gate in the circuit.
-#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
-pub struct GateId(Key);
-
-impl GateId {
-    /// Create a new gate id from a key.
-    pub fn new(key: Key) -> Self {
-        Self(key)
-    }
+use vulcano_arena::{KeyType, new_key_type};
 
-    /// Return the underlying key.
-    pub fn key(self) -> Key {
-        self.0
-    }
-}
+new_key_type! {
+    /// Handle identifying a gate in the circuit.
+    pub struct GateId;
 
-/// Handle identifying a clone operation in the circuit.
-#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
-pub struct CloneId(Key);
+    /// Handle identifying a clone operation in the circuit.
+    pub struct CloneId;
 
-impl CloneId {
-    /// Create a new clone id from a key.
-    pub fn new(key: Key) -> Self {
-        Self(key)
-    }
+    /// Handle identifying a drop operation in the circuit.
+    pub struct DropId;
 
-    /// Return the underlying key.
-    pub fn key(self) -> Key {
-        self.0
-    }
-}
-
-/// Handle identifying a drop operation in the circuit.
-#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
-pub struct DropId(Key);
-
-impl DropId {
-    /// Create a new drop id from a key.
-    pub fn new(key: Key) -> Self {
-        Self(key)
-    }
-
-    /// Return the underlying key.
-    pub fn key(self) -> Key {
-        self.0
-    }
-}
+    /// Handle identifying a constant in the circuit.
+    pub struct ConstantId;
 
-/// Handle identifying an SSA value in the circuit.
-///
-/// Each value is defined exactly once and consumed exactly once.
-/// A value can be borrowed any number of times before being consumed.
-#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
-pub struct ValueId(Key);
+    /// Handle identifying an SSA value in the circuit.
+    ///
+    /// Each value is defined exactly once and consumed exactly once.
+    /// A value can be borrowed any number of times before being consumed.
+    pub struct ValueId;
 
-impl ValueId {
-    /// Create a new value id from a key.
-    pub fn new(key: Key) -> Self {
-        Self(key)
-    }
+    /// Handle identifying a circuit input.
+    pub struct InputId;
 
-    /// Return the underlying key.
-    pub fn key(self) -> Key {
-        self.0
-    }
+    /// Handle identifying a circuit output.
+    pub struct OutputId;
 }
 
-/// Handle identifying a circuit input.
-#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
-pub struct InputId(Key);
-
-impl InputId {
-    /// Create a new input id from a key.
-    pub fn new(key: Key) -> Self {
-        Self(key)
-    }
-
-    /// Return the underlying key.
-    pub fn key(self) -> Key {
-        self.0
-    }
+/// Implement `Display` for handles as `<kind>#<index>@v<version>`.
+macro_rules! display_handle {
+    ($($name:ident => $kind:literal),* $(,)?) => {
+        $(
+            impl fmt::Display for $name {
+                fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    let key = self.key();
+                    write!(f, concat!($kind, "#{}@v{}"), key.index(), key.version())
+                }
+            }
+        )*
+    };
 }
 
-/// Handle identifying a circuit output.
-#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
-pub struct OutputId(Key);
-
-impl OutputId {
-    /// Create a new output id from a key.
-    pub fn new(key: Key) -> Self {
-        Self(key)
-    }
-
-    /// Return the underlying key.
-    pub fn key(self) -> Key {
-        self.0
-    }
+display_handle! {
+    GateId => "gate",
+    CloneId => "clone",
+    DropId => "drop",
+    ConstantId => "constant",
+    ValueId => "value",
+    InputId => "input",
+    OutputId => "output",
 }
 
 /// Handle identifying a port (input or output slot).
@@ -120,6 +74,12 @@ impl PortId {
     }
 }
 
+impl fmt::Display for PortId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "port {}", self.0)
+    }
+}
+
 /// Ownership mode for a use of a value.
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub(super) enum Ownership {
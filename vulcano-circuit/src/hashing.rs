@@ -0,0 +1,219 @@
+//! Structural hashing of circuits
+//!
+//! This module computes a canonical hash of a circuit: two circuits with the
+//! same topology, gates, constants and value types hash equally regardless of the
+//! handles assigned to their elements or the order operations were added in.
+//! Unbound parameters hash by name rather than by placeholder value.
+//! The hash is stable across process runs, so it can key on-disk caches.
+//!
+//! Each operation is hashed from its own description and the hashes of the
+//! values it consumes. The circuit hash combines the multiset of operation
+//! hashes with the ordered list of circuit outputs.
+
+use std::{
+    collections::{HashMap, HashSet},
+    hash::{Hash, Hasher},
+};
+
+use crate::{
+    circuit::{Circuit, Operation},
+    error::{Error, Result},
+    gate::Gate,
+    handles::ValueId,
+};
+
+/// Tags distinguishing operation kinds in the hash.
+const INPUT_TAG: u8 = 0;
+const GATE_TAG: u8 = 1;
+const CLONE_TAG: u8 = 2;
+const DROP_TAG: u8 = 3;
+const OUTPUT_TAG: u8 = 4;
+const CONSTANT_TAG: u8 = 5;
+const PARAMETER_TAG: u8 = 6;
+
+/// FNV-1a hasher.
+///
+/// Unlike the standard library hashers, its output is fixed by its
+/// definition and does not depend on the process or the Rust version.
+/// Integers are written as little-endian bytes, and pointer-sized ones as
+/// 64 bits, so the output does not depend on the platform either.
+struct StableHasher(u64);
+
+impl StableHasher {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    fn new() -> Self {
+        Self(Self::OFFSET_BASIS)
+    }
+}
+
+impl Hasher for StableHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(Self::PRIME);
+        }
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u128(&mut self, i: u128) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_isize(&mut self, i: isize) {
+        self.write_u64(i as i64 as u64);
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Hash a value with the stable hasher.
+fn stable_hash<T: Hash>(value: &T) -> u64 {
+    let mut hasher = StableHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl<G> Circuit<G>
+where
+    G: Gate + Hash,
+    G::Operand: Hash,
+    G::Constant: Hash,
+{
+    /// Compute the structural hash of the circuit.
+    ///
+    /// Inputs are identified by their position and outputs are ordered.
+    /// Fails if the circuit contains a cycle or dangling references.
+    pub(super) fn structural_hash(&self) -> Result<u64> {
+        let memo = self.operation_hashes()?;
+
+        let mut operation_hashes: Vec<u64> = memo.values().copied().collect();
+        // Sorting makes the hash independent of arena order.
+        operation_hashes.sort_unstable();
+
+        let output_hashes: Vec<u64> = self
+            .all_outputs()
+            .map(|(id, _)| memo[&Operation::Output(id)])
+            .collect();
+
+        Ok(stable_hash(&(operation_hashes, output_hashes)))
+    }
+
+    /// Compute the structural hash of every operation.
+    ///
+    /// Operations hash equally when they compute the same thing from inputs
+    /// at the same positions, whatever their handles.
+    pub(super) fn operation_hashes(&self) -> Result<HashMap<Operation, u64>> {
+        let mut memo: HashMap<Operation, u64> = HashMap::new();
+
+        // Inputs are the leaves of the computation, identified by position.
+        for (position, (id, input)) in self.all_inputs().enumerate() {
+            let ty = self.value(input.get_output())?.get_type();
+            memo.insert(
+                Operation::Input(id),
+                stable_hash(&(INPUT_TAG, position, ty)),
+            );
+        }
+
+        for op in self.all_operations() {
+            self.operation_hash(op, &mut memo)?;
+        }
+        Ok(memo)
+    }
+
+    /// Hash an operation, hashing the operations it depends on first.
+    fn operation_hash(&self, root: Operation, memo: &mut HashMap<Operation, u64>) -> Result<u64> {
+        // Iterative post-order traversal, to avoid overflowing the stack on deep circuits.
+        let mut stack = Vec::from([(root, false)]);
+        let mut on_path = HashSet::new();
+
+        while let Some((op, expanded)) = stack.pop() {
+            if memo.contains_key(&op) {
+                continue;
+            }
+
+            if !expanded {
+                // Reaching an operation still being expanded means we looped back.
+                if !on_path.insert(op) {
+                    return Err(Error::CycleDetected(self.find_cycle(&on_path)?));
+                }
+                stack.push((op, true));
+                for value in self.consumed_values(op)? {
+                    let producer = Operation::from(self.value(value)?.get_producer());
+                    if !memo.contains_key(&producer) {
+                        stack.push((producer, false));
+                    }
+                }
+                continue;
+            }
+
+            on_path.remove(&op);
+            let hash = match op {
+                // Inputs are hashed upfront, so this one is not in the circuit.
+                Operation::Input(id) => return Err(Error::InputNotFound(id)),
+                Operation::Constant(id) => {
+                    let constant = self.constant_op(id)?;
+                    let ty = self.value(constant.get_output())?.get_type();
+                    match self.parameter(id) {
+                        Some(name) => stable_hash(&(PARAMETER_TAG, name, ty)),
+                        None => stable_hash(&(CONSTANT_TAG, constant.get_value(), ty)),
+                    }
+                }
+                Operation::Gate(id) => {
+                    let gate = self.gate_op(id)?;
+                    let inputs = gate
+                        .get_inputs()
+                        .iter()
+                        .map(|&v| self.value_hash(v, memo))
+                        .collect::<Result<Vec<_>>>()?;
+                    stable_hash(&(GATE_TAG, gate.get_gate(), inputs))
+                }
+                Operation::Clone(id) => {
+                    let clone = self.clone_op(id)?;
+                    let input = self.value_hash(clone.get_input(), memo)?;
+                    stable_hash(&(CLONE_TAG, input, clone.output_count()))
+                }
+                Operation::Drop(id) => {
+                    let input = self.value_hash(self.drop_op(id)?.get_input(), memo)?;
+                    stable_hash(&(DROP_TAG, input))
+                }
+                Operation::Output(id) => {
+                    let input = self.value_hash(self.output_op(id)?.get_input(), memo)?;
+                    stable_hash(&(OUTPUT_TAG, input))
+                }
+            };
+            memo.insert(op, hash);
+        }
+
+        Ok(memo[&root])
+    }
+
+    /// Hash a value whose producer has already been hashed.
+    fn value_hash(&self, id: ValueId, memo: &HashMap<Operation, u64>) -> Result<u64> {
+        let value = self.value(id)?;
+        let producer = memo[&Operation::from(value.get_producer())];
+        Ok(stable_hash(&(
+            producer,
+            value.get_port().index(),
+            value.get_type(),
+        )))
+    }
+}
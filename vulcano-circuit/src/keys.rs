@@ -0,0 +1,47 @@
+//! Evaluation key requirements
+//!
+//! This module collects the evaluation keys a circuit needs, as declared by
+//! its gates, so key generation can create exactly those keys instead of
+//! every key the parameters allow.
+
+use std::collections::BTreeSet;
+
+use crate::{circuit::Circuit, gate::EvaluationKeyGates};
+
+/// Evaluation keys needed by a circuit.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(super) struct KeyRequirements {
+    /// Degrees of the ciphertexts to relinearize, in increasing order.
+    pub relinearization: BTreeSet<usize>,
+    /// Offsets of the rotation keys, in increasing order.
+    pub rotations: BTreeSet<i64>,
+    /// Whether the conjugation key is needed.
+    pub conjugation: bool,
+}
+
+impl KeyRequirements {
+    /// Returns true if no evaluation key is needed.
+    pub(super) fn is_empty(&self) -> bool {
+        self.relinearization.is_empty() && self.rotations.is_empty() && !self.conjugation
+    }
+}
+
+impl<G: EvaluationKeyGates> Circuit<G> {
+    /// Collect the evaluation keys needed by the gates of the circuit.
+    ///
+    /// Rotations by offset zero need no key and are skipped.
+    pub(super) fn required_keys(&self) -> KeyRequirements {
+        let mut keys = KeyRequirements::default();
+        for (_, gate) in self.all_gates() {
+            let gate = gate.get_gate();
+            if let Some(degree) = gate.relinearization_degree() {
+                keys.relinearization.insert(degree);
+            }
+            if let Some(offset) = gate.rotation_key().filter(|&offset| offset != 0) {
+                keys.rotations.insert(offset);
+            }
+            keys.conjugation |= gate.needs_conjugation_key();
+        }
+        keys
+    }
+}
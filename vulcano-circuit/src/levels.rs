@@ -0,0 +1,75 @@
+//! Ciphertext levels
+//!
+//! This module assigns each value of a circuit over a leveled scheme the
+//! level it reaches, from the level cost declared by each gate. Values of
+//! types without levels, such as plaintexts, get none.
+
+use vulcano_arena::SecondaryMap;
+
+use crate::{
+    circuit::{Circuit, Operation},
+    error::Result,
+    gate::LeveledGates,
+    handles::ValueId,
+};
+
+/// Level of each value of a circuit.
+pub(super) struct Levels {
+    /// Level of each value of a leveled type.
+    levels: SecondaryMap<usize, ValueId>,
+}
+
+impl Levels {
+    /// Get the level of a value, if its type has levels.
+    pub(super) fn level(&self, value: ValueId) -> Option<usize> {
+        self.levels.get(value).copied()
+    }
+
+    /// Highest level reached by any value, which the scheme parameters must
+    /// support.
+    pub(super) fn max_level(&self) -> usize {
+        self.levels.values().copied().max().unwrap_or(0)
+    }
+}
+
+impl<G: LeveledGates> Circuit<G> {
+    /// Compute the level of every value.
+    ///
+    /// A gate output reaches the highest level among the gate operands plus
+    /// the gate cost. Clones keep the level of their input.
+    pub(super) fn levels(&self) -> Result<Levels> {
+        let mut levels = SecondaryMap::with_key();
+        for op in self.iter_scheduled()? {
+            let (level, outputs) = match op {
+                Operation::Gate(id) => {
+                    let gate = self.gate_op(id)?;
+                    let highest = gate
+                        .get_inputs()
+                        .iter()
+                        .filter_map(|&value| levels.get(value))
+                        .max()
+                        .copied();
+                    let level = highest.unwrap_or(0) + gate.get_gate().level_cost();
+                    (level, gate.get_outputs().to_vec())
+                }
+                Operation::Clone(id) => {
+                    let clone = self.clone_op(id)?;
+                    let Some(&level) = levels.get(clone.get_input()) else {
+                        continue;
+                    };
+                    (level, clone.get_outputs().to_vec())
+                }
+                Operation::Input(_) | Operation::Constant(_) => {
+                    (0, self.produced_values(op).collect())
+                }
+                Operation::Drop(_) | Operation::Output(_) => continue,
+            };
+            for value in outputs {
+                if G::mod_switch(self.value(value)?.get_type()).is_some() {
+                    levels.insert(value, level);
+                }
+            }
+        }
+        Ok(Levels { levels })
+    }
+}
@@ -1,9 +1,40 @@
 //! High-level primitives for building, manipulating and evaluating computation circuits
 //! composed of arbitrary gates.
 
+#![allow(
+    dead_code,
+    reason = "the public interface is not exposed yet, so most items have no user outside the crate"
+)]
+
+#[cfg(feature = "bench")]
+pub mod bench;
+
 mod analyzer;
+mod annotations;
+mod bristol;
+mod check;
 mod circuit;
+mod compiler;
+mod diff;
 mod error;
 mod gate;
 mod handles;
+mod hashing;
+mod keys;
+mod levels;
+mod module;
 mod optimizer;
+mod origin;
+mod parameter;
+mod partition;
+mod placement;
+mod profile;
+mod provenance;
+mod scope;
+mod simulate;
+mod stdlib;
+mod template;
+mod tracer;
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,205 @@
+//! Circuit modules
+//!
+//! This module defines reusable named circuits and their instantiation.
+//! Instantiating a module inlines its body into the calling circuit, so
+//! every analysis and pass keeps working on flat circuits, and tags each
+//! inlined operation with the chain of instances it comes from. Nested
+//! instances extend the chain, so the hierarchy of the user program can be
+//! recovered from the flat circuit.
+
+use std::{collections::HashMap, panic::Location};
+
+use crate::{
+    circuit::{Circuit, Operation},
+    error::{Error, Result},
+    gate::Gate,
+    handles::ValueId,
+    origin::{Origin, Origins},
+};
+
+/// A named reusable circuit.
+///
+/// Its signature is given by the types of the body inputs and outputs.
+pub(super) struct Module<G: Gate> {
+    /// Name of the module.
+    name: String,
+    /// Circuit computing the module outputs from its inputs.
+    body: Circuit<G>,
+}
+
+impl<G: Gate> Module<G> {
+    /// Create a module from its body.
+    pub(super) fn new(name: impl Into<String>, body: Circuit<G>) -> Self {
+        Self {
+            name: name.into(),
+            body,
+        }
+    }
+
+    /// Name of the module.
+    pub(super) fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The module body.
+    pub(super) fn body(&self) -> &Circuit<G> {
+        &self.body
+    }
+
+    /// Types of the module inputs, in order.
+    pub(super) fn input_types(&self) -> Result<Vec<G::Operand>> {
+        self.body
+            .all_inputs()
+            .map(|(_, input)| Ok(self.body.value(input.get_output())?.get_type()))
+            .collect()
+    }
+
+    /// Types of the module outputs, in order.
+    pub(super) fn output_types(&self) -> Result<Vec<G::Operand>> {
+        self.body
+            .all_outputs()
+            .map(|(_, output)| Ok(self.body.value(output.get_input())?.get_type()))
+            .collect()
+    }
+}
+
+/// One level of the instance chain of an operation.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(super) struct InstanceFrame {
+    /// Name of the instantiated module.
+    pub module: String,
+    /// Number of the instance, unique within the circuit it was created in.
+    pub instance: usize,
+}
+
+/// Instances an operation was inlined from, outermost first.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(super) struct Hierarchy(Vec<InstanceFrame>);
+
+impl Hierarchy {
+    /// The instance chain, outermost first.
+    pub(super) fn frames(&self) -> &[InstanceFrame] {
+        &self.0
+    }
+}
+
+impl<G: Gate> Circuit<G> {
+    /// Inline an instance of a module, returning the values of its outputs.
+    ///
+    /// The inputs are consumed as the module body consumes its own inputs.
+    /// Inlined gates keep the origins recorded in the body in addition to
+    /// the location of this call.
+    #[track_caller]
+    pub(super) fn instantiate(
+        &mut self,
+        module: &Module<G>,
+        inputs: Vec<ValueId>,
+    ) -> Result<Vec<ValueId>> {
+        let location = Location::caller();
+        let body = module.body();
+        if inputs.len() != body.input_count() {
+            return Err(Error::WrongInputCount {
+                expected: body.input_count(),
+                got: inputs.len(),
+            });
+        }
+        let frame = InstanceFrame {
+            module: module.name().to_string(),
+            instance: self.take_instance_number(),
+        };
+
+        let mut mapped = HashMap::new();
+        for ((_, input), value) in body.all_inputs().zip(inputs) {
+            mapped.insert(input.get_output(), value);
+        }
+        let map = |mapped: &HashMap<ValueId, ValueId>, value: ValueId| {
+            mapped
+                .get(&value)
+                .copied()
+                .ok_or(Error::ValueNotFound(value))
+        };
+
+        let mut outputs = Vec::with_capacity(body.output_count());
+        for op in body.iter_scheduled()? {
+            let inlined = match op {
+                Operation::Input(_) => continue,
+                Operation::Output(id) => {
+                    outputs.push(map(&mapped, body.output_op(id)?.get_input())?);
+                    continue;
+                }
+                Operation::Constant(id) => {
+                    let constant = body.constant_op(id)?;
+                    let ty = body.value(constant.get_output())?.get_type();
+                    let (new_id, value) = self.add_constant(constant.get_value().clone(), ty);
+                    mapped.insert(constant.get_output(), value);
+                    Operation::Constant(new_id)
+                }
+                Operation::Gate(id) => {
+                    let gate = body.gate_op(id)?;
+                    let mut gate_inputs = Vec::with_capacity(gate.get_inputs().len());
+                    for &value in gate.get_inputs() {
+                        gate_inputs.push(map(&mapped, value)?);
+                    }
+                    let (new_id, values) = self
+                        .add_derived_gate(*gate.get_gate(), gate_inputs)
+                        .map_err(|source| Error::Located {
+                            location,
+                            source: Box::new(source),
+                        })?;
+                    self.annotations_mut().insert(
+                        Operation::Gate(new_id),
+                        Origins::single(Origin::Location(location)),
+                    );
+                    // Each instance is a distinct part of the program.
+                    self.mint_provenance(Operation::Gate(new_id));
+                    mapped.extend(gate.get_outputs().iter().copied().zip(values));
+                    Operation::Gate(new_id)
+                }
+                Operation::Clone(id) => {
+                    let clone = body.clone_op(id)?;
+                    let input = map(&mapped, clone.get_input())?;
+                    let (new_id, values) = self.add_clone(input, clone.output_count())?;
+                    mapped.extend(clone.get_outputs().iter().copied().zip(values));
+                    Operation::Clone(new_id)
+                }
+                Operation::Drop(id) => {
+                    let input = map(&mapped, body.drop_op(id)?.get_input())?;
+                    Operation::Drop(self.add_drop(input))
+                }
+            };
+
+            if let Some(origins) = body.origins(op) {
+                match self.annotations_mut().get_mut::<Origins>(inlined) {
+                    Some(existing) => existing.merge(origins),
+                    None => {
+                        self.annotations_mut().insert(inlined, origins.clone());
+                    }
+                }
+            }
+            let mut frames = Vec::from([frame.clone()]);
+            if let Some(inner) = body.hierarchy(op) {
+                frames.extend(inner.frames().iter().cloned());
+            }
+            self.annotations_mut().insert(inlined, Hierarchy(frames));
+        }
+        Ok(outputs)
+    }
+
+    /// Get the instances an operation was inlined from, if any.
+    pub(super) fn hierarchy(&self, op: Operation) -> Option<&Hierarchy> {
+        self.annotations().get::<Hierarchy>(op)
+    }
+
+    /// Iterate over the operations inlined from an instance of this circuit,
+    /// including those of the instances nested in it.
+    pub(super) fn instance_operations(
+        &self,
+        instance: usize,
+    ) -> impl Iterator<Item = Operation> + '_ {
+        self.all_operations().filter(move |&op| {
+            self.hierarchy(op)
+                .and_then(|h| h.frames().first())
+                .is_some_and(|frame| frame.instance == instance)
+        })
+    }
+}
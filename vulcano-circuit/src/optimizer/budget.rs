@@ -0,0 +1,57 @@
+//! Optimization budgets
+//!
+//! This module defines limits on the resources an optimization may use.
+//! The optimizer stops running passes once the budget is exhausted, and
+//! passes that iterate over the circuit check it to stop early.
+
+use std::time::{Duration, Instant};
+
+use crate::{circuit::Circuit, gate::Gate};
+
+/// Limits on the wall time and circuit growth of an optimization.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct OptimizeBudget {
+    /// Wall time the whole optimization may take, if limited.
+    pub max_wall_time: Option<Duration>,
+    /// Gates the circuit may grow by over its initial size, if limited.
+    pub max_gate_growth: Option<usize>,
+    /// When the optimization started and the gate count at that point.
+    start: Option<(Instant, usize)>,
+}
+
+impl OptimizeBudget {
+    /// Create a budget with the given limits.
+    pub(crate) fn new(max_wall_time: Option<Duration>, max_gate_growth: Option<usize>) -> Self {
+        Self {
+            max_wall_time,
+            max_gate_growth,
+            start: None,
+        }
+    }
+
+    /// Start measuring from now and from the size of the given circuit.
+    pub(crate) fn start<G: Gate>(&mut self, circuit: &Circuit<G>) {
+        self.start = Some((Instant::now(), circuit.gate_count()));
+    }
+
+    /// Wall time since the optimization started, if it did.
+    pub(crate) fn elapsed(&self) -> Option<Duration> {
+        self.start.map(|(instant, _)| instant.elapsed())
+    }
+
+    /// Check if any limit has been hit for the circuit being optimized.
+    ///
+    /// A budget that has not been started is never exhausted.
+    pub(crate) fn exhausted<G: Gate>(&self, circuit: &Circuit<G>) -> bool {
+        let Some((instant, initial_gates)) = self.start else {
+            return false;
+        };
+        let out_of_time = self
+            .max_wall_time
+            .is_some_and(|max| instant.elapsed() >= max);
+        let too_large = self
+            .max_gate_growth
+            .is_some_and(|max| circuit.gate_count() > initial_gates.saturating_add(max));
+        out_of_time || too_large
+    }
+}
@@ -0,0 +1,664 @@
+//! Equality saturation
+//!
+//! This module optimizes circuits with an e-graph: a compact representation
+//! of many equivalent circuits at once. Values are grouped into equivalence
+//! classes, and each class holds every known way of computing it.
+//!
+//! Rewrite rules add equivalent forms of the values they match until no rule
+//! adds anything new or a limit is hit. The cheapest form of every output is
+//! then extracted under a [`CostModel`]. Unlike a sequence of passes, no
+//! rewrite has to be chosen before knowing which others it enables.
+//!
+//! Gates are represented per output port, so multi-output gates are rebuilt
+//! once per distinct set of inputs. Clones and drops are not represented:
+//! the extracted circuit should go through ownership reconciliation.
+
+use std::{
+    collections::{HashMap, HashSet, hash_map::Entry},
+    hash::Hash,
+};
+
+use crate::{
+    circuit::{Circuit, Operation},
+    error::{Error, Result},
+    gate::Gate,
+    handles::ValueId,
+    profile::Profile,
+};
+
+/// Handle to an equivalence class.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(crate) struct ClassId(usize);
+
+/// A way of computing a value, over equivalence classes.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Node<G, C, O> {
+    /// Circuit input at the given position.
+    Input(usize),
+    /// Constant of the given type.
+    Constant(C, O),
+    /// Unbound parameter with its placeholder value and type.
+    Parameter(String, C, O),
+    /// Output port of a gate applied to the given classes.
+    Gate {
+        gate: G,
+        port: usize,
+        children: Vec<ClassId>,
+    },
+}
+
+/// Node over the gate set of a circuit.
+type GateNode<G> = Node<G, <G as Gate>::Constant, <G as Gate>::Operand>;
+
+/// Pattern matching and building values in rewrite rules.
+#[derive(Clone)]
+pub(crate) enum Pattern<G: Gate> {
+    /// Any value, bound to the given variable. Repeated variables must bind
+    /// the same class.
+    Var(usize),
+    /// A constant with the given value.
+    Constant(G::Constant),
+    /// Output port of a gate applied to sub-patterns.
+    Gate {
+        gate: G,
+        port: usize,
+        children: Vec<Pattern<G>>,
+    },
+}
+
+impl<G: Gate> Pattern<G> {
+    /// Pattern of the single output of a gate.
+    pub(crate) fn gate(gate: G, children: Vec<Pattern<G>>) -> Self {
+        Pattern::Gate {
+            gate,
+            port: 0,
+            children,
+        }
+    }
+
+    /// Collect the variables of the pattern.
+    fn vars(&self, vars: &mut HashSet<usize>) {
+        match self {
+            Pattern::Var(var) => {
+                vars.insert(*var);
+            }
+            Pattern::Constant(_) => {}
+            Pattern::Gate { children, .. } => children.iter().for_each(|c| c.vars(vars)),
+        }
+    }
+}
+
+/// A rewrite rule stating that values matching `lhs` equal `rhs`.
+#[derive(Clone)]
+pub(crate) struct Rewrite<G: Gate> {
+    /// Pattern to match.
+    lhs: Pattern<G>,
+    /// Equivalent form to add.
+    rhs: Pattern<G>,
+}
+
+impl<G: Gate> Rewrite<G> {
+    /// Create a rewrite rule.
+    ///
+    /// Fails if `rhs` uses a variable `lhs` does not bind.
+    pub(crate) fn new(lhs: Pattern<G>, rhs: Pattern<G>) -> Result<Self> {
+        let (mut bound, mut used) = (HashSet::new(), HashSet::new());
+        lhs.vars(&mut bound);
+        rhs.vars(&mut used);
+        if let Some(&var) = used.difference(&bound).min() {
+            return Err(Error::UnboundPatternVariable(var));
+        }
+        Ok(Self { lhs, rhs })
+    }
+}
+
+/// Cost of gates, used to extract the cheapest circuit.
+///
+/// Inputs and constants are free. Negative costs count as zero.
+pub(crate) trait CostModel<G: Gate> {
+    /// Cost of evaluating a gate once.
+    fn cost(&self, gate: &G) -> f64;
+}
+
+/// Cost model counting gates.
+pub(crate) struct UnitCost;
+
+impl<G: Gate> CostModel<G> for UnitCost {
+    fn cost(&self, _gate: &G) -> f64 {
+        1.0
+    }
+}
+
+/// Measured latencies by gate name, one for gates never measured.
+impl<G: Gate> CostModel<G> for Profile {
+    fn cost(&self, gate: &G) -> f64 {
+        gate.name()
+            .and_then(|name| self.name_latency(name))
+            .unwrap_or(1.0)
+    }
+}
+
+/// Limits on the growth of an e-graph during saturation.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SaturationLimits {
+    /// Maximum number of rounds applying all rules.
+    pub max_iterations: usize,
+    /// Number of nodes after which no further round starts.
+    pub max_nodes: usize,
+}
+
+impl Default for SaturationLimits {
+    fn default() -> Self {
+        Self {
+            max_iterations: 32,
+            max_nodes: 100_000,
+        }
+    }
+}
+
+/// Why saturation stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Saturation {
+    /// No rule adds anything new: every reachable form is represented.
+    Saturated,
+    /// The iteration limit was hit.
+    IterationLimit,
+    /// The node limit was hit.
+    NodeLimit,
+}
+
+/// Variable bindings of a match.
+type Subst = HashMap<usize, ClassId>;
+
+/// An e-graph of the values of a circuit.
+pub(crate) struct EGraph<G: Gate> {
+    /// Union-find parent of each class.
+    parents: Vec<usize>,
+    /// Nodes of each canonical class.
+    nodes: Vec<Vec<GateNode<G>>>,
+    /// Type of the values of each class.
+    types: Vec<G::Operand>,
+    /// Class of each canonical node.
+    memo: HashMap<GateNode<G>, ClassId>,
+    /// Types of the circuit inputs, by position.
+    inputs: Vec<G::Operand>,
+    /// Classes of the circuit outputs, in order.
+    outputs: Vec<ClassId>,
+}
+
+impl<G> EGraph<G>
+where
+    G: Gate + Hash,
+    G::Operand: Hash,
+    G::Constant: Eq + Hash,
+{
+    /// Build the e-graph of a circuit.
+    pub(crate) fn from_circuit(circuit: &Circuit<G>) -> Result<Self> {
+        let mut egraph = Self {
+            parents: Vec::new(),
+            nodes: Vec::new(),
+            types: Vec::new(),
+            memo: HashMap::new(),
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+        };
+
+        let mut classes: HashMap<ValueId, ClassId> = HashMap::new();
+        let class_of = |classes: &HashMap<ValueId, ClassId>, value: ValueId| {
+            classes
+                .get(&value)
+                .copied()
+                .ok_or(Error::ValueNotFound(value))
+        };
+        for op in circuit.iter_scheduled()? {
+            match op {
+                Operation::Input(id) => {
+                    let value = circuit.input_op(id)?.get_output();
+                    let ty = circuit.value(value)?.get_type();
+                    let class = egraph.add(Node::Input(egraph.inputs.len()), ty);
+                    egraph.inputs.push(ty);
+                    classes.insert(value, class);
+                }
+                Operation::Constant(id) => {
+                    let constant = circuit.constant_op(id)?;
+                    let ty = circuit.value(constant.get_output())?.get_type();
+                    let value = constant.get_value().clone();
+                    let node = match circuit.parameter(id) {
+                        Some(name) => Node::Parameter(name.to_string(), value, ty),
+                        None => Node::Constant(value, ty),
+                    };
+                    classes.insert(constant.get_output(), egraph.add(node, ty));
+                }
+                Operation::Gate(id) => {
+                    let gate_op = circuit.gate_op(id)?;
+                    let children = gate_op
+                        .get_inputs()
+                        .iter()
+                        .map(|&v| class_of(&classes, v))
+                        .collect::<Result<Vec<_>>>()?;
+                    for (port, &value) in gate_op.get_outputs().iter().enumerate() {
+                        let node = Node::Gate {
+                            gate: *gate_op.get_gate(),
+                            port,
+                            children: children.clone(),
+                        };
+                        let ty = circuit.value(value)?.get_type();
+                        classes.insert(value, egraph.add(node, ty));
+                    }
+                }
+                Operation::Clone(id) => {
+                    let clone = circuit.clone_op(id)?;
+                    let class = class_of(&classes, clone.get_input())?;
+                    for &value in clone.get_outputs() {
+                        classes.insert(value, class);
+                    }
+                }
+                Operation::Output(id) => {
+                    let class = class_of(&classes, circuit.output_op(id)?.get_input())?;
+                    egraph.outputs.push(class);
+                }
+                Operation::Drop(_) => {}
+            }
+        }
+        Ok(egraph)
+    }
+
+    /// Number of equivalence classes.
+    pub(crate) fn class_count(&self) -> usize {
+        self.memo
+            .values()
+            .map(|&c| self.find(c))
+            .collect::<HashSet<_>>()
+            .len()
+    }
+
+    /// Number of nodes.
+    pub(crate) fn node_count(&self) -> usize {
+        self.memo.len()
+    }
+
+    /// Check if two classes are known to be equal.
+    pub(crate) fn equivalent(&self, a: ClassId, b: ClassId) -> bool {
+        self.find(a) == self.find(b)
+    }
+
+    /// Classes of the circuit outputs, in order.
+    pub(crate) fn outputs(&self) -> &[ClassId] {
+        &self.outputs
+    }
+
+    /// Apply rewrite rules until saturation or a limit is hit.
+    pub(crate) fn saturate(
+        &mut self,
+        rules: &[Rewrite<G>],
+        limits: SaturationLimits,
+    ) -> Result<Saturation> {
+        for _ in 0..limits.max_iterations {
+            if self.node_count() >= limits.max_nodes {
+                return Ok(Saturation::NodeLimit);
+            }
+
+            // Match everything first, so a round sees a consistent e-graph.
+            let mut matches = Vec::new();
+            for rule in rules {
+                for class in self.canonical_classes() {
+                    for subst in self.match_pattern(&rule.lhs, class, Subst::new()) {
+                        matches.push((rule, class, subst));
+                    }
+                }
+            }
+
+            let before = (self.node_count(), self.class_count());
+            for (rule, class, subst) in matches {
+                let ty = self.types[self.find(class).0];
+                // Skip matches whose right-hand side would not type check,
+                // such as a variable bound to a class of another type.
+                if !self.well_typed(&rule.rhs, ty, &subst)? {
+                    continue;
+                }
+                let built = self.instantiate(&rule.rhs, ty, &subst)?;
+                self.union(class, built);
+            }
+            self.rebuild();
+
+            if (self.node_count(), self.class_count()) == before {
+                return Ok(Saturation::Saturated);
+            }
+        }
+        Ok(Saturation::IterationLimit)
+    }
+
+    /// Extract the cheapest circuit computing the outputs.
+    pub(crate) fn extract<M: CostModel<G>>(&self, model: &M) -> Result<Circuit<G>> {
+        let best = self.best_nodes(model);
+
+        let mut circuit = Circuit::new();
+        let inputs: Vec<ValueId> = self
+            .inputs
+            .iter()
+            .map(|&ty| circuit.add_input(ty).1)
+            .collect();
+
+        let mut values: HashMap<ClassId, ValueId> = HashMap::new();
+        let mut gates: HashMap<(G, Vec<ValueId>), Vec<ValueId>> = HashMap::new();
+        for &output in &self.outputs {
+            // Iterative post-order, to avoid overflowing the stack on deep circuits.
+            let mut stack = Vec::from([(self.find(output), false)]);
+            let mut on_path = HashSet::new();
+            while let Some((class, expanded)) = stack.pop() {
+                if values.contains_key(&class) {
+                    continue;
+                }
+                let node = best.get(&class).ok_or(Error::CycleDetected(Vec::new()))?;
+                if !expanded {
+                    if !on_path.insert(class) {
+                        return Err(Error::CycleDetected(Vec::new()));
+                    }
+                    stack.push((class, true));
+                    if let Node::Gate { children, .. } = node {
+                        stack.extend(children.iter().map(|&c| (self.find(c), false)));
+                    }
+                    continue;
+                }
+                on_path.remove(&class);
+
+                let value = match node {
+                    Node::Input(position) => inputs[*position],
+                    Node::Constant(constant, ty) => circuit.add_constant(constant.clone(), *ty).1,
+                    Node::Parameter(name, placeholder, ty) => {
+                        circuit
+                            .add_parameter_with(name.clone(), placeholder.clone(), *ty)
+                            .1
+                    }
+                    Node::Gate {
+                        gate,
+                        port,
+                        children,
+                    } => {
+                        let operands: Vec<ValueId> =
+                            children.iter().map(|&c| values[&self.find(c)]).collect();
+                        let outputs = match gates.entry((*gate, operands.clone())) {
+                            Entry::Occupied(entry) => entry.into_mut(),
+                            Entry::Vacant(entry) => {
+                                entry.insert(circuit.add_derived_gate(*gate, operands)?.1)
+                            }
+                        };
+                        *outputs.get(*port).ok_or(Error::InvalidOutputIndex {
+                            idx: *port,
+                            max: outputs.len(),
+                        })?
+                    }
+                };
+                values.insert(class, value);
+            }
+            circuit.add_output(values[&self.find(output)]);
+        }
+        Ok(circuit)
+    }
+
+    /// Add a node, returning its class.
+    fn add(&mut self, node: GateNode<G>, ty: G::Operand) -> ClassId {
+        let node = self.canonicalize(node);
+        if let Some(&class) = self.memo.get(&node) {
+            return self.find(class);
+        }
+        let class = ClassId(self.parents.len());
+        self.parents.push(class.0);
+        self.nodes.push(Vec::from([node.clone()]));
+        self.types.push(ty);
+        self.memo.insert(node, class);
+        class
+    }
+
+    /// Find the canonical class of a class.
+    fn find(&self, class: ClassId) -> ClassId {
+        let mut idx = class.0;
+        while self.parents[idx] != idx {
+            idx = self.parents[idx];
+        }
+        ClassId(idx)
+    }
+
+    /// Merge two classes. Congruence is restored by `rebuild`.
+    fn union(&mut self, a: ClassId, b: ClassId) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a == b {
+            return;
+        }
+        // Keep the older class as representative.
+        let (keep, merge) = if a < b { (a, b) } else { (b, a) };
+        self.parents[merge.0] = keep.0;
+        let nodes = std::mem::take(&mut self.nodes[merge.0]);
+        self.nodes[keep.0].extend(nodes);
+    }
+
+    /// Restore the invariants after unions: nodes refer to canonical classes,
+    /// and equal nodes belong to the same class.
+    fn rebuild(&mut self) {
+        loop {
+            let mut memo: HashMap<GateNode<G>, ClassId> = HashMap::new();
+            let mut merges = Vec::new();
+            for class in self.canonical_classes() {
+                let nodes = std::mem::take(&mut self.nodes[class.0]);
+                let mut canonical: Vec<GateNode<G>> = Vec::with_capacity(nodes.len());
+                for node in nodes {
+                    let node = self.canonicalize(node);
+                    if !canonical.contains(&node) {
+                        canonical.push(node);
+                    }
+                }
+                for node in &canonical {
+                    match memo.entry(node.clone()) {
+                        Entry::Occupied(entry) => merges.push((*entry.get(), class)),
+                        Entry::Vacant(entry) => {
+                            entry.insert(class);
+                        }
+                    }
+                }
+                self.nodes[class.0] = canonical;
+            }
+            if merges.is_empty() {
+                self.memo = memo;
+                return;
+            }
+            for (a, b) in merges {
+                self.union(a, b);
+            }
+        }
+    }
+
+    /// Rewrite the children of a node to their canonical classes.
+    fn canonicalize(&self, node: GateNode<G>) -> GateNode<G> {
+        match node {
+            Node::Gate {
+                gate,
+                port,
+                children,
+            } => Node::Gate {
+                gate,
+                port,
+                children: children.into_iter().map(|c| self.find(c)).collect(),
+            },
+            node => node,
+        }
+    }
+
+    /// Canonical classes, in creation order.
+    fn canonical_classes(&self) -> Vec<ClassId> {
+        (0..self.parents.len())
+            .filter(|&idx| self.parents[idx] == idx)
+            .map(ClassId)
+            .collect()
+    }
+
+    /// Match a pattern against a class, extending the given bindings.
+    fn match_pattern(&self, pattern: &Pattern<G>, class: ClassId, subst: Subst) -> Vec<Subst> {
+        let class = self.find(class);
+        match pattern {
+            Pattern::Var(var) => match subst.get(var) {
+                Some(&bound) if self.find(bound) != class => Vec::new(),
+                _ => {
+                    let mut subst = subst;
+                    subst.insert(*var, class);
+                    Vec::from([subst])
+                }
+            },
+            Pattern::Constant(value) => {
+                let found = self.nodes[class.0]
+                    .iter()
+                    .any(|node| matches!(node, Node::Constant(c, _) if c == value));
+                if found {
+                    Vec::from([subst])
+                } else {
+                    Vec::new()
+                }
+            }
+            Pattern::Gate {
+                gate,
+                port,
+                children,
+            } => {
+                let mut results = Vec::new();
+                for node in &self.nodes[class.0] {
+                    let Node::Gate {
+                        gate: node_gate,
+                        port: node_port,
+                        children: node_children,
+                    } = node
+                    else {
+                        continue;
+                    };
+                    if node_gate != gate
+                        || node_port != port
+                        || node_children.len() != children.len()
+                    {
+                        continue;
+                    }
+                    let mut partial = Vec::from([subst.clone()]);
+                    for (child, &child_class) in children.iter().zip(node_children) {
+                        partial = partial
+                            .into_iter()
+                            .flat_map(|s| self.match_pattern(child, child_class, s))
+                            .collect();
+                    }
+                    results.extend(partial);
+                }
+                results
+            }
+        }
+    }
+
+    /// Check that a pattern instantiated with a substitution has the given
+    /// type, and so do all the operands of its gates.
+    fn well_typed(&self, pattern: &Pattern<G>, ty: G::Operand, subst: &Subst) -> Result<bool> {
+        match pattern {
+            Pattern::Var(var) => {
+                let class = subst.get(var).ok_or(Error::UnboundPatternVariable(*var))?;
+                Ok(self.types[self.find(*class).0] == ty)
+            }
+            Pattern::Constant(_) => Ok(true),
+            Pattern::Gate {
+                gate,
+                port,
+                children,
+            } => {
+                if gate.output_type(*port)? != ty {
+                    return Ok(false);
+                }
+                for (idx, child) in children.iter().enumerate() {
+                    if !self.well_typed(child, gate.input_type(idx)?, subst)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+        }
+    }
+
+    /// Add the nodes of a pattern, returning the class of its root.
+    fn instantiate(
+        &mut self,
+        pattern: &Pattern<G>,
+        ty: G::Operand,
+        subst: &Subst,
+    ) -> Result<ClassId> {
+        match pattern {
+            Pattern::Var(var) => subst
+                .get(var)
+                .copied()
+                .ok_or(Error::UnboundPatternVariable(*var)),
+            Pattern::Constant(value) => Ok(self.add(Node::Constant(value.clone(), ty), ty)),
+            Pattern::Gate {
+                gate,
+                port,
+                children,
+            } => {
+                let mut classes = Vec::with_capacity(children.len());
+                for (idx, child) in children.iter().enumerate() {
+                    classes.push(self.instantiate(child, gate.input_type(idx)?, subst)?);
+                }
+                let node = Node::Gate {
+                    gate: *gate,
+                    port: *port,
+                    children: classes,
+                };
+                Ok(self.add(node, gate.output_type(*port)?))
+            }
+        }
+    }
+
+    /// Choose the cheapest node of every class.
+    ///
+    /// Costs are relaxed until no class improves. With non-negative costs a
+    /// class never improves through itself, so the choice has no cycles.
+    fn best_nodes<M: CostModel<G>>(&self, model: &M) -> HashMap<ClassId, GateNode<G>> {
+        let mut best: HashMap<ClassId, (f64, &GateNode<G>)> = HashMap::new();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for class in self.canonical_classes() {
+                for node in &self.nodes[class.0] {
+                    let cost = match node {
+                        Node::Input(_) | Node::Constant(..) | Node::Parameter(..) => Some(0.0),
+                        Node::Gate { gate, children, .. } => children
+                            .iter()
+                            .try_fold(model.cost(gate).max(0.0), |acc, c| {
+                                best.get(&self.find(*c)).map(|(cost, _)| acc + cost)
+                            }),
+                    };
+                    let Some(cost) = cost else {
+                        continue;
+                    };
+                    if best.get(&class).is_none_or(|(current, _)| cost < *current) {
+                        best.insert(class, (cost, node));
+                        changed = true;
+                    }
+                }
+            }
+        }
+        best.into_iter()
+            .map(|(class, (_, node))| (class, node.clone()))
+            .collect()
+    }
+}
+
+/// Optimize a circuit by equality saturation.
+///
+/// Builds the e-graph of the circuit, saturates it with the rules within the
+/// limits and extracts the cheapest equivalent circuit.
+pub(crate) fn equality_saturation<G, M>(
+    circuit: &Circuit<G>,
+    rules: &[Rewrite<G>],
+    limits: SaturationLimits,
+    model: &M,
+) -> Result<Circuit<G>>
+where
+    G: Gate + Hash,
+    G::Operand: Hash,
+    G::Constant: Eq + Hash,
+    M: CostModel<G>,
+{
+    let mut egraph = EGraph::from_circuit(circuit)?;
+    egraph.saturate(rules, limits)?;
+    egraph.extract(model)
+}
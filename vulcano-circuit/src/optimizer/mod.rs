@@ -3,22 +3,31 @@
 //! This module provides functionality to optimize circuits.
 //! Optimizations can leverage analyses provided by the Analyzer.
 
-mod passes;
+pub(super) mod budget;
+#[cfg(feature = "egraph")]
+pub(super) mod egraph;
+pub(super) mod passes;
 
 use std::any::TypeId;
 
-use crate::{analyzer::Analyzer, circuit::Circuit, error::Result, gate::Gate};
+use crate::{
+    analyzer::Analyzer, circuit::Circuit, error::Result, gate::Gate,
+    optimizer::budget::OptimizeBudget, profile::Profile,
+};
 
 /// A type alias for an optimizer pass function.
 ///
 /// Passes return a tuple containing the optimized circuit and a Vec of TypeIds
 /// representing the analyses they preserve.
-type OptimizerPass<T> = fn(Circuit<T>, &mut Analyzer<T>) -> Result<(Circuit<T>, Vec<TypeId>)>;
+pub(super) type OptimizerPass<T> =
+    fn(Circuit<T>, &mut Analyzer<T>) -> Result<(Circuit<T>, Vec<TypeId>)>;
 
 /// Manages and applies optimization passes to circuits.
 pub(super) struct Optimizer<T: Gate> {
     analyzer: Analyzer<T>,
     passes: Vec<OptimizerPass<T>>,
+    /// Whether the last run stopped early because the budget was exhausted.
+    stopped_early: bool,
 }
 
 impl<T: Gate> Optimizer<T> {
@@ -27,18 +36,50 @@ impl<T: Gate> Optimizer<T> {
         Self {
             analyzer: Analyzer::new(),
             passes: Vec::new(),
+            stopped_early: false,
         }
     }
 
+    /// Set the profile passes use as cost model.
+    pub(super) fn set_profile(&mut self, profile: Profile) {
+        self.analyzer.set_profile(profile);
+    }
+
+    /// Set the budget limiting each run of the optimizer.
+    pub(super) fn set_budget(&mut self, budget: OptimizeBudget) {
+        self.analyzer.set_budget(budget);
+    }
+
+    /// Check if the last run stopped before all passes because the budget
+    /// was exhausted.
+    pub(super) fn stopped_early(&self) -> bool {
+        self.stopped_early
+    }
+
+    /// Get the analyzer, holding the analyses valid for the last optimized
+    /// circuit, so later stages can reuse them.
+    pub(super) fn analyzer_mut(&mut self) -> &mut Analyzer<T> {
+        &mut self.analyzer
+    }
+
     /// Add an optimization pass.
     pub(super) fn add_pass(&mut self, pass: OptimizerPass<T>) {
         self.passes.push(pass);
     }
 
     /// Run all optimization passes on the circuit.
+    ///
+    /// Passes are skipped once the budget, if any, is exhausted.
     pub(super) fn optimize(&mut self, mut circuit: Circuit<T>) -> Result<Circuit<T>> {
-        for pass in &self.passes {
-            let (optimized_circuit, preserved_analyses) = pass(circuit, &mut self.analyzer)?;
+        self.analyzer.start_budget(&circuit);
+        self.stopped_early = false;
+        for (idx, pass) in self.passes.iter().enumerate() {
+            if self.analyzer.budget_exhausted(&circuit) {
+                self.stopped_early = true;
+                break;
+            }
+            let (optimized_circuit, preserved_analyses) = pass(circuit, &mut self.analyzer)
+                .map_err(|e| e.with_context(format!("running optimizer pass #{}", idx)))?;
             circuit = optimized_circuit;
             self.analyzer.invalidate_except(&preserved_analyses);
         }
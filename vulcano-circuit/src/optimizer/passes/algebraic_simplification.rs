@@ -0,0 +1,226 @@
+//! Algebraic Simplification Pass
+//!
+//! Simplifies binary gates using the identities they declare:
+//! - Commutative gates get their constant operand moved to the right.
+//! - Identity operations (`x + 0`) are replaced by their other operand, when
+//!   the gate moves it.
+//! - Annihilated operations (`x * 0`) are replaced by the annihilator, and
+//!   their moved operand is dropped.
+//! - Associative chains with constants (`(x + 1) + 2`) get their constants
+//!   folded together (`x + 3`).
+//!
+//! Only gates with two inputs and one output of matching types are considered.
+//! Simplification stops early when the optimization budget is exhausted.
+
+use std::any::TypeId;
+
+use crate::{
+    analyzer::{Analyzer, analyses::topological_order::TopologicalOrder},
+    circuit::{Circuit, Consumer, Operation, Producer},
+    error::Result,
+    gate::GateIdentities,
+    handles::{GateId, Ownership, PortId, ValueId},
+};
+
+/// Simplify the circuit using gate-declared algebraic identities.
+pub(crate) fn algebraic_simplification<G>(
+    mut circuit: Circuit<G>,
+    analyzer: &mut Analyzer<G>,
+) -> Result<(Circuit<G>, Vec<TypeId>)>
+where
+    G: GateIdentities,
+    G::Constant: PartialEq,
+{
+    let order = analyzer.get::<TopologicalOrder>(&circuit)?;
+
+    let mut changed = false;
+    for &op in order.iter() {
+        if analyzer.budget_exhausted(&circuit) {
+            break;
+        }
+        let Operation::Gate(id) = op else {
+            continue;
+        };
+        // Earlier simplifications may have removed this gate.
+        if circuit.gate_op(id).is_err() || !is_simplifiable(&circuit, id)? {
+            continue;
+        }
+
+        changed |= normalize_operands(&mut circuit, id)?;
+        changed |= if short_circuit(&mut circuit, id)? {
+            true
+        } else {
+            reassociate(&mut circuit, id)?
+        };
+    }
+
+    if !changed {
+        return Ok((circuit, Vec::from([TypeId::of::<TopologicalOrder>()])));
+    }
+
+    // All cached analyses are invalidated after mutation.
+    Ok((circuit, Vec::new()))
+}
+
+/// Check if a gate is binary with operands and result of the same type.
+fn is_simplifiable<G: GateIdentities>(circuit: &Circuit<G>, id: GateId) -> Result<bool> {
+    let gate = circuit.gate_op(id)?.get_gate();
+    if gate.input_count() != 2 || gate.output_count() != 1 {
+        return Ok(false);
+    }
+    let ty = gate.output_type(0)?;
+    Ok(gate.input_type(0)? == ty && gate.input_type(1)? == ty)
+}
+
+/// Get the constant behind a value, if it is produced by a constant.
+fn constant_of<G: GateIdentities>(
+    circuit: &Circuit<G>,
+    value: ValueId,
+) -> Result<Option<&G::Constant>> {
+    match circuit.value(value)?.get_producer() {
+        Producer::Constant(id) if circuit.parameter(id).is_none() => {
+            Ok(Some(circuit.constant_op(id)?.get_value()))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Move the constant operand of a commutative gate to the right.
+fn normalize_operands<G: GateIdentities>(circuit: &mut Circuit<G>, id: GateId) -> Result<bool> {
+    let gate_op = circuit.gate_op(id)?;
+    let gate = *gate_op.get_gate();
+    let [lhs, rhs] = [gate_op.get_inputs()[0], gate_op.get_inputs()[1]];
+
+    // Swapping must not change which operand is moved.
+    if !gate.is_commutative() || gate.access_mode(0)? != gate.access_mode(1)? {
+        return Ok(false);
+    }
+    if constant_of(circuit, lhs)?.is_none() || constant_of(circuit, rhs)?.is_some() {
+        return Ok(false);
+    }
+
+    circuit.swap_gate_inputs(id, PortId::new(0), PortId::new(1))?;
+    Ok(true)
+}
+
+/// Replace identity and annihilated operations by their result.
+fn short_circuit<G>(circuit: &mut Circuit<G>, id: GateId) -> Result<bool>
+where
+    G: GateIdentities,
+    G::Constant: PartialEq,
+{
+    let gate_op = circuit.gate_op(id)?;
+    let gate = *gate_op.get_gate();
+    let inputs = [gate_op.get_inputs()[0], gate_op.get_inputs()[1]];
+    let output = gate_op.get_outputs()[0];
+
+    // Operand ports holding the constant, right first.
+    let ports: &[usize] = if gate.is_commutative() { &[1, 0] } else { &[1] };
+
+    for &port in ports {
+        let Some(constant) = constant_of(circuit, inputs[port])? else {
+            continue;
+        };
+
+        // Forwarding a borrowed operand would add a use the circuit does not
+        // own, so only moved operands replace the result.
+        if gate.identity().as_ref() == Some(constant)
+            && gate.access_mode(1 - port)? == Ownership::Move
+        {
+            let other = inputs[1 - port];
+            circuit.replace_all_uses(output, other);
+            circuit.remove_gate(id)?;
+            remove_unused_constant(circuit, inputs[port])?;
+            return Ok(true);
+        }
+
+        if let Some(annihilator) = gate.annihilator()
+            && &annihilator == constant
+        {
+            let ty = circuit.value(output)?.get_type();
+            let (constant_id, value) = circuit.add_constant(annihilator, ty);
+            circuit.inherit_origins(Operation::Gate(id), Operation::Constant(constant_id));
+            circuit.replace_all_uses(output, value);
+            circuit.remove_gate(id)?;
+            // The other operand is no longer consumed, so a moved one is
+            // dropped instead of leaking.
+            if gate.access_mode(1 - port)? == Ownership::Move {
+                let drop_id = circuit.add_drop(inputs[1 - port]);
+                circuit.inherit_origins(Operation::Constant(constant_id), Operation::Drop(drop_id));
+            }
+            remove_unused_constant(circuit, inputs[port])?;
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+/// Fold `(x op c1) op c2` into `x op (c1 op c2)` for associative gates.
+fn reassociate<G: GateIdentities>(circuit: &mut Circuit<G>, id: GateId) -> Result<bool> {
+    let gate_op = circuit.gate_op(id)?;
+    let gate = *gate_op.get_gate();
+    let [inner_value, outer_constant] = [gate_op.get_inputs()[0], gate_op.get_inputs()[1]];
+
+    if !gate.is_associative() {
+        return Ok(false);
+    }
+    let Some(c2) = constant_of(circuit, outer_constant)? else {
+        return Ok(false);
+    };
+
+    // The inner operation must be the same gate, only feeding this one.
+    let inner = circuit.value(inner_value)?;
+    let Producer::Gate(inner_id) = inner.get_producer() else {
+        return Ok(false);
+    };
+    if inner.get_uses().len() != 1 {
+        return Ok(false);
+    }
+    let inner_op = circuit.gate_op(inner_id)?;
+    if *inner_op.get_gate() != gate {
+        return Ok(false);
+    }
+    let [x, inner_constant] = [inner_op.get_inputs()[0], inner_op.get_inputs()[1]];
+    let Some(c1) = constant_of(circuit, inner_constant)? else {
+        return Ok(false);
+    };
+
+    let Some(folded) = gate
+        .fold(&[c1.clone(), c2.clone()])
+        .and_then(|mut f| f.pop())
+    else {
+        return Ok(false);
+    };
+
+    let ty = circuit.value(outer_constant)?.get_type();
+    let (constant_id, constant) = circuit.add_constant(folded, ty);
+    circuit.inherit_origins(Operation::Gate(inner_id), Operation::Gate(id));
+
+    // Both gates are the same, so `x` keeps the access mode it had.
+    let consumer = Consumer::Gate(id);
+    circuit.rewire_use(inner_value, x, consumer, PortId::new(0));
+    circuit.rewire_use(outer_constant, constant, consumer, PortId::new(1));
+    circuit.inherit_origins(Operation::Gate(id), Operation::Constant(constant_id));
+    circuit.remove_gate(inner_id)?;
+    remove_unused_constant(circuit, inner_constant)?;
+    remove_unused_constant(circuit, outer_constant)?;
+
+    Ok(true)
+}
+
+/// Remove the constant producing a value if it is no longer used.
+fn remove_unused_constant<G: GateIdentities>(
+    circuit: &mut Circuit<G>,
+    value: ValueId,
+) -> Result<()> {
+    let Ok(val) = circuit.value(value) else {
+        return Ok(());
+    };
+    if let Producer::Constant(id) = val.get_producer()
+        && val.get_uses().is_empty()
+    {
+        circuit.remove_constant(id)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,128 @@
+//! Constant Folding Pass
+//!
+//! Evaluates operations whose inputs are all constants at compile time:
+//! - Gates are folded through `Gate::fold` and replaced by constants.
+//! - Clones of a constant are replaced by copies of the constant.
+//!
+//! Operations are visited in topological order, so folded results feed
+//! further folding downstream. Constants left without uses are removed.
+//! Folding stops early when the optimization budget is exhausted.
+
+use std::any::TypeId;
+
+use crate::{
+    analyzer::{Analyzer, analyses::topological_order::TopologicalOrder},
+    circuit::{Circuit, Operation, Producer},
+    error::Result,
+    gate::Gate,
+    handles::{CloneId, GateId, ValueId},
+};
+
+/// Fold constant subexpressions of the circuit.
+pub(crate) fn constant_folding<G: Gate>(
+    mut circuit: Circuit<G>,
+    analyzer: &mut Analyzer<G>,
+) -> Result<(Circuit<G>, Vec<TypeId>)> {
+    let order = analyzer.get::<TopologicalOrder>(&circuit)?;
+
+    let mut changed = false;
+    for &op in order.iter() {
+        if analyzer.budget_exhausted(&circuit) {
+            break;
+        }
+        changed |= match op {
+            Operation::Gate(id) => fold_gate(&mut circuit, id)?,
+            Operation::Clone(id) => fold_clone(&mut circuit, id)?,
+            _ => false,
+        };
+    }
+
+    if !changed {
+        return Ok((circuit, Vec::from([TypeId::of::<TopologicalOrder>()])));
+    }
+
+    // All cached analyses are invalidated after mutation.
+    Ok((circuit, Vec::new()))
+}
+
+/// Get the constant behind a value, if it is produced by a constant.
+fn constant_of<G: Gate>(circuit: &Circuit<G>, value: ValueId) -> Result<Option<G::Constant>> {
+    match circuit.value(value)?.get_producer() {
+        Producer::Constant(id) if circuit.parameter(id).is_none() => {
+            Ok(Some(circuit.constant_op(id)?.get_value().clone()))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Replace a gate with constant inputs by the constants it evaluates to.
+fn fold_gate<G: Gate>(circuit: &mut Circuit<G>, id: GateId) -> Result<bool> {
+    let gate_op = circuit.gate_op(id)?;
+    let gate = *gate_op.get_gate();
+    let inputs = gate_op.get_inputs().to_vec();
+    let outputs = gate_op.get_outputs().to_vec();
+
+    let mut constants = Vec::with_capacity(inputs.len());
+    for &input in &inputs {
+        match constant_of(circuit, input)? {
+            Some(constant) => constants.push(constant),
+            None => return Ok(false),
+        }
+    }
+
+    let Some(folded) = gate.fold(&constants) else {
+        return Ok(false);
+    };
+    if folded.len() != outputs.len() {
+        return Ok(false);
+    }
+
+    for (output, constant) in outputs.into_iter().zip(folded) {
+        let ty = circuit.value(output)?.get_type();
+        let (constant_id, value) = circuit.add_constant(constant, ty);
+        circuit.inherit_origins(Operation::Gate(id), Operation::Constant(constant_id));
+        circuit.replace_all_uses(output, value);
+    }
+    circuit.remove_gate(id)?;
+    remove_unused_constants(circuit, &inputs)?;
+
+    Ok(true)
+}
+
+/// Replace a clone of a constant by copies of the constant.
+fn fold_clone<G: Gate>(circuit: &mut Circuit<G>, id: CloneId) -> Result<bool> {
+    let clone_op = circuit.clone_op(id)?;
+    let input = clone_op.get_input();
+    let outputs = clone_op.get_outputs().to_vec();
+
+    let Some(constant) = constant_of(circuit, input)? else {
+        return Ok(false);
+    };
+
+    let ty = circuit.value(input)?.get_type();
+    for output in outputs {
+        let (constant_id, value) = circuit.add_constant(constant.clone(), ty);
+        circuit.inherit_origins(Operation::Clone(id), Operation::Constant(constant_id));
+        circuit.replace_all_uses(output, value);
+    }
+    circuit.remove_clone(id)?;
+    remove_unused_constants(circuit, &[input])?;
+
+    Ok(true)
+}
+
+/// Remove the constants producing the given values if they are no longer used.
+fn remove_unused_constants<G: Gate>(circuit: &mut Circuit<G>, values: &[ValueId]) -> Result<()> {
+    for &value in values {
+        let Ok(val) = circuit.value(value) else {
+            // Already removed through a duplicated input.
+            continue;
+        };
+        if let Producer::Constant(id) = val.get_producer()
+            && val.get_uses().is_empty()
+        {
+            circuit.remove_constant(id)?;
+        }
+    }
+    Ok(())
+}
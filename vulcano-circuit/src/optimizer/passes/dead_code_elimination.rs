@@ -45,6 +45,12 @@ pub(crate) fn dead_code_elimination<G: Gate>(
         .map(|(id, _)| id)
         .collect();
 
+    let unreachable_constants: Vec<_> = circuit
+        .all_constants()
+        .filter(|(id, _)| !reachability.is_operation_reachable(Operation::Constant(*id)))
+        .map(|(id, _)| id)
+        .collect();
+
     let unreachable_inputs: Vec<_> = circuit
         .all_inputs()
         .filter(|(id, _)| !reachability.is_operation_reachable(Operation::Input(*id)))
@@ -74,6 +80,9 @@ pub(crate) fn dead_code_elimination<G: Gate>(
     for id in unreachable_drops {
         circuit.remove_drop_unchecked(id);
     }
+    for id in unreachable_constants {
+        circuit.remove_constant_unchecked(id);
+    }
     for id in unreachable_inputs {
         circuit.remove_input_unchecked(id);
     }
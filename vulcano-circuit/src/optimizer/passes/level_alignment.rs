@@ -0,0 +1,74 @@
+//! Level Alignment Pass
+//!
+//! Brings the operands of each gate over a leveled scheme to the same level
+//! by inserting the mod switch gates provided by the scheme in front of the
+//! operands at lower levels. Operands switched to the same level for several
+//! gates share one chain of mod switches.
+//!
+//! Shared switches are consumed once per gate, so the circuit should go
+//! through ownership reconciliation afterwards.
+
+use std::{any::TypeId, collections::HashMap};
+
+use crate::{
+    analyzer::Analyzer,
+    circuit::{Circuit, Consumer, Operation},
+    error::Result,
+    gate::LeveledGates,
+    handles::{GateId, PortId, ValueId},
+};
+
+/// Insert mod switches so that every gate gets its operands at one level.
+pub(crate) fn level_alignment<G: LeveledGates>(
+    mut circuit: Circuit<G>,
+    _analyzer: &mut Analyzer<G>,
+) -> Result<(Circuit<G>, Vec<TypeId>)> {
+    let levels = circuit.levels()?;
+    let gates: Vec<GateId> = circuit
+        .iter_scheduled()?
+        .filter_map(|op| match op {
+            Operation::Gate(id) => Some(id),
+            _ => None,
+        })
+        .collect();
+
+    // Values switched down from each value, by level reached.
+    let mut switched: HashMap<(ValueId, usize), ValueId> = HashMap::new();
+
+    for id in gates {
+        let inputs = circuit.gate_op(id)?.get_inputs().to_vec();
+        let Some(target) = inputs.iter().filter_map(|&v| levels.level(v)).max() else {
+            continue;
+        };
+        for (port, value) in inputs.into_iter().enumerate() {
+            let Some(level) = levels.level(value) else {
+                continue;
+            };
+            if level == target {
+                continue;
+            }
+
+            let ty = circuit.value(value)?.get_type();
+            let mut current = value;
+            for reached in level + 1..=target {
+                current = match switched.get(&(value, reached)) {
+                    Some(&next) => next,
+                    None => {
+                        let Some(switch) = G::mod_switch(ty) else {
+                            break;
+                        };
+                        let (switch_id, outputs) =
+                            circuit.add_derived_gate(switch, Vec::from([current]))?;
+                        circuit.inherit_origins(Operation::Gate(id), Operation::Gate(switch_id));
+                        switched.insert((value, reached), outputs[0]);
+                        outputs[0]
+                    }
+                };
+            }
+            circuit.rewire_use(value, current, Consumer::Gate(id), PortId::new(port));
+        }
+    }
+
+    // All cached analyses are invalidated after mutation.
+    Ok((circuit, Vec::new()))
+}
@@ -2,5 +2,9 @@
 //!
 //! This module contains the optimizer passes used to optimize the circuit.
 
-mod dead_code_elimination;
-mod reconcile_ownership;
+pub(crate) mod algebraic_simplification;
+pub(crate) mod constant_folding;
+pub(crate) mod dead_code_elimination;
+pub(crate) mod level_alignment;
+pub(crate) mod reconcile_ownership;
+pub(crate) mod rotation_scheduling;
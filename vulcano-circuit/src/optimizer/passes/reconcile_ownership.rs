@@ -3,45 +3,104 @@
 //! Fixes ownership issues in the circuit:
 //! - Inserts drops for leaked values (never consumed).
 //! - Inserts clones for overconsumed values (moved multiple times).
+//!
+//! Inserted operations inherit the origins of the value's producer.
 
 use std::any::TypeId;
 
 use crate::{
     analyzer::{Analyzer, analyses::ownership_issues::OwnershipIssues},
-    circuit::Circuit,
+    circuit::{Circuit, Operation},
     error::Result,
     gate::Gate,
 };
 
+/// Configuration of ownership reconciliation.
+#[derive(Clone, Debug)]
+pub(crate) struct ReconcileConfig {
+    /// Whether to drop leaked values.
+    pub drop_leaked: bool,
+    /// Maximum number of outputs of each inserted clone, if limited.
+    ///
+    /// Copies beyond it are spread over several clones of the value.
+    pub max_clone_fanout: Option<usize>,
+}
+
+impl Default for ReconcileConfig {
+    fn default() -> Self {
+        Self {
+            drop_leaked: true,
+            max_clone_fanout: None,
+        }
+    }
+}
+
+/// What ownership reconciliation inserted.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct ReconcileReport {
+    /// Drops inserted for leaked values.
+    pub drops: usize,
+    /// Clones inserted for overconsumed values.
+    pub clones: usize,
+    /// Copies produced by the inserted clones.
+    pub copies: usize,
+}
+
 /// Reconcile ownership issues by inserting drops and clones.
 pub(crate) fn reconcile_ownership<G: Gate>(
-    mut circuit: Circuit<G>,
+    circuit: Circuit<G>,
     analyzer: &mut Analyzer<G>,
 ) -> Result<(Circuit<G>, Vec<TypeId>)> {
+    let (circuit, preserved, _) =
+        reconcile_ownership_with(circuit, analyzer, &ReconcileConfig::default())?;
+    Ok((circuit, preserved))
+}
+
+/// Reconcile ownership issues with the given configuration, reporting what
+/// was inserted.
+pub(crate) fn reconcile_ownership_with<G: Gate>(
+    mut circuit: Circuit<G>,
+    analyzer: &mut Analyzer<G>,
+    config: &ReconcileConfig,
+) -> Result<(Circuit<G>, Vec<TypeId>, ReconcileReport)> {
+    let mut report = ReconcileReport::default();
+
     // Get ownership analysis.
     let issues = analyzer.get::<OwnershipIssues>(&circuit)?;
 
     // Insert drops for leaked values.
-    for value_id in issues.leaked() {
-        circuit.add_drop(value_id);
+    if config.drop_leaked {
+        for value_id in issues.leaked() {
+            let producer = circuit.value(value_id)?.get_producer();
+            let drop_id = circuit.add_drop(value_id);
+            circuit.inherit_origins(producer.into(), Operation::Drop(drop_id));
+            report.drops += 1;
+        }
     }
 
     // Insert clones for overconsumed values.
+    let max_fanout = config.max_clone_fanout.unwrap_or(usize::MAX).max(1);
     for (value_id, move_count) in issues.overconsumed() {
-        // One consumer uses the original, the rest use clone outputs.
-        let clone_count = move_count - 1;
-
-        // Get all move usages before inserting clone.
+        // Get all move usages before inserting clones.
         let move_uses = circuit.get_move_uses(value_id);
+        let producer = circuit.value(value_id)?.get_producer();
 
-        // Insert clone that produces (N-1) copies.
-        let (_, clone_outputs) = circuit.add_clone(value_id, clone_count);
+        // One consumer uses the original, the rest use clone outputs.
+        let mut copies = Vec::with_capacity(move_count - 1);
+        while copies.len() < move_count - 1 {
+            let count = (move_count - 1 - copies.len()).min(max_fanout);
+            let (clone_id, clone_outputs) = circuit.add_clone(value_id, count)?;
+            circuit.inherit_origins(producer.into(), Operation::Clone(clone_id));
+            copies.extend(clone_outputs);
+            report.clones += 1;
+        }
+        report.copies += copies.len();
 
         // Rewire all but the first move to use clone outputs instead.
-        for (usage, clone_output) in move_uses.iter().skip(1).zip(clone_outputs.iter()) {
+        for (usage, clone_output) in move_uses.iter().skip(1).zip(copies.iter()) {
             circuit.rewire_use(value_id, *clone_output, usage.consumer, usage.port);
         }
     }
 
-    Ok((circuit, Vec::new()))
+    Ok((circuit, Vec::new(), report))
 }
@@ -0,0 +1,441 @@
+//! Rotation Scheduling Pass
+//!
+//! Rewrites sums of rotations of the same value into a baby-step giant-step
+//! structure. A sum of `n` terms `rot(x, k) [* c_k]` costs `n` rotations.
+//! Splitting each offset as `k = g * j + b` gives:
+//!
+//! `sum_j rot(sum_b rot(x, b) [* rot(c_k, -g * j)], g * j)`
+//!
+//! which only needs one rotation per distinct baby step `b` and one per
+//! distinct giant step `j`, roughly `2 * sqrt(n)` rotations.
+//!
+//! Rotations of constant product operands are considered free, as constant
+//! folding evaluates them at compile time. The giant step is chosen to
+//! minimize the number of rotations, and sums are only rewritten when this
+//! reduces it. The rewrite may introduce multiple uses of a value, so
+//! ownership must be reconciled afterwards. Sums are rewritten one at a time
+//! until the optimization budget is exhausted.
+
+use std::{
+    any::TypeId,
+    collections::{BTreeMap, HashMap, HashSet},
+};
+
+use crate::{
+    analyzer::Analyzer,
+    circuit::{Circuit, Consumer, Operation, Producer},
+    error::Result,
+    gate::RotationGates,
+    handles::{GateId, ValueId},
+};
+
+/// A rotated term of a sum.
+struct Term {
+    /// Rotation offset.
+    offset: i64,
+    /// The rotation gate.
+    rotation: GateId,
+    /// Slot-wise product applied to the rotation, if any.
+    product: Option<Product>,
+}
+
+/// A slot-wise product of a rotation and another operand.
+struct Product {
+    /// The product gate.
+    gate: GateId,
+    /// The operand that is not rotated.
+    operand: ValueId,
+}
+
+/// Terms of a sum rotating the same value in the same way.
+struct Group<G> {
+    /// The rotated value.
+    source: ValueId,
+    /// The rotation gate, with offset zero.
+    rotation: G,
+    /// The product gate and the port of the rotated operand, if any.
+    product: Option<(G, usize)>,
+    /// The terms of the group.
+    terms: Vec<Term>,
+}
+
+/// Rewrite sums of rotations into baby-step giant-step form.
+pub(crate) fn rotation_scheduling<G: RotationGates>(
+    mut circuit: Circuit<G>,
+    analyzer: &mut Analyzer<G>,
+) -> Result<(Circuit<G>, Vec<TypeId>)> {
+    let mut roots = Vec::new();
+    for (id, _) in circuit.all_gates() {
+        if is_sum_root(&circuit, id)? {
+            roots.push(id);
+        }
+    }
+
+    let mut changed = false;
+    for root in roots {
+        if analyzer.budget_exhausted(&circuit) {
+            break;
+        }
+        changed |= schedule_sum(&mut circuit, root)?;
+    }
+
+    if !changed {
+        return Ok((circuit, analyzer.cached_analyses()));
+    }
+
+    // All cached analyses are invalidated after mutation.
+    Ok((circuit, Vec::new()))
+}
+
+/// Check if a gate is a binary addition.
+fn is_addition<G: RotationGates>(gate: &G) -> bool {
+    gate.is_addition() && gate.input_count() == 2 && gate.output_count() == 1
+}
+
+/// Check if a gate is an addition not feeding a larger sum.
+fn is_sum_root<G: RotationGates>(circuit: &Circuit<G>, id: GateId) -> Result<bool> {
+    let gate_op = circuit.gate_op(id)?;
+    if !is_addition(gate_op.get_gate()) {
+        return Ok(false);
+    }
+    let uses = circuit.value(gate_op.get_outputs()[0])?.get_uses();
+    Ok(match uses {
+        [usage] => match usage.consumer {
+            Consumer::Gate(parent) => circuit.gate_op(parent)?.get_gate() != gate_op.get_gate(),
+            _ => true,
+        },
+        _ => true,
+    })
+}
+
+/// Collect the additions of a sum tree, parents first, and its terms.
+fn collect_sum<G: RotationGates>(
+    circuit: &Circuit<G>,
+    root: GateId,
+) -> Result<(Vec<GateId>, Vec<ValueId>)> {
+    let adder = *circuit.gate_op(root)?.get_gate();
+    let mut additions = Vec::new();
+    let mut terms = Vec::new();
+    let mut stack = Vec::from([root]);
+
+    while let Some(id) = stack.pop() {
+        additions.push(id);
+        for &input in circuit.gate_op(id)?.get_inputs() {
+            let value = circuit.value(input)?;
+            match value.get_producer() {
+                Producer::Gate(child)
+                    if value.get_uses().len() == 1
+                        && *circuit.gate_op(child)?.get_gate() == adder =>
+                {
+                    stack.push(child)
+                }
+                _ => terms.push(input),
+            }
+        }
+    }
+
+    Ok((additions, terms))
+}
+
+/// Get the rotation producing a value used only once, with its offset and source.
+fn single_use_rotation<G: RotationGates>(
+    circuit: &Circuit<G>,
+    value: ValueId,
+) -> Result<Option<(GateId, i64, ValueId)>> {
+    let val = circuit.value(value)?;
+    let Producer::Gate(id) = val.get_producer() else {
+        return Ok(None);
+    };
+    let gate_op = circuit.gate_op(id)?;
+    match gate_op.get_gate().rotation_offset() {
+        Some(offset) if val.get_uses().len() == 1 && gate_op.get_inputs().len() == 1 => {
+            Ok(Some((id, offset, gate_op.get_inputs()[0])))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Classify the terms of a sum into rotation groups, leaving out other terms.
+fn group_terms<G: RotationGates>(
+    circuit: &Circuit<G>,
+    terms: &[ValueId],
+) -> Result<(Vec<Group<G>>, Vec<ValueId>)> {
+    let mut groups: Vec<Group<G>> = Vec::new();
+    let mut rest = Vec::new();
+
+    for &value in terms {
+        let mut classified = None;
+
+        if let Some((rotation, offset, source)) = single_use_rotation(circuit, value)? {
+            let template = circuit
+                .gate_op(rotation)?
+                .get_gate()
+                .with_rotation_offset(0);
+            let term = Term {
+                offset,
+                rotation,
+                product: None,
+            };
+            classified = Some((source, template, None, term));
+        } else if let Producer::Gate(id) = circuit.value(value)?.get_producer()
+            && circuit.value(value)?.get_uses().len() == 1
+        {
+            // The product is removed with the sum, so the sum must be its
+            // only use.
+            let gate_op = circuit.gate_op(id)?;
+            let gate = *gate_op.get_gate();
+            if gate.is_slotwise_product() && gate_op.get_inputs().len() == 2 {
+                for port in 0..2 {
+                    let inputs = gate_op.get_inputs();
+                    if let Some((rotation, offset, source)) =
+                        single_use_rotation(circuit, inputs[port])?
+                    {
+                        let template = circuit
+                            .gate_op(rotation)?
+                            .get_gate()
+                            .with_rotation_offset(0);
+                        let term = Term {
+                            offset,
+                            rotation,
+                            product: Some(Product {
+                                gate: id,
+                                operand: inputs[1 - port],
+                            }),
+                        };
+                        classified = Some((source, template, Some((gate, port)), term));
+                        break;
+                    }
+                }
+            }
+        }
+
+        let Some((source, rotation, product, term)) = classified else {
+            rest.push(value);
+            continue;
+        };
+        match groups
+            .iter_mut()
+            .find(|g| g.source == source && g.rotation == rotation && g.product == product)
+        {
+            Some(group) => group.terms.push(term),
+            None => groups.push(Group {
+                source,
+                rotation,
+                product,
+                terms: Vec::from([term]),
+            }),
+        }
+    }
+
+    Ok((groups, rest))
+}
+
+/// Choose the giant step minimizing rotations, if it improves on the current count.
+fn giant_step<G: RotationGates>(circuit: &Circuit<G>, group: &Group<G>) -> Result<Option<i64>> {
+    // Whether rotating the product operand of each term costs a rotation.
+    let mut costly_operand = Vec::with_capacity(group.terms.len());
+    for term in &group.terms {
+        costly_operand.push(match &term.product {
+            Some(product) => !matches!(
+                circuit.value(product.operand)?.get_producer(),
+                Producer::Constant(_)
+            ),
+            None => false,
+        });
+    }
+
+    let current = group.terms.iter().filter(|t| t.offset != 0).count();
+    let min = group.terms.iter().map(|t| t.offset).min().unwrap_or(0);
+    let max = group.terms.iter().map(|t| t.offset).max().unwrap_or(0);
+    let span = (i128::from(max) - i128::from(min) + 1) as f64;
+    let limit = (2.0 * span.sqrt()).ceil() as i64;
+
+    let mut best: Option<(usize, i64)> = None;
+    for step in 2..=limit.max(2) {
+        let mut babies = HashSet::new();
+        let mut giants = HashSet::new();
+        let mut operands = 0;
+        for (term, &costly) in group.terms.iter().zip(&costly_operand) {
+            let (giant, baby) = (term.offset.div_euclid(step), term.offset.rem_euclid(step));
+            if baby != 0 {
+                babies.insert(baby);
+            }
+            if giant != 0 {
+                giants.insert(giant);
+                operands += usize::from(costly);
+            }
+        }
+        let cost = babies.len() + giants.len() + operands;
+        if best.is_none_or(|(best_cost, _)| cost < best_cost) {
+            best = Some((cost, step));
+        }
+    }
+
+    Ok(best
+        .filter(|&(cost, _)| cost < current)
+        .map(|(_, step)| step))
+}
+
+/// Add a single-output gate derived from the sum rooted at `root`.
+fn add_derived<G: RotationGates>(
+    circuit: &mut Circuit<G>,
+    root: GateId,
+    gate: G,
+    inputs: Vec<ValueId>,
+) -> Result<ValueId> {
+    let (id, outputs) = circuit.add_derived_gate(gate, inputs)?;
+    circuit.inherit_origins(Operation::Gate(root), Operation::Gate(id));
+    Ok(outputs[0])
+}
+
+/// Add a chain of additions summing the given values.
+fn add_sum<G: RotationGates>(
+    circuit: &mut Circuit<G>,
+    root: GateId,
+    adder: G,
+    values: Vec<ValueId>,
+) -> Result<Option<ValueId>> {
+    let mut values = values.into_iter();
+    let Some(mut acc) = values.next() else {
+        return Ok(None);
+    };
+    for value in values {
+        acc = add_derived(circuit, root, adder, Vec::from([acc, value]))?;
+    }
+    Ok(Some(acc))
+}
+
+/// Emit the baby-step giant-step form of a group, returning its sum.
+fn emit_group<G: RotationGates>(
+    circuit: &mut Circuit<G>,
+    root: GateId,
+    adder: G,
+    group: &Group<G>,
+    step: i64,
+) -> Result<Option<ValueId>> {
+    let mut babies: HashMap<i64, ValueId> = HashMap::from([(0, group.source)]);
+    let mut giants: BTreeMap<i64, Vec<ValueId>> = BTreeMap::new();
+
+    for term in &group.terms {
+        let (giant, baby) = (term.offset.div_euclid(step), term.offset.rem_euclid(step));
+
+        let rotated = match babies.get(&baby) {
+            Some(&rotated) => rotated,
+            None => {
+                let rotation = group.rotation.with_rotation_offset(baby);
+                let rotated = add_derived(circuit, root, rotation, Vec::from([group.source]))?;
+                babies.insert(baby, rotated);
+                rotated
+            }
+        };
+
+        let value = match (&term.product, group.product) {
+            (Some(product), Some((gate, port))) => {
+                // Pre-rotate the operand to cancel the giant step applied later.
+                let mut operand = product.operand;
+                if giant != 0 {
+                    let rotation = group.rotation.with_rotation_offset(-step * giant);
+                    operand = add_derived(circuit, root, rotation, Vec::from([operand]))?;
+                }
+                let mut inputs = Vec::from([operand, operand]);
+                inputs[port] = rotated;
+                add_derived(circuit, root, gate, inputs)?
+            }
+            _ => rotated,
+        };
+        giants.entry(giant).or_default().push(value);
+    }
+
+    let mut partial = Vec::with_capacity(giants.len());
+    for (giant, values) in giants {
+        let Some(mut inner) = add_sum(circuit, root, adder, values)? else {
+            continue;
+        };
+        if giant != 0 {
+            let rotation = group.rotation.with_rotation_offset(step * giant);
+            inner = add_derived(circuit, root, rotation, Vec::from([inner]))?;
+        }
+        partial.push(inner);
+    }
+    add_sum(circuit, root, adder, partial)
+}
+
+/// Rewrite the sum rooted at the given addition, if profitable.
+fn schedule_sum<G: RotationGates>(circuit: &mut Circuit<G>, root: GateId) -> Result<bool> {
+    let adder = *circuit.gate_op(root)?.get_gate();
+    let root_output = circuit.gate_op(root)?.get_outputs()[0];
+    let (additions, terms) = collect_sum(circuit, root)?;
+    let (groups, mut rest) = group_terms(circuit, &terms)?;
+
+    let mut plans = Vec::new();
+    for group in groups {
+        let step = if rotates_operands(circuit, &group)? {
+            giant_step(circuit, &group)?
+        } else {
+            None
+        };
+        match step {
+            Some(step) => plans.push((group, step)),
+            // Keep the group as it is.
+            None => rest.extend(group_values(circuit, &group)?),
+        }
+    }
+    if plans.is_empty() {
+        return Ok(false);
+    }
+
+    // Emit the new sum.
+    let mut sums = Vec::with_capacity(plans.len() + rest.len());
+    for (group, step) in &plans {
+        sums.extend(emit_group(circuit, root, adder, group, *step)?);
+    }
+    sums.extend(rest);
+    let Some(result) = add_sum(circuit, root, adder, sums)? else {
+        return Ok(false);
+    };
+    circuit.replace_all_uses(root_output, result);
+
+    // Remove the old sum, consumers before producers.
+    for id in additions {
+        circuit.remove_gate(id)?;
+    }
+    for (group, _) in &plans {
+        for term in &group.terms {
+            if let Some(product) = &term.product {
+                circuit.remove_gate(product.gate)?;
+            }
+            circuit.remove_gate(term.rotation)?;
+        }
+    }
+
+    Ok(true)
+}
+
+/// Check that the rotation of a group applies to its product operands, which
+/// are pre-rotated by the giant steps.
+///
+/// Operands of another type, such as plaintext diagonals of a ciphertext
+/// rotation, cannot be pre-rotated with it.
+fn rotates_operands<G: RotationGates>(circuit: &Circuit<G>, group: &Group<G>) -> Result<bool> {
+    let rotated = group.rotation.input_type(0)?;
+    for term in &group.terms {
+        if let Some(product) = &term.product
+            && circuit.value(product.operand)?.get_type() != rotated
+        {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Values of the terms of a group, as they appear in the sum.
+fn group_values<G: RotationGates>(circuit: &Circuit<G>, group: &Group<G>) -> Result<Vec<ValueId>> {
+    group
+        .terms
+        .iter()
+        .map(|term| {
+            let id = term.product.as_ref().map_or(term.rotation, |p| p.gate);
+            Ok(circuit.gate_op(id)?.get_outputs()[0])
+        })
+        .collect()
+}
@@ -0,0 +1,95 @@
+//! Operation origins
+//!
+//! This module defines where operations come from: the source location of
+//! the builder call that created them and optional user labels.
+//! Origins are stored as annotations and survive optimization: when a pass
+//! merges or derives operations, the resulting operation keeps the union of
+//! the origins it came from.
+
+use std::{fmt, panic::Location};
+
+/// A single origin of an operation.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub(super) enum Origin {
+    /// Source location of the call that created the operation.
+    Location(&'static Location<'static>),
+    /// User-provided label.
+    Label(String),
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Origin::Location(location) => write!(f, "{}", location),
+            Origin::Label(label) => write!(f, "\"{}\"", label),
+        }
+    }
+}
+
+/// Set of origins attached to an operation, in insertion order.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(super) struct Origins(Vec<Origin>);
+
+impl Origins {
+    /// Create an empty origin set.
+    pub(super) fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    /// Create an origin set holding a single origin.
+    pub(super) fn single(origin: Origin) -> Self {
+        Self(Vec::from([origin]))
+    }
+
+    /// Add an origin, ignoring duplicates.
+    pub(super) fn insert(&mut self, origin: Origin) {
+        if !self.0.contains(&origin) {
+            self.0.push(origin);
+        }
+    }
+
+    /// Add all origins of another set, ignoring duplicates.
+    pub(super) fn merge(&mut self, other: &Origins) {
+        for origin in &other.0 {
+            self.insert(origin.clone());
+        }
+    }
+
+    /// Iterate over the origins.
+    pub(super) fn iter(&self) -> impl Iterator<Item = &Origin> {
+        self.0.iter()
+    }
+
+    /// Iterate over the source locations.
+    pub(super) fn locations(&self) -> impl Iterator<Item = &'static Location<'static>> + '_ {
+        self.0.iter().filter_map(|o| match o {
+            Origin::Location(location) => Some(*location),
+            Origin::Label(_) => None,
+        })
+    }
+
+    /// Iterate over the user labels.
+    pub(super) fn labels(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().filter_map(|o| match o {
+            Origin::Label(label) => Some(label.as_str()),
+            Origin::Location(_) => None,
+        })
+    }
+
+    /// Returns true if there are no origins.
+    pub(super) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl fmt::Display for Origins {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, origin) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", origin)?;
+        }
+        Ok(())
+    }
+}
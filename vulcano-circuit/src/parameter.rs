@@ -0,0 +1,95 @@
+//! Circuit parameters
+//!
+//! This module provides symbolic constants: constants known by name whose
+//! value is bound later, so one circuit shape can be compiled once and run
+//! with many different constants.
+//!
+//! A parameter is a constant holding a placeholder value and tagged with
+//! its name. Passes never fold parameters, and structural hashing identifies
+//! them by name, so parameters with the same name are the same symbol.
+//! Binding a parameter replaces the placeholder and turns it into an
+//! ordinary constant, which later passes may fold.
+
+use std::collections::HashMap;
+
+use crate::{
+    circuit::{Circuit, Operation},
+    error::{Error, Result},
+    gate::Gate,
+    handles::{ConstantId, ValueId},
+};
+
+/// Name of a parameter, attached to its constant.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(super) struct Parameter(String);
+
+impl<G: Gate> Circuit<G> {
+    /// Create a parameter with the default value as placeholder.
+    pub(super) fn add_parameter(
+        &mut self,
+        name: impl Into<String>,
+        value_type: G::Operand,
+    ) -> (ConstantId, ValueId)
+    where
+        G::Constant: Default,
+    {
+        self.add_parameter_with(name, G::Constant::default(), value_type)
+    }
+
+    /// Create a parameter with the given placeholder value.
+    ///
+    /// Evaluating a circuit with unbound parameters uses the placeholders.
+    pub(super) fn add_parameter_with(
+        &mut self,
+        name: impl Into<String>,
+        placeholder: G::Constant,
+        value_type: G::Operand,
+    ) -> (ConstantId, ValueId) {
+        let (id, value) = self.add_constant(placeholder, value_type);
+        self.annotations_mut()
+            .insert(Operation::Constant(id), Parameter(name.into()));
+        (id, value)
+    }
+
+    /// Get the name of a constant if it is an unbound parameter.
+    pub(super) fn parameter(&self, id: ConstantId) -> Option<&str> {
+        self.annotations()
+            .get::<Parameter>(Operation::Constant(id))
+            .map(|p| p.0.as_str())
+    }
+
+    /// Iterate over the unbound parameters and their names.
+    pub(super) fn parameters(&self) -> impl Iterator<Item = (ConstantId, &str)> {
+        self.all_constants()
+            .filter_map(|(id, _)| Some((id, self.parameter(id)?)))
+    }
+
+    /// Bind parameters to concrete values by name.
+    ///
+    /// Bound parameters become ordinary constants. Parameters without a
+    /// binding are left unbound. Fails without binding anything if a name
+    /// matches no parameter.
+    pub(super) fn bind_parameters(
+        &mut self,
+        bindings: &HashMap<String, G::Constant>,
+    ) -> Result<()> {
+        let mut targets = Vec::new();
+        for (id, name) in self.parameters() {
+            if let Some(value) = bindings.get(name) {
+                targets.push((id, value.clone()));
+            }
+        }
+        for name in bindings.keys() {
+            if self.parameters().all(|(_, parameter)| parameter != name) {
+                return Err(Error::UnknownParameter(name.clone()));
+            }
+        }
+
+        for (id, value) in targets {
+            self.set_constant_value(id, value)?;
+            self.annotations_mut()
+                .remove::<Parameter>(Operation::Constant(id));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,161 @@
+//! Circuit partitions
+//!
+//! This module splits a circuit into standalone partition circuits following
+//! a [`PartitionMap`], such as the partitioning or balanced partitioning
+//! analyses. Values crossing partitions become explicit partition
+//! inputs and outputs, identified by the value of the original circuit.
+//! Constants are duplicated into every partition that uses them.
+//!
+//! Exporting a value adds a move of it, so partitions should go through
+//! ownership reconciliation before execution.
+
+use std::collections::HashMap;
+
+use vulcano_arena::KeyType;
+
+use crate::{
+    analyzer::analyses::partitioning::PartitionMap,
+    circuit::{Circuit, Operation, Producer},
+    error::Result,
+    gate::Gate,
+    handles::{InputId, OutputId, ValueId},
+    placement::Device,
+};
+
+/// A standalone piece of a partitioned circuit.
+pub(super) struct Partition<G: Gate> {
+    /// The partition circuit.
+    pub circuit: Circuit<G>,
+    /// Partition inputs and the original values they receive.
+    pub imports: Vec<(InputId, ValueId)>,
+    /// Partition outputs and the original values they provide.
+    pub exports: Vec<(OutputId, ValueId)>,
+    /// Device the partition must run on, if any.
+    pub device: Option<Device>,
+}
+
+impl<G: Gate> Circuit<G> {
+    /// Split the circuit into one circuit per partition.
+    ///
+    /// Circuit outputs are not reproduced: the partition exporting the value
+    /// consumed by an original output provides it.
+    pub(super) fn split_partitions<P: PartitionMap>(
+        &self,
+        partitioning: &P,
+    ) -> Result<Vec<Partition<G>>> {
+        let count = partitioning.partition_count();
+        let mut partitions: Vec<Partition<G>> = (0..count)
+            .map(|p| Partition {
+                circuit: Circuit::new(),
+                imports: Vec::new(),
+                exports: Vec::new(),
+                device: partitioning.device(p),
+            })
+            .collect();
+        // Original values available in each partition.
+        let mut mapped: Vec<HashMap<ValueId, ValueId>> = vec![HashMap::new(); count];
+
+        for op in self.iter_scheduled()? {
+            let Some(p) = partitioning.partition(op) else {
+                continue;
+            };
+            match op {
+                Operation::Gate(id) => {
+                    let gate = self.gate_op(id)?;
+                    let mut inputs = Vec::with_capacity(gate.get_inputs().len());
+                    for &value in gate.get_inputs() {
+                        inputs.push(self.import(&mut partitions[p], &mut mapped[p], value)?);
+                    }
+                    let (new_id, outputs) = partitions[p]
+                        .circuit
+                        .add_derived_gate(*gate.get_gate(), inputs)?;
+                    if let Some(origins) = self.origins(op) {
+                        partitions[p]
+                            .circuit
+                            .annotations_mut()
+                            .insert(Operation::Gate(new_id), origins.clone());
+                    }
+                    if let Some(provenance) = self.provenance(op) {
+                        partitions[p]
+                            .circuit
+                            .annotations_mut()
+                            .insert(Operation::Gate(new_id), provenance.clone());
+                    }
+                    mapped[p].extend(gate.get_outputs().iter().copied().zip(outputs));
+                }
+                Operation::Clone(id) => {
+                    let clone = self.clone_op(id)?;
+                    let input =
+                        self.import(&mut partitions[p], &mut mapped[p], clone.get_input())?;
+                    let (_, outputs) = partitions[p]
+                        .circuit
+                        .add_clone(input, clone.output_count())?;
+                    mapped[p].extend(clone.get_outputs().iter().copied().zip(outputs));
+                }
+                Operation::Drop(id) => {
+                    let value = self.drop_op(id)?.get_input();
+                    let input = self.import(&mut partitions[p], &mut mapped[p], value)?;
+                    partitions[p].circuit.add_drop(input);
+                }
+                _ => {}
+            }
+        }
+
+        // Export values consumed outside of the partition producing them.
+        for (p, partition) in partitions.iter_mut().enumerate() {
+            let mut values: Vec<_> = mapped[p].iter().map(|(&o, &l)| (o, l)).collect();
+            values.sort_by_key(|(original, _)| original.key().index());
+            for (original, local) in values {
+                // Imported values are provided by the partition producing them.
+                let value = self.value(original)?;
+                let producer = Operation::from(value.get_producer());
+                if partitioning.partition(producer) != Some(p) {
+                    continue;
+                }
+                let needed_elsewhere = value.get_uses().iter().any(|usage| {
+                    partitioning
+                        .partition(Operation::from(usage.consumer))
+                        .is_none_or(|q| q != p)
+                });
+                if needed_elsewhere {
+                    let output = partition.circuit.add_output(local);
+                    partition.exports.push((output, original));
+                }
+            }
+        }
+
+        Ok(partitions)
+    }
+
+    /// Get the partition value for an original value, importing it if needed.
+    fn import(
+        &self,
+        partition: &mut Partition<G>,
+        mapped: &mut HashMap<ValueId, ValueId>,
+        original: ValueId,
+    ) -> Result<ValueId> {
+        if let Some(&local) = mapped.get(&original) {
+            return Ok(local);
+        }
+        let value = self.value(original)?;
+        let local = match value.get_producer() {
+            Producer::Constant(id) => {
+                let constant = self.constant_op(id)?.get_value().clone();
+                match self.parameter(id) {
+                    Some(name) => {
+                        let ty = value.get_type();
+                        partition.circuit.add_parameter_with(name, constant, ty).1
+                    }
+                    None => partition.circuit.add_constant(constant, value.get_type()).1,
+                }
+            }
+            _ => {
+                let (input, local) = partition.circuit.add_input(value.get_type());
+                partition.imports.push((input, original));
+                local
+            }
+        };
+        mapped.insert(original, local);
+        Ok(local)
+    }
+}
@@ -0,0 +1,51 @@
+//! Device placement
+//!
+//! This module lets users pin operations to the device that must run them,
+//! for deployments mixing processors and accelerators. Placements are
+//! stored as annotations and are hints for partitioning: the balanced
+//! partitioning analysis keeps operations placed on the same device in one
+//! party and records the device of each party.
+
+use std::fmt;
+
+use crate::{
+    circuit::{Circuit, Operation},
+    gate::Gate,
+};
+
+/// A device able to run operations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(super) enum Device {
+    /// The host processor.
+    Cpu,
+    /// An accelerator, by index.
+    Accelerator(usize),
+}
+
+impl fmt::Display for Device {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Device::Cpu => write!(f, "cpu"),
+            Device::Accelerator(idx) => write!(f, "accelerator#{}", idx),
+        }
+    }
+}
+
+/// Device an operation is pinned to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Placement(Device);
+
+impl<G: Gate> Circuit<G> {
+    /// Pin an operation to a device.
+    ///
+    /// Subcircuits are pinned by placing each of their operations, such as
+    /// those of a module instance.
+    pub(super) fn place(&mut self, op: Operation, device: Device) {
+        self.annotations_mut().insert(op, Placement(device));
+    }
+
+    /// Get the device an operation is pinned to, if any.
+    pub(super) fn placement(&self, op: Operation) -> Option<Device> {
+        self.annotations().get::<Placement>(op).map(|p| p.0)
+    }
+}
@@ -0,0 +1,124 @@
+//! Execution profiles
+//!
+//! This module defines measured gate latencies from previous runs, used as
+//! the cost model of analyses and passes. Latencies are recorded per gate
+//! name, and optionally per gate of the program by provenance id, which
+//! takes priority and stays valid across the passes that renumber gates.
+//!
+//! Profiles can be loaded from a plain text format with one `name latency`
+//! pair per line. Empty lines and lines starting with `#` are ignored.
+
+use std::collections::HashMap;
+
+use crate::{
+    error::{Error, Result},
+    gate::Gate,
+    provenance::{Provenance, ProvenanceId},
+};
+
+/// Running average of measured latencies.
+#[derive(Clone, Copy, Debug, Default)]
+struct Measurement {
+    /// Sum of all samples.
+    total: f64,
+    /// Number of samples.
+    samples: u64,
+}
+
+impl Measurement {
+    /// Add a sample.
+    fn record(&mut self, latency: f64) {
+        self.total += latency;
+        self.samples += 1;
+    }
+
+    /// Average of the samples.
+    fn average(&self) -> f64 {
+        self.total / self.samples as f64
+    }
+}
+
+/// Measured gate latencies.
+#[derive(Clone, Debug, Default)]
+pub(super) struct Profile {
+    /// Latencies by gate name.
+    names: HashMap<String, Measurement>,
+    /// Latencies of specific gates, by provenance.
+    gates: HashMap<ProvenanceId, Measurement>,
+}
+
+impl Profile {
+    /// Create an empty profile.
+    pub(super) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse a profile from `name latency` lines.
+    pub(super) fn parse(text: &str) -> Result<Self> {
+        let mut profile = Self::new();
+        for (idx, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut parts = line.split_whitespace();
+            let (Some(name), Some(latency), None) = (parts.next(), parts.next(), parts.next())
+            else {
+                return Err(Error::InvalidProfileLine(idx + 1));
+            };
+            let latency = latency
+                .parse::<f64>()
+                .ok()
+                .filter(|l| l.is_finite() && *l >= 0.0)
+                .ok_or(Error::InvalidProfileLine(idx + 1))?;
+            profile.record_name(name, latency);
+        }
+        Ok(profile)
+    }
+
+    /// Record a latency sample for all gates with the given name.
+    pub(super) fn record_name(&mut self, name: &str, latency: f64) {
+        self.names
+            .entry(name.to_owned())
+            .or_default()
+            .record(latency);
+    }
+
+    /// Record a latency sample for a specific gate of the program.
+    pub(super) fn record_gate(&mut self, gate: ProvenanceId, latency: f64) {
+        self.gates.entry(gate).or_default().record(latency);
+    }
+
+    /// Average latency measured for a gate name.
+    pub(super) fn name_latency(&self, name: &str) -> Option<f64> {
+        self.names.get(name).map(Measurement::average)
+    }
+
+    /// Average latency measured for a specific gate of the program.
+    pub(super) fn gate_latency(&self, gate: ProvenanceId) -> Option<f64> {
+        self.gates.get(&gate).map(Measurement::average)
+    }
+
+    /// Latency of a gate: the average measurement of the program gates it
+    /// comes from, or the one of its name.
+    pub(super) fn latency<G: Gate>(
+        &self,
+        provenance: Option<&Provenance>,
+        gate: &G,
+    ) -> Option<f64> {
+        let measured: Vec<f64> = provenance
+            .into_iter()
+            .flat_map(Provenance::iter)
+            .filter_map(|id| self.gate_latency(id))
+            .collect();
+        if !measured.is_empty() {
+            return Some(measured.iter().sum::<f64>() / measured.len() as f64);
+        }
+        gate.name().and_then(|n| self.name_latency(n))
+    }
+
+    /// Returns true if nothing was measured.
+    pub(super) fn is_empty(&self) -> bool {
+        self.names.is_empty() && self.gates.is_empty()
+    }
+}
@@ -0,0 +1,93 @@
+//! Gate provenance
+//!
+//! This module gives every gate added by the user program a provenance id
+//! that, unlike its handle, stays meaningful across optimization. Provenance
+//! is stored as an annotation and merged like origins: an operation derived
+//! from or merged with others carries the ids of all of them, so optimized
+//! operations can be related back to the gates of the original program.
+//! Gates created by passes get no id of their own.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt,
+};
+
+use crate::{
+    circuit::{Circuit, Operation},
+    gate::Gate,
+    handles::GateId,
+};
+
+/// Stable identity of a gate as created by the user program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(super) struct ProvenanceId(usize);
+
+impl ProvenanceId {
+    /// Create a provenance id from its number.
+    pub(super) fn new(id: usize) -> Self {
+        Self(id)
+    }
+}
+
+impl fmt::Display for ProvenanceId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "provenance#{}", self.0)
+    }
+}
+
+/// Provenance ids of the gates an operation comes from.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(super) struct Provenance(BTreeSet<ProvenanceId>);
+
+impl Provenance {
+    /// Create a provenance holding a single id.
+    pub(super) fn single(id: ProvenanceId) -> Self {
+        Self(BTreeSet::from([id]))
+    }
+
+    /// Add all ids of another provenance.
+    pub(super) fn merge(&mut self, other: &Provenance) {
+        self.0.extend(other.0.iter().copied());
+    }
+
+    /// Iterate over the ids, in increasing order.
+    pub(super) fn iter(&self) -> impl Iterator<Item = ProvenanceId> + '_ {
+        self.0.iter().copied()
+    }
+
+    /// Returns true if the provenance includes the id.
+    pub(super) fn contains(&self, id: ProvenanceId) -> bool {
+        self.0.contains(&id)
+    }
+}
+
+impl<G: Gate> Circuit<G> {
+    /// Get the provenance of an operation, if it comes from any gate.
+    pub(super) fn provenance(&self, op: Operation) -> Option<&Provenance> {
+        self.annotations().get::<Provenance>(op)
+    }
+
+    /// Find the operations coming from a gate of the original program.
+    ///
+    /// Gates folded into constants are found as constants.
+    pub(super) fn find_by_provenance(&self, id: ProvenanceId) -> Vec<Operation> {
+        self.all_operations()
+            .filter(|&op| self.provenance(op).is_some_and(|p| p.contains(id)))
+            .collect()
+    }
+
+    /// Map each provenance id to the current gates coming from it.
+    pub(super) fn provenance_map(&self) -> HashMap<ProvenanceId, Vec<GateId>> {
+        let mut map: HashMap<ProvenanceId, Vec<GateId>> = HashMap::new();
+        for (id, _) in self.all_gates() {
+            for provenance in self
+                .provenance(Operation::Gate(id))
+                .into_iter()
+                .flat_map(|p| p.iter())
+            {
+                map.entry(provenance).or_default().push(id);
+            }
+        }
+        map
+    }
+}
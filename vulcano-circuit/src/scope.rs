@@ -0,0 +1,43 @@
+//! Scoped construction
+//!
+//! This module lets users build part of a circuit inside a scope that keeps
+//! the linear ownership discipline for them: temporaries created in the scope
+//! and never consumed get a drop when the scope ends, and only the values the
+//! scope returns stay available to the rest of the circuit.
+
+use std::collections::HashSet;
+
+use crate::{
+    circuit::{Circuit, Operation},
+    error::Result,
+    gate::Gate,
+    handles::{Ownership, ValueId},
+};
+
+impl<G: Gate> Circuit<G> {
+    /// Build part of the circuit in a scope.
+    ///
+    /// Values created by `build` that are neither moved by an operation nor
+    /// among the returned values are dropped when it returns. Inserted drops
+    /// inherit the origins of the value's producer.
+    pub(super) fn scope<F>(&mut self, build: F) -> Result<Vec<ValueId>>
+    where
+        F: FnOnce(&mut Circuit<G>) -> Result<Vec<ValueId>>,
+    {
+        let existing: HashSet<ValueId> = self.all_values().map(|(id, _)| id).collect();
+        let escaping = build(self)?;
+
+        let mut leaked = Vec::new();
+        for (id, value) in self.all_values() {
+            let consumed = value.get_uses().iter().any(|u| u.mode == Ownership::Move);
+            if !existing.contains(&id) && !consumed && !escaping.contains(&id) {
+                leaked.push((id, value.get_producer()));
+            }
+        }
+        for (value, producer) in leaked {
+            let drop_id = self.add_drop(value);
+            self.inherit_origins(producer.into(), Operation::Drop(drop_id));
+        }
+        Ok(escaping)
+    }
+}
@@ -0,0 +1,211 @@
+//! Circuit simulation
+//!
+//! This module evaluates circuits on a [`Model`] giving meaning to gates and
+//! constants, such as a plaintext reference or a real backend. Two models can
+//! be run side by side to find the first operation where they disagree,
+//! which is the usual way to validate a kernel against a golden model.
+//!
+//! Operations run in the order of [`Circuit::iter_scheduled`]. Clones copy
+//! their input and drops are ignored.
+
+use std::collections::HashMap;
+
+use crate::{
+    circuit::{Circuit, Operation},
+    error::{Error, Result},
+    gate::Gate,
+    handles::ValueId,
+};
+
+/// Meaning of the gates and constants of a circuit.
+pub(super) trait Model<G: Gate> {
+    /// Runtime value of an operand.
+    type Value: Clone;
+
+    /// Evaluate a constant.
+    fn constant(&mut self, value: &G::Constant) -> Result<Self::Value>;
+
+    /// Evaluate a gate, returning one value per output.
+    fn gate(&mut self, gate: &G, inputs: &[Self::Value]) -> Result<Vec<Self::Value>>;
+}
+
+/// Model defined by a closure per gate and a closure per constant.
+pub(super) struct ClosureModel<F, K> {
+    /// Evaluates gates.
+    gate: F,
+    /// Evaluates constants.
+    constant: K,
+}
+
+impl<F, K> ClosureModel<F, K> {
+    /// Create a model from its closures.
+    pub(super) fn new(gate: F, constant: K) -> Self {
+        Self { gate, constant }
+    }
+}
+
+impl<G, V, F, K> Model<G> for ClosureModel<F, K>
+where
+    G: Gate,
+    V: Clone,
+    F: FnMut(&G, &[V]) -> Result<Vec<V>>,
+    K: FnMut(&G::Constant) -> Result<V>,
+{
+    type Value = V;
+
+    fn constant(&mut self, value: &G::Constant) -> Result<V> {
+        (self.constant)(value)
+    }
+
+    fn gate(&mut self, gate: &G, inputs: &[V]) -> Result<Vec<V>> {
+        (self.gate)(gate, inputs)
+    }
+}
+
+/// First disagreement between two models.
+#[derive(Debug, Clone)]
+pub(super) struct Divergence<A, B> {
+    /// Operation producing the value.
+    pub operation: Operation,
+    /// The value the models disagree on.
+    pub value: ValueId,
+    /// Value computed by the model under test.
+    pub actual: A,
+    /// Value computed by the reference model.
+    pub expected: B,
+}
+
+impl<G: Gate> Circuit<G> {
+    /// Evaluate the circuit on a model, returning one value per output.
+    pub(super) fn simulate<M: Model<G>>(
+        &self,
+        model: &mut M,
+        inputs: Vec<M::Value>,
+    ) -> Result<Vec<M::Value>> {
+        let mut state = Simulation::new(self, inputs)?;
+        for op in self.iter_scheduled()? {
+            state
+                .step(self, model, op)
+                .map_err(|e| e.with_context(self.describe(op)))?;
+        }
+        state.outputs(self)
+    }
+
+    /// Evaluate the circuit on a model and a reference model side by side.
+    ///
+    /// After each operation, every value it produced is compared with `agree`,
+    /// which may allow for a tolerance. Returns the first value the models
+    /// disagree on, or `None` if they agree on the whole circuit.
+    pub(super) fn co_simulate<M, R, C>(
+        &self,
+        model: &mut M,
+        reference: &mut R,
+        inputs: Vec<M::Value>,
+        reference_inputs: Vec<R::Value>,
+        mut agree: C,
+    ) -> Result<Option<Divergence<M::Value, R::Value>>>
+    where
+        M: Model<G>,
+        R: Model<G>,
+        C: FnMut(&M::Value, &R::Value) -> bool,
+    {
+        let mut actual = Simulation::new(self, inputs)?;
+        let mut expected = Simulation::new(self, reference_inputs)?;
+        for op in self.iter_scheduled()? {
+            let at = |e: Error| e.with_context(self.describe(op));
+            actual.step(self, model, op).map_err(at)?;
+            expected.step(self, reference, op).map_err(at)?;
+            for value in self.produced_values(op) {
+                let (a, b) = (actual.get(value)?, expected.get(value)?);
+                if !agree(a, b) {
+                    return Ok(Some(Divergence {
+                        operation: op,
+                        value,
+                        actual: a.clone(),
+                        expected: b.clone(),
+                    }));
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Values computed so far on a model.
+struct Simulation<V> {
+    /// Value of each circuit value.
+    values: HashMap<ValueId, V>,
+}
+
+impl<V: Clone> Simulation<V> {
+    /// Bind the circuit inputs.
+    fn new<G: Gate>(circuit: &Circuit<G>, inputs: Vec<V>) -> Result<Self> {
+        if inputs.len() != circuit.input_count() {
+            return Err(Error::WrongInputCount {
+                expected: circuit.input_count(),
+                got: inputs.len(),
+            });
+        }
+        let values = circuit
+            .all_inputs()
+            .map(|(_, input)| input.get_output())
+            .zip(inputs)
+            .collect();
+        Ok(Self { values })
+    }
+
+    /// Get the value computed for a circuit value.
+    fn get(&self, value: ValueId) -> Result<&V> {
+        self.values.get(&value).ok_or(Error::ValueNotFound(value))
+    }
+
+    /// Evaluate one operation.
+    fn step<G: Gate, M: Model<G, Value = V>>(
+        &mut self,
+        circuit: &Circuit<G>,
+        model: &mut M,
+        op: Operation,
+    ) -> Result<()> {
+        match op {
+            Operation::Constant(id) => {
+                let constant = circuit.constant_op(id)?;
+                let value = model.constant(constant.get_value())?;
+                self.values.insert(constant.get_output(), value);
+            }
+            Operation::Gate(id) => {
+                let gate = circuit.gate_op(id)?;
+                let inputs = gate
+                    .get_inputs()
+                    .iter()
+                    .map(|&v| self.get(v).cloned())
+                    .collect::<Result<Vec<_>>>()?;
+                let outputs = model.gate(gate.get_gate(), &inputs)?;
+                if outputs.len() != gate.get_outputs().len() {
+                    return Err(Error::InvalidOutputIndex {
+                        idx: outputs.len(),
+                        max: gate.get_outputs().len(),
+                    });
+                }
+                self.values
+                    .extend(gate.get_outputs().iter().copied().zip(outputs));
+            }
+            Operation::Clone(id) => {
+                let clone = circuit.clone_op(id)?;
+                let value = self.get(clone.get_input())?.clone();
+                for &output in clone.get_outputs() {
+                    self.values.insert(output, value.clone());
+                }
+            }
+            Operation::Input(_) | Operation::Drop(_) | Operation::Output(_) => {}
+        }
+        Ok(())
+    }
+
+    /// Collect the values of the circuit outputs.
+    fn outputs<G: Gate>(&self, circuit: &Circuit<G>) -> Result<Vec<V>> {
+        circuit
+            .all_outputs()
+            .map(|(_, output)| self.get(output.get_input()).cloned())
+            .collect()
+    }
+}
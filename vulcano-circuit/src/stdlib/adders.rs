@@ -0,0 +1,216 @@
+//! Adders
+//!
+//! Adders and subtractors over bit vectors, least significant bit first.
+//! Carries combine mutually exclusive terms, so they are merged with XOR and
+//! only AND, XOR and NOT gates are needed.
+
+use super::apply;
+use crate::{
+    circuit::Circuit,
+    error::{Error, Result},
+    gate::BooleanGates,
+    handles::ValueId,
+};
+
+/// Add two bits, returning the sum and the carry.
+#[track_caller]
+pub(crate) fn half_adder<G: BooleanGates>(
+    circuit: &mut Circuit<G>,
+    a: ValueId,
+    b: ValueId,
+) -> Result<(ValueId, ValueId)> {
+    let sum = apply(circuit, G::xor(), vec![a, b])?;
+    let carry = apply(circuit, G::and(), vec![a, b])?;
+    Ok((sum, carry))
+}
+
+/// Add two bits and a carry, returning the sum and the carry.
+#[track_caller]
+pub(crate) fn full_adder<G: BooleanGates>(
+    circuit: &mut Circuit<G>,
+    a: ValueId,
+    b: ValueId,
+    carry: ValueId,
+) -> Result<(ValueId, ValueId)> {
+    let (partial, generate) = half_adder(circuit, a, b)?;
+    let (sum, propagate) = half_adder(circuit, partial, carry)?;
+    let carry = apply(circuit, G::xor(), vec![generate, propagate])?;
+    Ok((sum, carry))
+}
+
+/// Add two bit vectors of equal width with a chain of full adders.
+///
+/// Returns the sum, as wide as the operands, and the carry out. Depth is
+/// linear in the width.
+#[track_caller]
+pub(crate) fn ripple_carry_add<G: BooleanGates>(
+    circuit: &mut Circuit<G>,
+    a: &[ValueId],
+    b: &[ValueId],
+    carry_in: Option<ValueId>,
+) -> Result<(Vec<ValueId>, ValueId)> {
+    check_widths(a, b)?;
+    let (sum, carry) = add_uneven(circuit, a, b, carry_in)?;
+    Ok((sum, carry.ok_or(NOTHING_TO_ADD)?))
+}
+
+/// Add two bit vectors of equal width computing carries with a parallel
+/// prefix network.
+///
+/// Returns the sum, as wide as the operands, and the carry out. Depth is
+/// logarithmic in the width.
+#[track_caller]
+pub(crate) fn carry_lookahead_add<G: BooleanGates>(
+    circuit: &mut Circuit<G>,
+    a: &[ValueId],
+    b: &[ValueId],
+    carry_in: Option<ValueId>,
+) -> Result<(Vec<ValueId>, ValueId)> {
+    check_widths(a, b)?;
+    if a.is_empty() {
+        return Ok((Vec::new(), carry_in.ok_or(NOTHING_TO_ADD)?));
+    }
+
+    let mut propagate = Vec::with_capacity(a.len());
+    let mut generate = Vec::with_capacity(a.len());
+    for (&x, &y) in a.iter().zip(b) {
+        let (p, g) = half_adder(circuit, x, y)?;
+        propagate.push(p);
+        generate.push(g);
+    }
+    if let Some(carry_in) = carry_in {
+        let absorbed = apply(circuit, G::and(), vec![propagate[0], carry_in])?;
+        generate[0] = apply(circuit, G::xor(), vec![generate[0], absorbed])?;
+    }
+
+    // Sklansky network over (generate, propagate) pairs: the group of bits
+    // `j..=i` generates a carry if the upper group generates one, or
+    // propagates the one generated by the lower group.
+    let mut group_generate = generate;
+    let mut group_propagate = propagate.clone();
+    let mut span = 1;
+    while span < a.len() {
+        for i in 0..a.len() {
+            if i & span != 0 {
+                let last = (i & !(span - 1)) - 1;
+                let absorbed = apply(
+                    circuit,
+                    G::and(),
+                    vec![group_propagate[i], group_generate[last]],
+                )?;
+                group_generate[i] = apply(circuit, G::xor(), vec![group_generate[i], absorbed])?;
+                group_propagate[i] = apply(
+                    circuit,
+                    G::and(),
+                    vec![group_propagate[i], group_propagate[last]],
+                )?;
+            }
+        }
+        span *= 2;
+    }
+
+    let mut sum = Vec::with_capacity(a.len());
+    for (i, &p) in propagate.iter().enumerate() {
+        let carry = match i {
+            0 => carry_in,
+            _ => Some(group_generate[i - 1]),
+        };
+        sum.push(match carry {
+            Some(carry) => apply(circuit, G::xor(), vec![p, carry])?,
+            None => p,
+        });
+    }
+    Ok((sum, group_generate[a.len() - 1]))
+}
+
+/// Subtract two bit vectors of equal width with a chain of full subtractors.
+///
+/// Returns the difference modulo `2^width` and the borrow out, which is set
+/// when `b` is greater than `a` as unsigned integers.
+#[track_caller]
+pub(crate) fn ripple_borrow_subtract<G: BooleanGates>(
+    circuit: &mut Circuit<G>,
+    a: &[ValueId],
+    b: &[ValueId],
+) -> Result<(Vec<ValueId>, ValueId)> {
+    check_widths(a, b)?;
+    let mut difference = Vec::with_capacity(a.len());
+    let mut borrow: Option<ValueId> = None;
+    for (&x, &y) in a.iter().zip(b) {
+        let (bit, out) = full_subtractor(circuit, x, y, borrow)?;
+        difference.push(bit);
+        borrow = Some(out);
+    }
+    Ok((difference, borrow.ok_or(NOTHING_TO_ADD)?))
+}
+
+/// Subtract two bits and an optional borrow, returning the difference and
+/// the borrow.
+///
+/// A borrow is raised when `a` is unset and `b` is set, or when `a` equals
+/// `b` and a borrow comes in.
+#[track_caller]
+pub(crate) fn full_subtractor<G: BooleanGates>(
+    circuit: &mut Circuit<G>,
+    a: ValueId,
+    b: ValueId,
+    borrow: Option<ValueId>,
+) -> Result<(ValueId, ValueId)> {
+    let partial = apply(circuit, G::xor(), vec![a, b])?;
+    let not_a = apply(circuit, G::not(), vec![a])?;
+    let generate = apply(circuit, G::and(), vec![not_a, b])?;
+    let Some(borrow) = borrow else {
+        return Ok((partial, generate));
+    };
+
+    let bit = apply(circuit, G::xor(), vec![partial, borrow])?;
+    let equal = apply(circuit, G::not(), vec![partial])?;
+    let propagate = apply(circuit, G::and(), vec![equal, borrow])?;
+    let borrow = apply(circuit, G::xor(), vec![generate, propagate])?;
+    Ok((bit, borrow))
+}
+
+/// Add two bit vectors where `a` may be narrower than `b`.
+///
+/// Bits of `b` beyond the width of `a` only absorb the carry. Returns the sum,
+/// as wide as `b`, and the carry out, if any bit produced one.
+#[track_caller]
+pub(super) fn add_uneven<G: BooleanGates>(
+    circuit: &mut Circuit<G>,
+    a: &[ValueId],
+    b: &[ValueId],
+    carry_in: Option<ValueId>,
+) -> Result<(Vec<ValueId>, Option<ValueId>)> {
+    let mut sum = Vec::with_capacity(b.len());
+    let mut carry = carry_in;
+    for (i, &y) in b.iter().enumerate() {
+        let (bit, out) = match (a.get(i), carry) {
+            (Some(&x), Some(c)) => full_adder(circuit, x, y, c)?,
+            (Some(&x), None) | (None, Some(x)) => half_adder(circuit, x, y)?,
+            (None, None) => {
+                sum.push(y);
+                continue;
+            }
+        };
+        sum.push(bit);
+        carry = Some(out);
+    }
+    Ok((sum, carry))
+}
+
+/// Error raised when there are no bits to operate on.
+const NOTHING_TO_ADD: Error = Error::WrongInputCount {
+    expected: 1,
+    got: 0,
+};
+
+/// Check that operands have equal width.
+fn check_widths(a: &[ValueId], b: &[ValueId]) -> Result<()> {
+    if a.len() != b.len() {
+        return Err(Error::WrongInputCount {
+            expected: a.len(),
+            got: b.len(),
+        });
+    }
+    Ok(())
+}
@@ -0,0 +1,108 @@
+//! Comparison
+//!
+//! Equality and magnitude comparators over bit vectors, least significant
+//! bit first. Magnitude comparators treat the vectors as unsigned integers.
+
+use super::{adders::full_subtractor, apply, prefix::reduce};
+use crate::{
+    circuit::Circuit,
+    error::{Error, Result},
+    gate::BooleanGates,
+    handles::ValueId,
+};
+
+/// Compare two bit vectors of equal width for equality.
+///
+/// Negates the bitwise XOR and combines the bits with a balanced AND tree.
+#[track_caller]
+pub(crate) fn equal<G: BooleanGates>(
+    circuit: &mut Circuit<G>,
+    a: &[ValueId],
+    b: &[ValueId],
+) -> Result<ValueId> {
+    if a.len() != b.len() {
+        return Err(Error::WrongInputCount {
+            expected: a.len(),
+            got: b.len(),
+        });
+    }
+
+    let mut bits = Vec::with_capacity(a.len());
+    for (&x, &y) in a.iter().zip(b) {
+        let diff = apply(circuit, G::xor(), vec![x, y])?;
+        bits.push(apply(circuit, G::not(), vec![diff])?);
+    }
+    reduce(circuit, G::and(), &bits)
+}
+
+/// Compare two bit vectors of equal width for inequality.
+///
+/// Negates the result of [`equal`].
+#[track_caller]
+pub(crate) fn not_equal<G: BooleanGates>(
+    circuit: &mut Circuit<G>,
+    a: &[ValueId],
+    b: &[ValueId],
+) -> Result<ValueId> {
+    let eq = equal(circuit, a, b)?;
+    apply(circuit, G::not(), vec![eq])
+}
+
+/// Check whether `a` is less than `b`.
+///
+/// Computes the borrow out of `a - b` without the difference bits.
+#[track_caller]
+pub(crate) fn less_than<G: BooleanGates>(
+    circuit: &mut Circuit<G>,
+    a: &[ValueId],
+    b: &[ValueId],
+) -> Result<ValueId> {
+    if a.len() != b.len() {
+        return Err(Error::WrongInputCount {
+            expected: a.len(),
+            got: b.len(),
+        });
+    }
+
+    let mut borrow = None;
+    for (&x, &y) in a.iter().zip(b) {
+        let (_, out) = full_subtractor(circuit, x, y, borrow)?;
+        borrow = Some(out);
+    }
+    borrow.ok_or(Error::WrongInputCount {
+        expected: 1,
+        got: 0,
+    })
+}
+
+/// Check whether `a` is greater than `b`.
+#[track_caller]
+pub(crate) fn greater_than<G: BooleanGates>(
+    circuit: &mut Circuit<G>,
+    a: &[ValueId],
+    b: &[ValueId],
+) -> Result<ValueId> {
+    less_than(circuit, b, a)
+}
+
+/// Check whether `a` is less than or equal to `b`.
+#[track_caller]
+pub(crate) fn less_equal<G: BooleanGates>(
+    circuit: &mut Circuit<G>,
+    a: &[ValueId],
+    b: &[ValueId],
+) -> Result<ValueId> {
+    let greater = less_than(circuit, b, a)?;
+    apply(circuit, G::not(), vec![greater])
+}
+
+/// Check whether `a` is greater than or equal to `b`.
+#[track_caller]
+pub(crate) fn greater_equal<G: BooleanGates>(
+    circuit: &mut Circuit<G>,
+    a: &[ValueId],
+    b: &[ValueId],
+) -> Result<ValueId> {
+    let less = less_than(circuit, a, b)?;
+    apply(circuit, G::not(), vec![less])
+}
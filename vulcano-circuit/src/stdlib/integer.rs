@@ -0,0 +1,152 @@
+//! Encrypted integers
+//!
+//! This module bundles a fixed number of bits into an unsigned integer whose
+//! operations expand into the constructions of the standard library.
+//! Arithmetic wraps around modulo `2^N`.
+
+use super::{adders, compare, multipliers, select};
+use crate::{
+    circuit::Circuit,
+    error::{Error, Result},
+    gate::BooleanGates,
+    handles::{OutputId, ValueId},
+};
+
+/// Unsigned integer of `N` bits, least significant first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct EncryptedInt<const N: usize> {
+    /// Values holding each bit.
+    bits: [ValueId; N],
+}
+
+impl<const N: usize> EncryptedInt<N> {
+    /// Bundle existing bit values, least significant first.
+    pub(crate) fn from_bits(bits: [ValueId; N]) -> Self {
+        Self { bits }
+    }
+
+    /// Add one circuit input per bit.
+    pub(crate) fn input<G: BooleanGates>(circuit: &mut Circuit<G>, bit_type: G::Operand) -> Self {
+        Self {
+            bits: std::array::from_fn(|_| circuit.add_input(bit_type).1),
+        }
+    }
+
+    /// Returns the bit values, least significant first.
+    pub(crate) fn bits(&self) -> &[ValueId; N] {
+        &self.bits
+    }
+
+    /// Add one circuit output per bit.
+    pub(crate) fn output<G: BooleanGates>(&self, circuit: &mut Circuit<G>) -> [OutputId; N] {
+        self.bits.map(|bit| circuit.add_output(bit))
+    }
+
+    /// Add two integers, wrapping around.
+    #[track_caller]
+    pub(crate) fn add<G: BooleanGates>(
+        &self,
+        circuit: &mut Circuit<G>,
+        other: &Self,
+    ) -> Result<Self> {
+        let (sum, _) = adders::carry_lookahead_add(circuit, &self.bits, &other.bits, None)?;
+        Self::from_vec(sum)
+    }
+
+    /// Subtract two integers, wrapping around.
+    #[track_caller]
+    pub(crate) fn sub<G: BooleanGates>(
+        &self,
+        circuit: &mut Circuit<G>,
+        other: &Self,
+    ) -> Result<Self> {
+        let (difference, _) = adders::ripple_borrow_subtract(circuit, &self.bits, &other.bits)?;
+        Self::from_vec(difference)
+    }
+
+    /// Multiply two integers, wrapping around.
+    #[track_caller]
+    pub(crate) fn mul<G: BooleanGates>(
+        &self,
+        circuit: &mut Circuit<G>,
+        other: &Self,
+    ) -> Result<Self> {
+        let product = multipliers::truncated_multiply(circuit, &self.bits, &other.bits)?;
+        Self::from_vec(product)
+    }
+
+    /// Returns a bit set when both integers are equal.
+    #[track_caller]
+    pub(crate) fn eq<G: BooleanGates>(
+        &self,
+        circuit: &mut Circuit<G>,
+        other: &Self,
+    ) -> Result<ValueId> {
+        compare::equal(circuit, &self.bits, &other.bits)
+    }
+
+    /// Returns a bit set when this integer is less than the other.
+    #[track_caller]
+    pub(crate) fn lt<G: BooleanGates>(
+        &self,
+        circuit: &mut Circuit<G>,
+        other: &Self,
+    ) -> Result<ValueId> {
+        compare::less_than(circuit, &self.bits, &other.bits)
+    }
+
+    /// Returns a bit set when this integer is less than or equal to the other.
+    #[track_caller]
+    pub(crate) fn le<G: BooleanGates>(
+        &self,
+        circuit: &mut Circuit<G>,
+        other: &Self,
+    ) -> Result<ValueId> {
+        compare::less_equal(circuit, &self.bits, &other.bits)
+    }
+
+    /// Returns a bit set when this integer is greater than the other.
+    #[track_caller]
+    pub(crate) fn gt<G: BooleanGates>(
+        &self,
+        circuit: &mut Circuit<G>,
+        other: &Self,
+    ) -> Result<ValueId> {
+        compare::greater_than(circuit, &self.bits, &other.bits)
+    }
+
+    /// Returns a bit set when this integer is greater than or equal to the
+    /// other.
+    #[track_caller]
+    pub(crate) fn ge<G: BooleanGates>(
+        &self,
+        circuit: &mut Circuit<G>,
+        other: &Self,
+    ) -> Result<ValueId> {
+        compare::greater_equal(circuit, &self.bits, &other.bits)
+    }
+
+    /// Select `a` when the selector bit is set and `b` otherwise.
+    #[track_caller]
+    pub(crate) fn select<G: BooleanGates>(
+        circuit: &mut Circuit<G>,
+        sel: ValueId,
+        a: &Self,
+        b: &Self,
+    ) -> Result<Self> {
+        let mut bits = Vec::with_capacity(N);
+        for (&x, &y) in a.bits.iter().zip(&b.bits) {
+            bits.push(select::mux(circuit, sel, x, y)?);
+        }
+        Self::from_vec(bits)
+    }
+
+    /// Bundle bits produced by a construction.
+    fn from_vec(bits: Vec<ValueId>) -> Result<Self> {
+        let got = bits.len();
+        let bits = bits
+            .try_into()
+            .map_err(|_| Error::WrongInputCount { expected: N, got })?;
+        Ok(Self { bits })
+    }
+}
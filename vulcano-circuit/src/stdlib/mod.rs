@@ -0,0 +1,33 @@
+//! Standard library
+//!
+//! This module provides generic composite constructions built from a small
+//! set of base gates, described by [`BooleanGates`](crate::gate::BooleanGates)
+//! or [`ArithmeticGates`](crate::gate::ArithmeticGates). Constructions record
+//! gates into an existing circuit and return the resulting values.
+//!
+//! Values may be used any number of times; ownership is left to
+//! reconciliation.
+
+pub(super) mod adders;
+pub(super) mod compare;
+pub(super) mod integer;
+pub(super) mod multipliers;
+pub(super) mod prefix;
+pub(super) mod select;
+
+use crate::{
+    circuit::Circuit,
+    error::{Error, Result},
+    gate::Gate,
+    handles::ValueId,
+};
+
+/// Add a gate and return its first output.
+#[track_caller]
+fn apply<G: Gate>(circuit: &mut Circuit<G>, gate: G, inputs: Vec<ValueId>) -> Result<ValueId> {
+    let (_, outputs) = circuit.add_gate(gate, inputs)?;
+    outputs
+        .first()
+        .copied()
+        .ok_or(Error::InvalidOutputIndex { idx: 0, max: 0 })
+}
@@ -0,0 +1,78 @@
+//! Multipliers
+//!
+//! Multipliers over bit vectors, least significant bit first.
+
+use super::{adders::add_uneven, apply};
+use crate::{
+    circuit::Circuit,
+    error::{Error, Result},
+    gate::BooleanGates,
+    handles::ValueId,
+};
+
+/// Multiply two unsigned bit vectors by shifting and adding partial products.
+///
+/// The i-th partial product is `a` masked by the i-th bit of `b`, and is
+/// added with a ripple-carry adder to the running sum shifted right by one.
+/// Returns the product, at most `a.len() + b.len()` bits wide: bits that are
+/// always zero are not materialized.
+#[track_caller]
+pub(crate) fn shift_add_multiply<G: BooleanGates>(
+    circuit: &mut Circuit<G>,
+    a: &[ValueId],
+    b: &[ValueId],
+) -> Result<Vec<ValueId>> {
+    if a.is_empty() || b.is_empty() {
+        return Err(Error::WrongInputCount {
+            expected: 1,
+            got: 0,
+        });
+    }
+
+    let mut product = Vec::with_capacity(a.len() + b.len());
+    let mut running: Vec<ValueId> = Vec::new();
+    for &y in b {
+        let mut partial = Vec::with_capacity(a.len());
+        for &x in a {
+            partial.push(apply(circuit, G::and(), vec![x, y])?);
+        }
+        let (sum, carry) = add_uneven(circuit, &running, &partial, None)?;
+        product.push(sum[0]);
+        running = sum[1..].to_vec();
+        running.extend(carry);
+    }
+    product.extend(running);
+    Ok(product)
+}
+
+/// Multiply two unsigned bit vectors of equal width modulo `2^width`.
+///
+/// Like [`shift_add_multiply`], but partial products and sums are cut at the
+/// operand width, so no gates are spent on the discarded high bits.
+#[track_caller]
+pub(crate) fn truncated_multiply<G: BooleanGates>(
+    circuit: &mut Circuit<G>,
+    a: &[ValueId],
+    b: &[ValueId],
+) -> Result<Vec<ValueId>> {
+    if a.len() != b.len() {
+        return Err(Error::WrongInputCount {
+            expected: a.len(),
+            got: b.len(),
+        });
+    }
+
+    let mut product = Vec::with_capacity(a.len());
+    let mut running: Vec<ValueId> = Vec::new();
+    for (i, &y) in b.iter().enumerate() {
+        let width = a.len() - i;
+        let mut partial = Vec::with_capacity(width);
+        for &x in &a[..width] {
+            partial.push(apply(circuit, G::and(), vec![x, y])?);
+        }
+        let (sum, _) = add_uneven(circuit, &running, &partial, None)?;
+        product.push(sum[0]);
+        running = sum[1..].to_vec();
+    }
+    Ok(product)
+}
@@ -0,0 +1,67 @@
+//! Prefix networks
+//!
+//! Reductions and prefix scans over an associative two-input gate, such as
+//! AND, XOR, ADD or MUL. Both have logarithmic depth.
+
+use super::apply;
+use crate::{
+    circuit::Circuit,
+    error::{Error, Result},
+    gate::Gate,
+    handles::ValueId,
+};
+
+/// Combine all values with an associative gate using a balanced tree.
+#[track_caller]
+pub(crate) fn reduce<G: Gate>(
+    circuit: &mut Circuit<G>,
+    gate: G,
+    values: &[ValueId],
+) -> Result<ValueId> {
+    if values.is_empty() {
+        return Err(Error::WrongInputCount {
+            expected: 1,
+            got: 0,
+        });
+    }
+
+    let mut level = values.to_vec();
+    while level.len() > 1 {
+        let mut next = Vec::with_capacity(level.len().div_ceil(2));
+        for pair in level.chunks(2) {
+            match *pair {
+                [a, b] => next.push(apply(circuit, gate, vec![a, b])?),
+                [a] => next.push(a),
+                _ => unreachable!(),
+            }
+        }
+        level = next;
+    }
+    Ok(level[0])
+}
+
+/// Compute all prefixes of the values with an associative gate.
+///
+/// The i-th result combines the values up to and including the i-th one.
+/// Uses a Sklansky network: depth `ceil(log2 n)` and `n/2 * log2 n` gates.
+#[track_caller]
+pub(crate) fn prefix_scan<G: Gate>(
+    circuit: &mut Circuit<G>,
+    gate: G,
+    values: &[ValueId],
+) -> Result<Vec<ValueId>> {
+    let mut prefixes = values.to_vec();
+    let mut span = 1;
+    while span < prefixes.len() {
+        for i in 0..prefixes.len() {
+            // Each block of `span` values on the upper half of a `2 * span`
+            // block absorbs the last prefix of the lower half.
+            if i & span != 0 {
+                let last = (i & !(span - 1)) - 1;
+                prefixes[i] = apply(circuit, gate, vec![prefixes[last], prefixes[i]])?;
+            }
+        }
+        span *= 2;
+    }
+    Ok(prefixes)
+}
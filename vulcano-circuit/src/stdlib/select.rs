@@ -0,0 +1,100 @@
+//! Selection
+//!
+//! Multiplexers choosing between two values on a selector, and select trees
+//! choosing among `2^k` values on `k` selector bits.
+
+use super::apply;
+use crate::{
+    circuit::Circuit,
+    error::{Error, Result},
+    gate::{ArithmeticGates, BooleanGates},
+    handles::ValueId,
+};
+
+/// Select `a` when the selector bit is set and `b` otherwise.
+///
+/// Computes `b ^ (sel & (a ^ b))`.
+#[track_caller]
+pub(crate) fn mux<G: BooleanGates>(
+    circuit: &mut Circuit<G>,
+    sel: ValueId,
+    a: ValueId,
+    b: ValueId,
+) -> Result<ValueId> {
+    let diff = apply(circuit, G::xor(), vec![a, b])?;
+    let masked = apply(circuit, G::and(), vec![sel, diff])?;
+    apply(circuit, G::xor(), vec![b, masked])
+}
+
+/// Select `a` when the selector is one and `b` when it is zero.
+///
+/// Computes `b + sel * (a - b)`, so the gate set must provide subtraction.
+#[track_caller]
+pub(crate) fn arithmetic_mux<G: ArithmeticGates>(
+    circuit: &mut Circuit<G>,
+    sel: ValueId,
+    a: ValueId,
+    b: ValueId,
+) -> Result<ValueId> {
+    let sub = G::sub().ok_or(Error::MissingOperatorGate("sub"))?;
+    let diff = apply(circuit, sub, vec![a, b])?;
+    let masked = apply(circuit, G::mul(), vec![sel, diff])?;
+    apply(circuit, G::add(), vec![b, masked])
+}
+
+/// Select one of `2^k` values on `k` selector bits, least significant first.
+///
+/// Each selector halves the candidates: pairs differing in that selector bit
+/// are merged, keeping the odd one when the bit is set.
+#[track_caller]
+pub(crate) fn select<G: BooleanGates>(
+    circuit: &mut Circuit<G>,
+    selectors: &[ValueId],
+    values: &[ValueId],
+) -> Result<ValueId> {
+    check_tree_size(selectors, values)?;
+    let mut level = values.to_vec();
+    for &sel in selectors {
+        let mut next = Vec::with_capacity(level.len() / 2);
+        for pair in level.chunks(2) {
+            next.push(mux(circuit, sel, pair[1], pair[0])?);
+        }
+        level = next;
+    }
+    Ok(level[0])
+}
+
+/// Select one of `2^k` values on `k` selectors, least significant first,
+/// with arithmetic multiplexers.
+#[track_caller]
+pub(crate) fn arithmetic_select<G: ArithmeticGates>(
+    circuit: &mut Circuit<G>,
+    selectors: &[ValueId],
+    values: &[ValueId],
+) -> Result<ValueId> {
+    check_tree_size(selectors, values)?;
+    let mut level = values.to_vec();
+    for &sel in selectors {
+        let mut next = Vec::with_capacity(level.len() / 2);
+        for pair in level.chunks(2) {
+            next.push(arithmetic_mux(circuit, sel, pair[1], pair[0])?);
+        }
+        level = next;
+    }
+    Ok(level[0])
+}
+
+/// Check that there is one value per combination of the selectors.
+fn check_tree_size(selectors: &[ValueId], values: &[ValueId]) -> Result<()> {
+    let expected = u32::try_from(selectors.len())
+        .ok()
+        .and_then(|k| 1usize.checked_shl(k))
+        .unwrap_or(usize::MAX);
+    if values.len() != expected {
+        return Err(Error::WrongInputCount {
+            expected,
+            got: values.len(),
+        });
+    }
+    Ok(())
+}
@@ -0,0 +1,394 @@
+//! Template matching
+//!
+//! This module finds every occurrence of a small template circuit inside a
+//! larger circuit, as a starting point for custom fusion passes or for
+//! reviews looking for known patterns.
+//!
+//! Template gates are matched one at a time in the style of VF2: each gate
+//! after the first is adjacent to an already matched one, so its candidates
+//! are the producers or consumers of matched values. Only the first gate of
+//! each connected piece of the template is looked up among all gates, and
+//! only among those with the same name.
+//!
+//! Template inputs match any value, and a template input used several times
+//! must match the same value each time. Template constants match constants
+//! of equal value. Clones are looked through on both sides, so a template
+//! matches regardless of how values are shared. Templates with symmetries
+//! match once per symmetry.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    circuit::{Circuit, Consumer, Producer},
+    error::Result,
+    gate::Gate,
+    handles::{GateId, ValueId},
+};
+
+/// An occurrence of a template inside a circuit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct TemplateMatch {
+    /// Template gates paired with the circuit gates they match.
+    pub gates: Vec<(GateId, GateId)>,
+    /// Circuit values matched by each template input, by position.
+    ///
+    /// Inputs not used by any template gate are `None`.
+    pub inputs: Vec<Option<ValueId>>,
+    /// Circuit values matched by each template output, by position.
+    ///
+    /// Outputs of constants and unused inputs are `None`.
+    pub outputs: Vec<Option<ValueId>>,
+    /// Whether values produced by the matched gates are only used outside
+    /// of them when they match template outputs. Only closed matches can be
+    /// replaced by something computing the template outputs.
+    pub closed: bool,
+}
+
+/// Where a template value comes from, looking through clones.
+enum Source<C> {
+    /// Output port of the template gate at the given index.
+    Gate { gate: usize, port: usize },
+    /// Template input at the given position.
+    Input(usize),
+    /// Constant value.
+    Constant(C),
+}
+
+/// A template prepared for matching.
+struct Template<'t, G: Gate> {
+    /// Template gates in insertion order.
+    gates: Vec<(GateId, &'t G)>,
+    /// Source of each input of each gate.
+    sources: Vec<Vec<Source<G::Constant>>>,
+    /// Consumers of the outputs of each gate, as consumer index, consumer
+    /// input port and output port.
+    feeds: Vec<Vec<(usize, usize, usize)>>,
+    /// Source of each template output.
+    outputs: Vec<Source<G::Constant>>,
+    /// Number of template inputs.
+    input_count: usize,
+    /// Order in which gates are matched.
+    order: Vec<usize>,
+}
+
+impl<'t, G: Gate> Template<'t, G> {
+    /// Prepare a template circuit for matching.
+    fn new(template: &'t Circuit<G>) -> Result<Self> {
+        let inputs: HashMap<_, _> = template
+            .all_inputs()
+            .enumerate()
+            .map(|(pos, (id, _))| (id, pos))
+            .collect();
+        let mut index = HashMap::new();
+        let mut gates = Vec::new();
+        for (id, gate) in template.all_gates() {
+            index.insert(id, gates.len());
+            gates.push((id, gate.get_gate()));
+        }
+
+        let source = |value: ValueId| -> Result<Source<G::Constant>> {
+            let root = template.root_value(value)?;
+            Ok(match template.value(root)?.get_producer() {
+                Producer::Gate(id) => Source::Gate {
+                    gate: index[&id],
+                    port: template.value(root)?.get_port().index(),
+                },
+                Producer::Input(id) => Source::Input(inputs[&id]),
+                Producer::Constant(id) => {
+                    Source::Constant(template.constant_op(id)?.get_value().clone())
+                }
+                Producer::Clone(_) => unreachable!("root values are not produced by clones"),
+            })
+        };
+
+        let mut sources = Vec::with_capacity(gates.len());
+        let mut feeds = vec![Vec::new(); gates.len()];
+        for (consumer, &(id, _)) in gates.iter().enumerate() {
+            let mut gate_sources = Vec::new();
+            for (port, &value) in template.gate_op(id)?.get_inputs().iter().enumerate() {
+                let source = source(value)?;
+                if let Source::Gate { gate, port: out } = source {
+                    feeds[gate].push((consumer, port, out));
+                }
+                gate_sources.push(source);
+            }
+            sources.push(gate_sources);
+        }
+        let outputs = template
+            .all_outputs()
+            .map(|(_, output)| source(output.get_input()))
+            .collect::<Result<Vec<_>>>()?;
+
+        // Match consumers before producers, keeping every gate after the
+        // first of each connected piece adjacent to a matched one.
+        let mut order = Vec::with_capacity(gates.len());
+        let mut seen = vec![false; gates.len()];
+        for start in (0..gates.len()).rev() {
+            if seen[start] {
+                continue;
+            }
+            seen[start] = true;
+            let first = order.len();
+            order.push(start);
+            let mut next = first;
+            while next < order.len() {
+                let gate = order[next];
+                next += 1;
+                let producers = sources[gate].iter().filter_map(|source| match source {
+                    Source::Gate { gate, .. } => Some(*gate),
+                    _ => None,
+                });
+                let consumers = feeds[gate].iter().map(|&(consumer, _, _)| consumer);
+                for neighbour in producers.chain(consumers).collect::<Vec<_>>() {
+                    if !seen[neighbour] {
+                        seen[neighbour] = true;
+                        order.push(neighbour);
+                    }
+                }
+            }
+        }
+
+        Ok(Self {
+            gates,
+            sources,
+            feeds,
+            outputs,
+            input_count: inputs.len(),
+            order,
+        })
+    }
+}
+
+/// Backtracking state of a template search.
+struct Matcher<'a, 't, G: Gate> {
+    circuit: &'a Circuit<G>,
+    template: &'a Template<'t, G>,
+    /// Circuit gates with each name.
+    by_name: HashMap<Option<&'static str>, Vec<GateId>>,
+    /// Circuit gate matched by each template gate.
+    mapping: Vec<Option<GateId>>,
+    /// Circuit gates already matched.
+    used: HashSet<GateId>,
+    /// Circuit value matched by each template input.
+    bindings: Vec<Option<ValueId>>,
+    /// Matches found so far.
+    matches: Vec<TemplateMatch>,
+}
+
+impl<G: Gate> Matcher<'_, '_, G>
+where
+    G::Constant: PartialEq,
+{
+    /// Match the template gates from the given position of the order on.
+    fn search(&mut self, depth: usize) -> Result<()> {
+        let Some(&gate) = self.template.order.get(depth) else {
+            let found = self.record()?;
+            self.matches.push(found);
+            return Ok(());
+        };
+        for candidate in self.candidates(gate)? {
+            if self.used.contains(&candidate) {
+                continue;
+            }
+            let mut bound = Vec::new();
+            if self.check(gate, candidate, &mut bound)? {
+                self.mapping[gate] = Some(candidate);
+                self.used.insert(candidate);
+                self.search(depth + 1)?;
+                self.used.remove(&candidate);
+                self.mapping[gate] = None;
+            }
+            for input in bound {
+                self.bindings[input] = None;
+            }
+        }
+        Ok(())
+    }
+
+    /// Circuit gates that may match a template gate.
+    fn candidates(&self, gate: usize) -> Result<Vec<GateId>> {
+        for (port, source) in self.template.sources[gate].iter().enumerate() {
+            if let Source::Gate {
+                gate: producer,
+                port: out,
+            } = *source
+                && let Some(matched) = self.mapping[producer]
+            {
+                let value = self.circuit.gate_op(matched)?.get_outputs()[out];
+                let mut candidates = Vec::new();
+                for (consumer, at) in self.circuit.uses_through_clones(value)? {
+                    if let Consumer::Gate(id) = consumer
+                        && at == port
+                        && !candidates.contains(&id)
+                    {
+                        candidates.push(id);
+                    }
+                }
+                return Ok(candidates);
+            }
+        }
+        for &(consumer, port, _) in &self.template.feeds[gate] {
+            if let Some(matched) = self.mapping[consumer] {
+                let value = self.circuit.gate_op(matched)?.get_inputs()[port];
+                let root = self.circuit.root_value(value)?;
+                return Ok(match self.circuit.value(root)?.get_producer() {
+                    Producer::Gate(id) => Vec::from([id]),
+                    _ => Vec::new(),
+                });
+            }
+        }
+        let name = self.template.gates[gate].1.name();
+        Ok(self.by_name.get(&name).cloned().unwrap_or_default())
+    }
+
+    /// Check a candidate against a template gate and the matches so far,
+    /// binding template inputs seen for the first time.
+    fn check(&mut self, gate: usize, candidate: GateId, bound: &mut Vec<usize>) -> Result<bool> {
+        let template_gate = self.template.gates[gate].1;
+        let op = self.circuit.gate_op(candidate)?;
+        if op.get_gate() != template_gate
+            || op.get_inputs().len() != self.template.sources[gate].len()
+        {
+            return Ok(false);
+        }
+
+        for (source, &value) in self.template.sources[gate].iter().zip(op.get_inputs()) {
+            let root = self.circuit.root_value(value)?;
+            let matches = match source {
+                Source::Gate { gate, port } => match self.mapping[*gate] {
+                    Some(matched) => self.circuit.gate_op(matched)?.get_outputs()[*port] == root,
+                    None => matches!(self.circuit.value(root)?.get_producer(), Producer::Gate(_)),
+                },
+                Source::Input(input) => match self.bindings[*input] {
+                    Some(binding) => binding == root,
+                    None => {
+                        self.bindings[*input] = Some(root);
+                        bound.push(*input);
+                        true
+                    }
+                },
+                Source::Constant(constant) => match self.circuit.value(root)?.get_producer() {
+                    Producer::Constant(id) => self.circuit.constant_op(id)?.get_value() == constant,
+                    _ => false,
+                },
+            };
+            if !matches {
+                return Ok(false);
+            }
+        }
+
+        for &(consumer, port, out) in &self.template.feeds[gate] {
+            if let Some(matched) = self.mapping[consumer] {
+                let value = self.circuit.gate_op(matched)?.get_inputs()[port];
+                if self.circuit.root_value(value)? != op.get_outputs()[out] {
+                    return Ok(false);
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    /// Build the match for the current mapping.
+    fn record(&self) -> Result<TemplateMatch> {
+        let gates: Vec<(GateId, GateId)> = self
+            .template
+            .gates
+            .iter()
+            .zip(&self.mapping)
+            .filter_map(|(&(id, _), matched)| matched.map(|m| (id, m)))
+            .collect();
+
+        let mut outputs = Vec::with_capacity(self.template.outputs.len());
+        for source in &self.template.outputs {
+            outputs.push(match source {
+                Source::Gate { gate, port } => match self.mapping[*gate] {
+                    Some(matched) => Some(self.circuit.gate_op(matched)?.get_outputs()[*port]),
+                    None => None,
+                },
+                Source::Input(input) => self.bindings[*input],
+                Source::Constant(_) => None,
+            });
+        }
+
+        let mut closed = true;
+        'gates: for &(_, matched) in &gates {
+            for &value in self.circuit.gate_op(matched)?.get_outputs() {
+                if outputs.contains(&Some(value)) {
+                    continue;
+                }
+                for (consumer, _) in self.circuit.uses_through_clones(value)? {
+                    let inside = match consumer {
+                        Consumer::Gate(id) => self.used.contains(&id),
+                        Consumer::Drop(_) => true,
+                        Consumer::Clone(_) | Consumer::Output(_) => false,
+                    };
+                    if !inside {
+                        closed = false;
+                        break 'gates;
+                    }
+                }
+            }
+        }
+
+        Ok(TemplateMatch {
+            gates,
+            inputs: self.bindings.clone(),
+            outputs,
+            closed,
+        })
+    }
+}
+
+impl<G: Gate> Circuit<G> {
+    /// Find every occurrence of a template circuit.
+    ///
+    /// Templates without gates match nothing.
+    pub(super) fn find_template(&self, template: &Circuit<G>) -> Result<Vec<TemplateMatch>>
+    where
+        G::Constant: PartialEq,
+    {
+        let template = Template::new(template)?;
+        if template.gates.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut by_name: HashMap<_, Vec<GateId>> = HashMap::new();
+        for (id, gate) in self.all_gates() {
+            by_name.entry(gate.get_gate().name()).or_default().push(id);
+        }
+        let mut matcher = Matcher {
+            circuit: self,
+            template: &template,
+            by_name,
+            mapping: vec![None; template.gates.len()],
+            used: HashSet::new(),
+            bindings: vec![None; template.input_count],
+            matches: Vec::new(),
+        };
+        matcher.search(0)?;
+        Ok(matcher.matches)
+    }
+
+    /// Follow a value through clones to the value they copy.
+    fn root_value(&self, mut value: ValueId) -> Result<ValueId> {
+        while let Producer::Clone(id) = self.value(value)?.get_producer() {
+            value = self.clone_op(id)?.get_input();
+        }
+        Ok(value)
+    }
+
+    /// Consumers of a value and of its clones, with the input port used.
+    fn uses_through_clones(&self, value: ValueId) -> Result<Vec<(Consumer, usize)>> {
+        let mut uses = Vec::new();
+        let mut pending = Vec::from([value]);
+        while let Some(value) = pending.pop() {
+            for usage in self.value(value)?.get_uses() {
+                match usage.consumer {
+                    Consumer::Clone(id) => pending.extend(self.clone_op(id)?.get_outputs()),
+                    consumer => uses.push((consumer, usage.port.index())),
+                }
+            }
+        }
+        Ok(uses)
+    }
+}
@@ -0,0 +1,197 @@
+use super::{Int, bits, fold, inputs, number};
+use crate::{
+    bristol::BoolGate,
+    circuit::Circuit,
+    error::Error,
+    stdlib::adders::{carry_lookahead_add, ripple_carry_add},
+};
+
+/// Two bit adder: inputs on wires 0-3, sum bits on wires 11-13.
+const ADDER: &str = "\
+8 14
+2 2 2
+1 3
+
+2 1 0 2 4 XOR
+2 1 0 2 5 AND
+2 1 1 3 6 XOR
+2 1 6 5 12 XOR
+4 2 1 6 3 5 7 8 MAND
+2 1 7 8 13 XOR
+1 1 4 11 EQW
+1 1 1 10 EQ
+";
+
+/// Check that a circuit adds two numbers of `width` bits.
+fn check_adder(circuit: &Circuit<BoolGate>, width: usize) {
+    for a in 0..1u64 << width {
+        for b in 0..1u64 << width {
+            let mut values = bits(a, width);
+            values.extend(bits(b, width));
+            assert_eq!(number(&fold(circuit, values)), a + b, "{a} + {b}");
+        }
+    }
+}
+
+/// Error raised reading a circuit, by the line it points to.
+fn invalid_line(text: &str) -> usize {
+    match Circuit::<BoolGate>::from_bristol(text) {
+        Err(Error::InvalidBristolLine(line)) => line,
+        Err(error) => panic!("unexpected error: {error}"),
+        Ok(_) => panic!("malformed circuit accepted"),
+    }
+}
+
+#[test]
+fn read_adder() {
+    let circuit = Circuit::<BoolGate>::from_bristol(ADDER).unwrap();
+    assert_eq!(circuit.input_count(), 4);
+    assert_eq!(circuit.output_count(), 3);
+    check_adder(&circuit, 2);
+}
+
+#[test]
+fn read_with_mapped_gates() {
+    let circuit = Circuit::<Int>::from_bristol_with(ADDER, 0, |gate| match gate {
+        BoolGate::And => Int::Mul,
+        BoolGate::Xor | BoolGate::Inv => Int::Add,
+    });
+    // XOR is not addition over integers, but both accept the bit type.
+    assert!(circuit.is_ok());
+}
+
+#[test]
+fn read_write_read_round_trip() {
+    let mut circuit: Circuit<BoolGate> = Circuit::new();
+    let a = inputs(&mut circuit, 3, ());
+    let b = inputs(&mut circuit, 3, ());
+    let (sum, carry) = carry_lookahead_add(&mut circuit, &a, &b, None).unwrap();
+    for value in sum.into_iter().chain([carry]) {
+        circuit.add_output(value);
+    }
+    check_adder(&circuit, 3);
+
+    let text = circuit.to_bristol().unwrap();
+    let read = Circuit::<BoolGate>::from_bristol(&text).unwrap();
+    check_adder(&read, 3);
+    assert_eq!(read.to_bristol().unwrap(), text);
+}
+
+#[test]
+fn write_copies_inputs_constants_and_repeated_outputs() {
+    let mut circuit: Circuit<BoolGate> = Circuit::new();
+    let a = inputs(&mut circuit, 2, ());
+    let b = inputs(&mut circuit, 2, ());
+    let (sum, carry) = ripple_carry_add(&mut circuit, &a, &b, None).unwrap();
+    let one = circuit.add_constant(true, ()).1;
+    for value in [sum[0], sum[1], carry, sum[0], a[1], one] {
+        circuit.add_output(value);
+    }
+
+    let read = Circuit::<BoolGate>::from_bristol(&circuit.to_bristol().unwrap()).unwrap();
+    for x in 0..4 {
+        for y in 0..4 {
+            let mut values = bits(x, 2);
+            values.extend(bits(y, 2));
+            let outputs = fold(&read, values);
+            assert_eq!(number(&outputs[..3]), x + y);
+            assert_eq!(outputs[3], (x + y) & 1 == 1);
+            assert_eq!(outputs[4], x >> 1 == 1);
+            assert!(outputs[5]);
+        }
+    }
+}
+
+#[test]
+fn write_rejects_unclassified_gates() {
+    let mut circuit: Circuit<Int> = Circuit::new();
+    let a = inputs(&mut circuit, 2, 0);
+    let sum = circuit.add_gate(Int::Add, a).unwrap().1[0];
+    circuit.add_output(sum);
+    let result = circuit.to_bristol_with(|_| None, |_| Some(false));
+    let Err(error) = result else {
+        panic!("unclassified gate exported");
+    };
+    assert!(matches!(error.root_cause(), Error::UnsupportedExport(_)));
+    assert!(error.to_string().contains("(add)"), "{error}");
+}
+
+#[test]
+fn write_json_netlist() {
+    let mut circuit: Circuit<BoolGate> = Circuit::new();
+    let [a, b] = inputs(&mut circuit, 2, ())[..] else {
+        unreachable!()
+    };
+    let x = circuit.add_gate(BoolGate::Xor, vec![a, b]).unwrap().1[0];
+    let y = circuit.add_gate(BoolGate::Inv, vec![x]).unwrap().1[0];
+    circuit.add_output(y);
+    let expected = r#"{
+  "wires": 4,
+  "inputs": [0, 1],
+  "outputs": [3],
+  "gates": [
+    {"op": "XOR", "inputs": [0, 1], "outputs": [2]},
+    {"op": "INV", "inputs": [2], "outputs": [3]}
+  ]
+}
+"#;
+    assert_eq!(circuit.to_json_netlist().unwrap(), expected);
+}
+
+#[test]
+fn read_rejects_malformed_headers() {
+    // Too few lines.
+    assert_eq!(invalid_line("1 3\n2 1 1\n"), 3);
+    // Header without a wire count.
+    assert_eq!(invalid_line("1\n2 1 1\n1 1\n2 1 0 1 2 AND\n"), 1);
+    // Value counts not matching the widths listed.
+    assert_eq!(invalid_line("1 3\n3 1 1\n1 1\n2 1 0 1 2 AND\n"), 2);
+    // More inputs and outputs than wires.
+    assert_eq!(invalid_line("1 3\n2 1 1\n1 2\n2 1 0 1 2 AND\n"), 3);
+    // Gate count not matching the body.
+    assert_eq!(invalid_line("2 3\n2 1 1\n1 1\n2 1 0 1 2 AND\n"), 1);
+    // Non numeric header.
+    assert_eq!(invalid_line("1 x\n2 1 1\n1 1\n2 1 0 1 2 AND\n"), 1);
+}
+
+#[test]
+fn read_rejects_overflowing_headers() {
+    let max = usize::MAX;
+    // Input widths overflowing when summed.
+    assert_eq!(
+        invalid_line(&format!("1 3\n2 {max} 1\n1 1\n2 1 0 1 2 AND\n")),
+        2
+    );
+    // Inputs and outputs overflowing when added.
+    assert_eq!(
+        invalid_line(&format!("1 {max}\n1 {max}\n1 1\n2 1 0 1 2 AND\n")),
+        3
+    );
+    // Gate arity overflowing when added.
+    assert_eq!(
+        invalid_line(&format!("1 3\n2 1 1\n1 1\n{max} 1 0 1 2 AND\n")),
+        4
+    );
+}
+
+#[test]
+fn read_rejects_wire_counts_the_body_cannot_reach() {
+    // Allocating this many wires would abort the process.
+    let text = format!("1 {}\n2 1 1\n1 1\n2 1 0 1 2 AND\n", usize::MAX / 2);
+    assert_eq!(invalid_line(&text), 1);
+}
+
+#[test]
+fn read_rejects_malformed_gates() {
+    // Wire read before it is written.
+    assert_eq!(invalid_line(&ADDER.replace("2 1 6 5 12", "2 1 6 9 12")), 8);
+    // Wire out of range.
+    assert_eq!(invalid_line(&ADDER.replace("2 1 0 2 4", "2 1 0 20 4")), 5);
+    // Unknown operation.
+    assert_eq!(invalid_line(&ADDER.replace("5 AND", "5 NAND")), 6);
+    // Constant that is not a bit.
+    assert_eq!(
+        invalid_line(&ADDER.replace("1 1 1 10 EQ", "1 1 2 10 EQ")),
+        12
+    );
+}
@@ -0,0 +1,278 @@
+use super::{CIPHER, Int, fold, inputs};
+use crate::{
+    analyzer::{Analyzer, analyses::topological_order::TopologicalOrder},
+    circuit::{Circuit, Consumer, Operation},
+    error::Error,
+    handles::PortId,
+    module::Module,
+    optimizer::passes::{
+        dead_code_elimination::dead_code_elimination, level_alignment::level_alignment,
+    },
+};
+
+/// Position of an operation in the schedule.
+fn position(circuit: &Circuit<Int>, op: Operation) -> usize {
+    circuit
+        .iter_scheduled()
+        .unwrap()
+        .position(|scheduled| scheduled == op)
+        .unwrap()
+}
+
+#[test]
+fn schedule_orders_producers_first() {
+    let mut circuit: Circuit<Int> = Circuit::new();
+    let x = circuit.add_input(CIPHER).1;
+    let (neg, n) = circuit.add_gate(Int::Neg, vec![x]).unwrap();
+    let (switch, s) = circuit.add_gate(Int::Switch, n).unwrap();
+    circuit.add_output(s[0]);
+    assert!(position(&circuit, Operation::Gate(neg)) < position(&circuit, Operation::Gate(switch)));
+    assert_eq!(fold(&circuit, vec![4]), vec![-4]);
+}
+
+#[test]
+fn schedule_orders_borrows_before_moves() {
+    let mut circuit: Circuit<Int> = Circuit::new();
+    let x = circuit.add_input(CIPHER).1;
+    // The move comes first in handle order.
+    let (neg, n) = circuit.add_gate(Int::Neg, vec![x]).unwrap();
+    let (peek, p) = circuit.add_gate(Int::Peek, vec![x]).unwrap();
+    circuit.add_output(n[0]);
+    circuit.add_output(p[0]);
+    assert!(position(&circuit, Operation::Gate(peek)) < position(&circuit, Operation::Gate(neg)));
+    assert_eq!(fold(&circuit, vec![4]), vec![-4, 4]);
+}
+
+#[test]
+fn topological_order_is_the_schedule() {
+    let mut circuit: Circuit<Int> = Circuit::new();
+    let x = circuit.add_input(CIPHER).1;
+    let n = circuit.add_gate(Int::Neg, vec![x]).unwrap().1[0];
+    let p = circuit.add_gate(Int::Peek, vec![x]).unwrap().1[0];
+    circuit.add_output(n);
+    circuit.add_output(p);
+    let order = Analyzer::new().get::<TopologicalOrder>(&circuit).unwrap();
+    let scheduled: Vec<_> = circuit.iter_scheduled().unwrap().collect();
+    assert_eq!(order.operations(), scheduled);
+}
+
+#[test]
+fn schedule_keeps_borrows_depending_on_the_move() {
+    let mut circuit: Circuit<Int> = Circuit::new();
+    let x = circuit.add_input(CIPHER).1;
+    let (neg, n) = circuit.add_gate(Int::Neg, vec![x]).unwrap();
+    // Borrows `x` after it was moved, which reconciliation must fix.
+    let (offset, o) = circuit.add_gate(Int::Offset, vec![x, n[0]]).unwrap();
+    circuit.add_output(o[0]);
+    assert!(position(&circuit, Operation::Gate(neg)) < position(&circuit, Operation::Gate(offset)));
+}
+
+#[test]
+fn schedule_reports_cycles() {
+    let mut circuit: Circuit<Int> = Circuit::new();
+    let x = circuit.add_input(CIPHER).1;
+    let (a, a_out) = circuit.add_gate(Int::Neg, vec![x]).unwrap();
+    let (b, b_out) = circuit.add_gate(Int::Neg, a_out.clone()).unwrap();
+    circuit.rewire_use(x, b_out[0], Consumer::Gate(a), PortId::new(0));
+    let Err(Error::CycleDetected(cycle)) = circuit.compute_schedule() else {
+        panic!("cycle not detected");
+    };
+    let ops: Vec<_> = cycle.iter().map(|step| step.operation).collect();
+    assert_eq!(ops.len(), 2);
+    assert!(ops.contains(&Operation::Gate(a)) && ops.contains(&Operation::Gate(b)));
+}
+
+#[test]
+fn schedule_reports_longer_cycles_in_dependency_order() {
+    let mut circuit: Circuit<Int> = Circuit::new();
+    let x = circuit.add_input(CIPHER).1;
+    let (a, a_out) = circuit.add_gate(Int::Neg, vec![x]).unwrap();
+    let (b, b_out) = circuit.add_gate(Int::Neg, a_out.clone()).unwrap();
+    let (c, c_out) = circuit.add_gate(Int::Neg, b_out.clone()).unwrap();
+    circuit.rewire_use(x, c_out[0], Consumer::Gate(a), PortId::new(0));
+    let expected = [(a, a_out[0]), (b, b_out[0]), (c, c_out[0])];
+    let check = |error: Error| {
+        let Error::CycleDetected(cycle) = error.root_cause() else {
+            panic!("cycle not detected: {error}");
+        };
+        let steps: Vec<_> = cycle
+            .iter()
+            .map(|step| (step.operation, step.value))
+            .collect();
+        let expected: Vec<_> = expected
+            .iter()
+            .map(|&(gate, value)| (Operation::Gate(gate), value))
+            .collect();
+        assert_eq!(steps, expected);
+        assert!(cycle.iter().all(|step| step.name == Some("neg")));
+        // Each step is described with its name and where it was added.
+        let message = error.to_string();
+        assert_eq!(message.matches("(neg) from").count(), 3, "{message}");
+    };
+
+    let Err(error) = circuit.compute_schedule() else {
+        panic!("cycle not detected");
+    };
+    check(error);
+    let Err(error) = Analyzer::new().get::<TopologicalOrder>(&circuit) else {
+        panic!("cycle not detected");
+    };
+    check(error);
+}
+
+#[test]
+fn errors_describe_operations_by_name_and_label() {
+    let mut circuit: Circuit<Int> = Circuit::new();
+    let x = circuit.add_input(CIPHER).1;
+    let (neg, n) = circuit.add_gate(Int::Neg, vec![x]).unwrap();
+    circuit.tag(Operation::Gate(neg), "negation");
+    // The second operand of a scaling must be a plaintext.
+    let Err(error) = circuit.add_gate(Int::Scale, vec![n[0], n[0]]) else {
+        panic!("type mismatch not detected");
+    };
+    assert!(matches!(
+        error.root_cause(),
+        Error::TypeMismatch { port: 1, .. }
+    ));
+    let message = error.to_string();
+    assert!(message.contains("adding gate scale"), "{message}");
+    assert!(message.contains("(neg) from"), "{message}");
+    assert!(message.contains("\"negation\""), "{message}");
+}
+
+#[test]
+fn check_reports_ownership_problems() {
+    let mut circuit: Circuit<Int> = Circuit::new();
+    let x = circuit.add_input(CIPHER).1;
+    let _unused = circuit.add_input(CIPHER).1;
+    let n = circuit.add_gate(Int::Neg, vec![x]).unwrap().1[0];
+    circuit.add_gate(Int::Neg, vec![x]).unwrap();
+    circuit.add_output(n);
+
+    let problems = circuit.check();
+    assert!(
+        problems
+            .iter()
+            .any(|p| matches!(p.root_cause(), Error::UnusedInput(_)))
+    );
+    assert!(problems.iter().any(
+        |p| matches!(p.root_cause(), Error::OverconsumedValue { value, moves: 2 } if *value == x)
+    ));
+    assert!(
+        problems
+            .iter()
+            .any(|p| matches!(p.root_cause(), Error::UnconsumedValue(_)))
+    );
+}
+
+#[test]
+fn pass_created_gates_inherit_provenance() {
+    let mut circuit: Circuit<Int> = Circuit::new();
+    let [a, b] = inputs(&mut circuit, 2, CIPHER)[..] else {
+        unreachable!()
+    };
+    let product = circuit.add_gate(Int::Mul, vec![a, b]).unwrap().1[0];
+    let c = circuit.add_input(CIPHER).1;
+    let (sum, s) = circuit.add_gate(Int::Add, vec![product, c]).unwrap();
+    circuit.add_output(s[0]);
+    let before = circuit.provenance_map();
+
+    let (circuit, _) = level_alignment(circuit, &mut Analyzer::new()).unwrap();
+    let switches: Vec<_> = circuit
+        .all_gates()
+        .filter(|(_, gate)| *gate.get_gate() == Int::Switch)
+        .map(|(id, _)| Operation::Gate(id))
+        .collect();
+    assert_eq!(switches.len(), 1);
+
+    // The switch comes from the gate it was added for, not from the pass.
+    let switch = switches[0];
+    assert_eq!(
+        circuit.provenance(switch),
+        circuit.provenance(Operation::Gate(sum))
+    );
+    assert_eq!(circuit.provenance_map().len(), before.len());
+    let origins = circuit.origins(switch).unwrap();
+    assert!(
+        origins
+            .locations()
+            .all(|location| location.file().ends_with("tests/circuit.rs"))
+    );
+}
+
+#[test]
+fn instance_numbers_are_not_reused() {
+    let mut body: Circuit<Int> = Circuit::new();
+    let x = body.add_input(CIPHER).1;
+    let n = body.add_gate(Int::Neg, vec![x]).unwrap().1[0];
+    body.add_output(n);
+    let module = Module::new("negate", body);
+
+    let mut circuit: Circuit<Int> = Circuit::new();
+    let [a, b, c] = inputs(&mut circuit, 3, CIPHER)[..] else {
+        unreachable!()
+    };
+    let kept = circuit.instantiate(&module, vec![a]).unwrap();
+    circuit.add_output(kept[0]);
+    // The second instance is unused and removed.
+    circuit.instantiate(&module, vec![b]).unwrap();
+    circuit.add_output(c);
+    let (mut circuit, _) = dead_code_elimination(circuit, &mut Analyzer::new()).unwrap();
+    assert_eq!(circuit.instance_operations(1).count(), 0);
+
+    let d = circuit.add_input(CIPHER).1;
+    let last = circuit.instantiate(&module, vec![d]).unwrap();
+    circuit.add_output(last[0]);
+    assert_eq!(circuit.instance_operations(0).count(), 1);
+    assert_eq!(circuit.instance_operations(1).count(), 0);
+    assert_eq!(circuit.instance_operations(2).count(), 1);
+    // `d` takes the slot of the removed input `b`, before `c`.
+    assert_eq!(fold(&circuit, vec![1, 3, 4]), vec![-1, 4, -3]);
+}
+
+#[test]
+fn inlined_gates_record_the_instantiation_site() {
+    let mut body: Circuit<Int> = Circuit::new();
+    let x = body.add_input(CIPHER).1;
+    let n = body.add_gate(Int::Neg, vec![x]).unwrap().1[0];
+    body.add_output(n);
+    let module = Module::new("negate", body);
+
+    let mut circuit: Circuit<Int> = Circuit::new();
+    let a = circuit.add_input(CIPHER).1;
+    circuit.instantiate(&module, vec![a]).unwrap();
+    let (id, _) = circuit.all_gates().next().unwrap();
+    let origins = circuit.origins(Operation::Gate(id)).unwrap();
+    // One location in the body and one where it was instantiated.
+    assert_eq!(origins.locations().count(), 2);
+    assert!(circuit.provenance(Operation::Gate(id)).is_some());
+}
+
+#[test]
+fn structural_hash_ignores_handles() {
+    let build = |swap: bool| {
+        let mut circuit: Circuit<Int> = Circuit::new();
+        let [a, b] = inputs(&mut circuit, 2, CIPHER)[..] else {
+            unreachable!()
+        };
+        let (first, second) = if swap { (b, a) } else { (a, b) };
+        let x = circuit.add_gate(Int::Neg, vec![first]).unwrap().1[0];
+        let y = circuit.add_gate(Int::Neg, vec![second]).unwrap().1[0];
+        let (na, nb) = if swap { (y, x) } else { (x, y) };
+        circuit.add_output(na);
+        circuit.add_output(nb);
+        circuit
+    };
+    assert_eq!(
+        build(false).structural_hash().unwrap(),
+        build(true).structural_hash().unwrap()
+    );
+
+    let mut changed = build(false);
+    let x = changed.add_input(CIPHER).1;
+    changed.add_drop(x);
+    assert_ne!(
+        changed.structural_hash().unwrap(),
+        build(false).structural_hash().unwrap()
+    );
+}
@@ -0,0 +1,24 @@
+use super::{CIPHER, Int, fold};
+use crate::{
+    analyzer::analyses::wire_allocation::LinearScan,
+    circuit::{Circuit, Operation},
+    compiler::Compiler,
+};
+
+#[test]
+fn compiled_order_is_the_schedule() {
+    let mut circuit: Circuit<Int> = Circuit::new();
+    let x = circuit.add_input(CIPHER).1;
+    // The borrow is added after the move of the same value.
+    let (neg, n) = circuit.add_gate(Int::Neg, vec![x]).unwrap();
+    let (peek, p) = circuit.add_gate(Int::Peek, vec![x]).unwrap();
+    circuit.add_output(n[0]);
+    circuit.add_output(p[0]);
+
+    let program = Compiler::new().compile::<LinearScan>(circuit).unwrap();
+    let scheduled: Vec<_> = program.circuit.iter_scheduled().unwrap().collect();
+    assert_eq!(program.order, scheduled);
+    let position = |op| program.order.iter().position(|&o| o == op).unwrap();
+    assert!(position(Operation::Gate(peek)) < position(Operation::Gate(neg)));
+    assert_eq!(fold(&program.circuit, vec![4]), vec![-4, 4]);
+}
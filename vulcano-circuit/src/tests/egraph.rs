@@ -0,0 +1,80 @@
+use super::{CIPHER, Int, PLAIN, fold};
+use crate::{
+    circuit::Circuit,
+    optimizer::egraph::{EGraph, Pattern, Rewrite, Saturation, SaturationLimits, UnitCost},
+};
+
+/// Rule stating that `lhs` equals `rhs`.
+fn rule(lhs: Pattern<Int>, rhs: Pattern<Int>) -> Rewrite<Int> {
+    Rewrite::new(lhs, rhs).unwrap()
+}
+
+#[test]
+fn saturation_finds_cheaper_forms() {
+    // (x * 1) + (1 * x)
+    let mut circuit: Circuit<Int> = Circuit::new();
+    let x = circuit.add_input(CIPHER).1;
+    let one = circuit.add_constant(1, CIPHER).1;
+    let other_one = circuit.add_constant(1, CIPHER).1;
+    let (_, xs) = circuit.add_clone(x, 2).unwrap();
+    let left = circuit.add_gate(Int::Mul, vec![xs[0], one]).unwrap().1[0];
+    let right = circuit
+        .add_gate(Int::Mul, vec![other_one, xs[1]])
+        .unwrap()
+        .1[0];
+    let sum = circuit.add_gate(Int::Add, vec![left, right]).unwrap().1[0];
+    circuit.add_output(sum);
+
+    let rules = [
+        rule(
+            Pattern::gate(Int::Mul, vec![Pattern::Var(0), Pattern::Constant(1)]),
+            Pattern::Var(0),
+        ),
+        rule(
+            Pattern::gate(Int::Mul, vec![Pattern::Var(0), Pattern::Var(1)]),
+            Pattern::gate(Int::Mul, vec![Pattern::Var(1), Pattern::Var(0)]),
+        ),
+    ];
+    let mut egraph = EGraph::from_circuit(&circuit).unwrap();
+    let saturation = egraph
+        .saturate(&rules, SaturationLimits::default())
+        .unwrap();
+    assert_eq!(saturation, Saturation::Saturated);
+
+    let extracted = egraph.extract(&UnitCost).unwrap();
+    assert_eq!(extracted.gate_count(), 1);
+    for value in [0, 3, -7] {
+        assert_eq!(fold(&extracted, vec![value]), vec![2 * value]);
+    }
+}
+
+#[test]
+fn rules_with_unbound_variables_are_rejected() {
+    assert!(Rewrite::<Int>::new(Pattern::Var(0), Pattern::Var(1)).is_err());
+}
+
+#[test]
+fn rewrites_never_merge_values_of_different_types() {
+    let mut circuit: Circuit<Int> = Circuit::new();
+    let x = circuit.add_input(CIPHER).1;
+    let k = circuit.add_input(PLAIN).1;
+    let scaled = circuit.add_gate(Int::Scale, vec![x, k]).unwrap().1[0];
+    circuit.add_output(scaled);
+
+    // The plaintext operand is cheaper, but not a ciphertext.
+    let rules = [rule(
+        Pattern::gate(Int::Scale, vec![Pattern::Var(0), Pattern::Var(1)]),
+        Pattern::Var(1),
+    )];
+    let mut egraph = EGraph::from_circuit(&circuit).unwrap();
+    let classes = egraph.class_count();
+    let saturation = egraph
+        .saturate(&rules, SaturationLimits::default())
+        .unwrap();
+    assert_eq!(saturation, Saturation::Saturated);
+    assert_eq!(egraph.class_count(), classes);
+
+    let extracted = egraph.extract(&UnitCost).unwrap();
+    assert_eq!(extracted.gate_count(), 1);
+    assert_eq!(fold(&extracted, vec![3, 5]), vec![15]);
+}
@@ -0,0 +1,285 @@
+use crate::{
+    circuit::Circuit,
+    error::{Error, Result},
+    gate::{ArithmeticGates, BooleanGates, Gate, GateIdentities, LeveledGates},
+    handles::{Ownership, ValueId},
+    simulate::ClosureModel,
+};
+
+mod bristol;
+mod circuit;
+mod compiler;
+#[cfg(feature = "egraph")]
+mod egraph;
+mod partition;
+mod passes;
+mod simulate;
+mod stdlib;
+mod tracer;
+
+/// Operand type of ciphertexts.
+const CIPHER: u8 = 0;
+/// Operand type of plaintexts.
+const PLAIN: u8 = 1;
+
+/// Integer gates over ciphertexts and plaintexts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Int {
+    /// Sum of two ciphertexts.
+    Add,
+    /// Product of two ciphertexts.
+    Mul,
+    /// Negation of a ciphertext.
+    Neg,
+    /// Mod switch of a ciphertext, keeping its value.
+    Switch,
+    /// Product of a ciphertext and a borrowed plaintext.
+    Scale,
+    /// Copy of a borrowed ciphertext.
+    Peek,
+    /// Sum of a borrowed ciphertext and a ciphertext.
+    Offset,
+}
+
+impl Gate for Int {
+    fn input_count(&self) -> usize {
+        match self {
+            Int::Add | Int::Mul | Int::Scale | Int::Offset => 2,
+            Int::Neg | Int::Switch | Int::Peek => 1,
+        }
+    }
+
+    fn output_count(&self) -> usize {
+        1
+    }
+
+    type Operand = u8;
+
+    type Constant = i64;
+
+    fn input_type(&self, idx: usize) -> Result<u8> {
+        match (self, idx) {
+            (Int::Scale, 1) => Ok(PLAIN),
+            _ if idx < self.input_count() => Ok(CIPHER),
+            _ => Err(Error::InvalidInputIndex {
+                idx,
+                max: self.input_count(),
+            }),
+        }
+    }
+
+    fn output_type(&self, idx: usize) -> Result<u8> {
+        if idx < self.output_count() {
+            Ok(CIPHER)
+        } else {
+            Err(Error::InvalidOutputIndex {
+                idx,
+                max: self.output_count(),
+            })
+        }
+    }
+
+    fn access_mode(&self, idx: usize) -> Result<Ownership> {
+        match (self, idx, self.input_type(idx)?) {
+            (Int::Peek, _, _) | (Int::Offset, 0, _) | (_, _, PLAIN) => Ok(Ownership::Borrow),
+            _ => Ok(Ownership::Move),
+        }
+    }
+
+    fn fold(&self, inputs: &[i64]) -> Option<Vec<i64>> {
+        let value = match (self, inputs) {
+            (Int::Add | Int::Offset, [a, b]) => a + b,
+            (Int::Mul | Int::Scale, [a, b]) => a * b,
+            (Int::Neg, [a]) => -a,
+            (Int::Switch | Int::Peek, [a]) => *a,
+            _ => return None,
+        };
+        Some(vec![value])
+    }
+
+    fn name(&self) -> Option<&'static str> {
+        Some(match self {
+            Int::Add => "add",
+            Int::Mul => "mul",
+            Int::Neg => "neg",
+            Int::Switch => "switch",
+            Int::Scale => "scale",
+            Int::Peek => "peek",
+            Int::Offset => "offset",
+        })
+    }
+}
+
+impl GateIdentities for Int {
+    fn is_commutative(&self) -> bool {
+        matches!(self, Int::Add | Int::Mul)
+    }
+
+    fn is_associative(&self) -> bool {
+        matches!(self, Int::Add | Int::Mul)
+    }
+
+    fn identity(&self) -> Option<i64> {
+        match self {
+            Int::Add | Int::Offset => Some(0),
+            Int::Mul => Some(1),
+            _ => None,
+        }
+    }
+
+    fn annihilator(&self) -> Option<i64> {
+        match self {
+            Int::Mul => Some(0),
+            _ => None,
+        }
+    }
+}
+
+impl ArithmeticGates for Int {
+    fn add() -> Self {
+        Int::Add
+    }
+
+    fn mul() -> Self {
+        Int::Mul
+    }
+
+    fn neg() -> Option<Self> {
+        Some(Int::Neg)
+    }
+}
+
+impl LeveledGates for Int {
+    fn level_cost(&self) -> usize {
+        match self {
+            Int::Mul | Int::Scale | Int::Switch => 1,
+            _ => 0,
+        }
+    }
+
+    fn mod_switch(operand: u8) -> Option<Self> {
+        (operand == CIPHER).then_some(Int::Switch)
+    }
+}
+
+/// Boolean gates over bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Bit {
+    /// Conjunction of two bits.
+    And,
+    /// Exclusive disjunction of two bits.
+    Xor,
+    /// Negation of a bit.
+    Not,
+}
+
+impl Gate for Bit {
+    fn input_count(&self) -> usize {
+        match self {
+            Bit::And | Bit::Xor => 2,
+            Bit::Not => 1,
+        }
+    }
+
+    fn output_count(&self) -> usize {
+        1
+    }
+
+    type Operand = ();
+
+    type Constant = bool;
+
+    fn input_type(&self, idx: usize) -> Result<()> {
+        if idx < self.input_count() {
+            Ok(())
+        } else {
+            Err(Error::InvalidInputIndex {
+                idx,
+                max: self.input_count(),
+            })
+        }
+    }
+
+    fn output_type(&self, idx: usize) -> Result<()> {
+        if idx < self.output_count() {
+            Ok(())
+        } else {
+            Err(Error::InvalidOutputIndex {
+                idx,
+                max: self.output_count(),
+            })
+        }
+    }
+
+    fn access_mode(&self, idx: usize) -> Result<Ownership> {
+        self.input_type(idx).map(|_| Ownership::Move)
+    }
+
+    fn fold(&self, inputs: &[bool]) -> Option<Vec<bool>> {
+        let value = match (self, inputs) {
+            (Bit::And, [a, b]) => a & b,
+            (Bit::Xor, [a, b]) => a ^ b,
+            (Bit::Not, [a]) => !a,
+            _ => return None,
+        };
+        Some(vec![value])
+    }
+}
+
+impl BooleanGates for Bit {
+    fn and() -> Self {
+        Bit::And
+    }
+
+    fn xor() -> Self {
+        Bit::Xor
+    }
+
+    fn not() -> Self {
+        Bit::Not
+    }
+}
+
+/// Evaluate a circuit, computing gates with `gate` and constants with
+/// `constant`.
+fn evaluate<G, V, F, C>(circuit: &Circuit<G>, inputs: Vec<V>, gate: F, constant: C) -> Vec<V>
+where
+    G: Gate,
+    V: Clone,
+    F: Fn(&G, &[V]) -> Vec<V>,
+    C: Fn(&G::Constant) -> V,
+{
+    let mut model = ClosureModel::new(
+        |g: &G, inputs: &[V]| Ok(gate(g, inputs)),
+        |value: &G::Constant| Ok(constant(value)),
+    );
+    circuit.simulate(&mut model, inputs).unwrap()
+}
+
+/// Evaluate a circuit by folding its gates and constants.
+fn fold<G: Gate>(circuit: &Circuit<G>, inputs: Vec<G::Constant>) -> Vec<G::Constant> {
+    evaluate(
+        circuit,
+        inputs,
+        |gate, inputs| gate.fold(inputs).expect("test gates fold"),
+        Clone::clone,
+    )
+}
+
+/// Bits of a number, least significant first.
+fn bits(value: u64, width: usize) -> Vec<bool> {
+    (0..width).map(|idx| (value >> idx) & 1 == 1).collect()
+}
+
+/// Number from its bits, least significant first.
+fn number(bits: &[bool]) -> u64 {
+    bits.iter()
+        .enumerate()
+        .map(|(idx, &bit)| u64::from(bit) << idx)
+        .sum()
+}
+
+/// Add `count` inputs of the same type.
+fn inputs<G: Gate>(circuit: &mut Circuit<G>, count: usize, ty: G::Operand) -> Vec<ValueId> {
+    (0..count).map(|_| circuit.add_input(ty).1).collect()
+}
@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+
+use super::{CIPHER, Int, fold, inputs};
+use crate::{
+    analyzer::{
+        Analyzer,
+        analyses::{
+            balanced_partitioning::BalancedPartitioning,
+            partitioning::{PartitionMap, Partitioning},
+        },
+    },
+    circuit::{Circuit, Operation},
+    handles::ValueId,
+    optimizer::passes::reconcile_ownership::reconcile_ownership,
+};
+
+/// Partitioning of operations given by hand.
+struct ByHand(HashMap<Operation, usize>);
+
+impl PartitionMap for ByHand {
+    fn partition(&self, op: Operation) -> Option<usize> {
+        self.0.get(&op).copied()
+    }
+
+    fn partition_count(&self) -> usize {
+        self.0.values().max().map_or(0, |&max| max + 1)
+    }
+}
+
+/// Evaluate a circuit partition by partition, passing values between them.
+fn fold_partitioned<P: PartitionMap>(
+    circuit: &Circuit<Int>,
+    partitioning: &P,
+    inputs: Vec<i64>,
+) -> Vec<i64> {
+    let mut values: HashMap<ValueId, i64> = circuit
+        .all_inputs()
+        .map(|(_, input)| input.get_output())
+        .zip(inputs)
+        .collect();
+    for partition in circuit.split_partitions(partitioning).unwrap() {
+        let (local, _) = reconcile_ownership(partition.circuit, &mut Analyzer::new()).unwrap();
+        let imported = partition
+            .imports
+            .iter()
+            .map(|(_, original)| values[original])
+            .collect();
+        let exported = fold(&local, imported);
+        for ((_, original), value) in partition.exports.iter().zip(exported) {
+            values.insert(*original, value);
+        }
+    }
+    circuit
+        .all_outputs()
+        .map(|(_, output)| values[&output.get_input()])
+        .collect()
+}
+
+#[test]
+fn values_are_exported_by_their_producer_only() {
+    let mut circuit: Circuit<Int> = Circuit::new();
+    let [x, y] = inputs(&mut circuit, 2, CIPHER)[..] else {
+        unreachable!()
+    };
+    // `a` is borrowed by the second partition and moved by the third.
+    let (first, a) = circuit.add_gate(Int::Neg, vec![x]).unwrap();
+    let (second, b) = circuit.add_gate(Int::Offset, vec![a[0], y]).unwrap();
+    let (third, c) = circuit.add_gate(Int::Neg, vec![a[0]]).unwrap();
+    circuit.add_output(b[0]);
+    circuit.add_output(c[0]);
+    let partitioning = ByHand(HashMap::from([
+        (Operation::Gate(first), 0),
+        (Operation::Gate(second), 1),
+        (Operation::Gate(third), 2),
+    ]));
+
+    let partitions = circuit.split_partitions(&partitioning).unwrap();
+    assert_eq!(partitions.len(), 3);
+    let exported: Vec<Vec<ValueId>> = partitions
+        .iter()
+        .map(|p| p.exports.iter().map(|&(_, value)| value).collect())
+        .collect();
+    assert_eq!(exported[0], vec![a[0]]);
+    assert_eq!(exported[1], vec![b[0]]);
+    assert_eq!(exported[2], vec![c[0]]);
+    assert_eq!(
+        fold_partitioned(&circuit, &partitioning, vec![2, 3]),
+        vec![1, 2]
+    );
+}
+
+#[test]
+fn partitions_compute_the_circuit() {
+    let mut circuit: Circuit<Int> = Circuit::new();
+    let [x, y, z] = inputs(&mut circuit, 3, CIPHER)[..] else {
+        unreachable!()
+    };
+    let (_, xs) = circuit.add_clone(x, 2).unwrap();
+    let product = circuit.add_gate(Int::Mul, vec![xs[0], y]).unwrap().1[0];
+    let sum = circuit.add_gate(Int::Add, vec![product, z]).unwrap().1[0];
+    let negated = circuit.add_gate(Int::Neg, vec![xs[1]]).unwrap().1[0];
+    let total = circuit.add_gate(Int::Offset, vec![sum, negated]).unwrap().1[0];
+    circuit.add_output(total);
+    circuit.add_output(sum);
+    let expected = fold(&circuit, vec![2, 3, 4]);
+    assert_eq!(expected, vec![8, 10]);
+
+    let partitioning = Analyzer::new().get::<Partitioning<2>>(&circuit).unwrap();
+    assert!(partitioning.partition_count() > 1);
+    let partitions = circuit.split_partitions(partitioning.as_ref()).unwrap();
+    for (index, partition) in partitions.iter().enumerate() {
+        // Every export is produced inside its partition.
+        for (_, value) in &partition.exports {
+            let producer = Operation::from(circuit.value(*value).unwrap().get_producer());
+            assert_eq!(partitioning.partition(producer), Some(index));
+        }
+    }
+    assert_eq!(
+        fold_partitioned(&circuit, partitioning.as_ref(), vec![2, 3, 4]),
+        expected
+    );
+}
+
+#[test]
+fn balanced_partitioning_splits_connected_circuits() {
+    // Two independent chains joined at the end.
+    let mut circuit: Circuit<Int> = Circuit::new();
+    let [x, y] = inputs(&mut circuit, 2, CIPHER)[..] else {
+        unreachable!()
+    };
+    let (mut left, mut right) = (x, y);
+    for _ in 0..8 {
+        left = circuit.add_gate(Int::Neg, vec![left]).unwrap().1[0];
+        right = circuit.add_gate(Int::Switch, vec![right]).unwrap().1[0];
+    }
+    let sum = circuit.add_gate(Int::Add, vec![left, right]).unwrap().1[0];
+    circuit.add_output(sum);
+
+    let mut analyzer = Analyzer::new();
+    analyzer.set_transfer_cost(|_| 2.0);
+    let partitioning = analyzer.get::<BalancedPartitioning<2>>(&circuit).unwrap();
+    assert_eq!(partitioning.partition_count(), 2);
+    let (a, b) = (partitioning.cost(0), partitioning.cost(1));
+    assert_eq!(a + b, 17.0);
+    assert!((a - b).abs() <= 3.0, "unbalanced: {a} and {b}");
+    // Only the result of one chain is sent to the other party.
+    assert_eq!(partitioning.cut_size(), 1);
+    assert_eq!(partitioning.messages()[0].to.len(), 1);
+    assert_eq!(partitioning.transfer_cost(), 2.0);
+    assert_eq!(
+        fold_partitioned(&circuit, partitioning.as_ref(), vec![5, 7]),
+        vec![12]
+    );
+}
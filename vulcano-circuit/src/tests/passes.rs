@@ -0,0 +1,383 @@
+use std::{any::TypeId, time::Duration};
+
+use super::{CIPHER, Int, PLAIN, evaluate, fold, inputs};
+use crate::{
+    analyzer::{Analyzer, analyses::topological_order::TopologicalOrder},
+    circuit::{Circuit, Producer},
+    error::{Error, Result},
+    gate::{Gate, RotationGates},
+    handles::{Ownership, ValueId},
+    optimizer::{
+        Optimizer,
+        budget::OptimizeBudget,
+        passes::{
+            algebraic_simplification::algebraic_simplification, constant_folding::constant_folding,
+            dead_code_elimination::dead_code_elimination, level_alignment::level_alignment,
+            reconcile_ownership::reconcile_ownership, rotation_scheduling::rotation_scheduling,
+        },
+    },
+};
+
+/// Gates over vectors of slots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Slot {
+    /// Rotation of a ciphertext by an offset.
+    Rot(i64),
+    /// Slot-wise sum of two ciphertexts.
+    Add,
+    /// Slot-wise product of two ciphertexts.
+    Mul,
+    /// Slot-wise product of a plaintext and a ciphertext.
+    Scale,
+}
+
+impl Gate for Slot {
+    fn input_count(&self) -> usize {
+        match self {
+            Slot::Rot(_) => 1,
+            _ => 2,
+        }
+    }
+
+    fn output_count(&self) -> usize {
+        1
+    }
+
+    type Operand = u8;
+
+    type Constant = Vec<i64>;
+
+    fn input_type(&self, idx: usize) -> Result<u8> {
+        match (self, idx) {
+            (Slot::Scale, 0) => Ok(PLAIN),
+            _ if idx < self.input_count() => Ok(CIPHER),
+            _ => Err(Error::InvalidInputIndex {
+                idx,
+                max: self.input_count(),
+            }),
+        }
+    }
+
+    fn output_type(&self, idx: usize) -> Result<u8> {
+        if idx < self.output_count() {
+            Ok(CIPHER)
+        } else {
+            Err(Error::InvalidOutputIndex {
+                idx,
+                max: self.output_count(),
+            })
+        }
+    }
+
+    fn access_mode(&self, idx: usize) -> Result<Ownership> {
+        self.input_type(idx).map(|_| Ownership::Borrow)
+    }
+}
+
+impl RotationGates for Slot {
+    fn rotation_offset(&self) -> Option<i64> {
+        match self {
+            Slot::Rot(offset) => Some(*offset),
+            _ => None,
+        }
+    }
+
+    fn with_rotation_offset(&self, offset: i64) -> Self {
+        Slot::Rot(offset)
+    }
+
+    fn is_addition(&self) -> bool {
+        *self == Slot::Add
+    }
+
+    fn is_slotwise_product(&self) -> bool {
+        matches!(self, Slot::Mul | Slot::Scale)
+    }
+}
+
+/// Evaluate a circuit of slot gates.
+fn evaluate_slots(circuit: &Circuit<Slot>, inputs: Vec<Vec<i64>>) -> Vec<Vec<i64>> {
+    evaluate(
+        circuit,
+        inputs,
+        |gate: &Slot, inputs: &[Vec<i64>]| {
+            let slots = |f: fn(i64, i64) -> i64| {
+                inputs[0]
+                    .iter()
+                    .zip(&inputs[1])
+                    .map(|(&a, &b)| f(a, b))
+                    .collect()
+            };
+            vec![match gate {
+                Slot::Rot(offset) => {
+                    let n = inputs[0].len() as i64;
+                    (0..n)
+                        .map(|idx| inputs[0][(idx + offset).rem_euclid(n) as usize])
+                        .collect()
+                }
+                Slot::Add => slots(|a, b| a + b),
+                Slot::Mul | Slot::Scale => slots(|a, b| a * b),
+            }]
+        },
+        Clone::clone,
+    )
+}
+
+/// Matrix-vector product by diagonals: the sum over `k` of the k-th
+/// diagonal times the input rotated by `k`.
+fn diagonal_product(slots: i64, product: Slot, diagonal_type: u8) -> Circuit<Slot> {
+    let mut circuit = Circuit::new();
+    let x = circuit.add_input(CIPHER).1;
+    let mut terms = Vec::new();
+    for k in 0..slots {
+        let rotated = circuit.add_gate(Slot::Rot(k), vec![x]).unwrap().1[0];
+        let diagonal = (0..slots).map(|idx| idx * 10 + k).collect();
+        let diagonal = circuit.add_constant(diagonal, diagonal_type).1;
+        terms.push(
+            circuit
+                .add_gate(product, vec![diagonal, rotated])
+                .unwrap()
+                .1[0],
+        );
+    }
+    let mut sum = terms[0];
+    for &term in &terms[1..] {
+        sum = circuit.add_gate(Slot::Add, vec![sum, term]).unwrap().1[0];
+    }
+    circuit.add_output(sum);
+    circuit
+}
+
+/// Number of rotations of values other than constants.
+fn runtime_rotations(circuit: &Circuit<Slot>) -> usize {
+    circuit
+        .all_gates()
+        .filter(|(_, gate)| matches!(gate.get_gate(), Slot::Rot(_)))
+        .filter(|(_, gate)| {
+            let producer = circuit.value(gate.get_inputs()[0]).unwrap().get_producer();
+            !matches!(producer, Producer::Constant(_))
+        })
+        .count()
+}
+
+#[test]
+fn rotation_scheduling_uses_baby_step_giant_step() {
+    let slots = 16;
+    let circuit = diagonal_product(slots, Slot::Mul, CIPHER);
+    let input: Vec<i64> = (0..slots).map(|idx| idx * idx - 3).collect();
+    let expected = evaluate_slots(&circuit, vec![input.clone()]);
+    assert_eq!(runtime_rotations(&circuit), 16);
+
+    let (circuit, _) = rotation_scheduling(circuit, &mut Analyzer::new()).unwrap();
+    assert!(runtime_rotations(&circuit) <= 8);
+    assert_eq!(evaluate_slots(&circuit, vec![input]), expected);
+}
+
+#[test]
+fn rotation_scheduling_keeps_products_it_cannot_rotate() {
+    // Plaintext diagonals cannot go through the ciphertext rotation.
+    let slots = 16;
+    let circuit = diagonal_product(slots, Slot::Scale, PLAIN);
+    let input: Vec<i64> = (0..slots).collect();
+    let expected = evaluate_slots(&circuit, vec![input.clone()]);
+
+    let (circuit, _) = rotation_scheduling(circuit, &mut Analyzer::new()).unwrap();
+    assert_eq!(evaluate_slots(&circuit, vec![input]), expected);
+}
+
+#[test]
+fn rotation_scheduling_keeps_products_with_other_uses() {
+    let slots = 16;
+    let mut circuit = diagonal_product(slots, Slot::Mul, CIPHER);
+    // One product is also an output of its own.
+    let (_, product) = circuit
+        .all_gates()
+        .find(|(_, gate)| *gate.get_gate() == Slot::Mul)
+        .unwrap();
+    let shared = product.get_outputs()[0];
+    circuit.add_output(shared);
+    let input: Vec<i64> = (0..slots).collect();
+    let expected = evaluate_slots(&circuit, vec![input.clone()]);
+
+    let (circuit, _) = rotation_scheduling(circuit, &mut Analyzer::new()).unwrap();
+    assert!(circuit.value(shared).is_ok());
+    assert_eq!(evaluate_slots(&circuit, vec![input]), expected);
+}
+
+#[test]
+fn rotation_scheduling_stops_when_the_budget_is_exhausted() {
+    let circuit = diagonal_product(16, Slot::Mul, CIPHER);
+    let mut analyzer = Analyzer::new();
+    analyzer.get::<TopologicalOrder>(&circuit).unwrap();
+    analyzer.set_budget(OptimizeBudget::new(Some(Duration::ZERO), None));
+    analyzer.start_budget(&circuit);
+
+    let (circuit, preserved) = rotation_scheduling(circuit, &mut analyzer).unwrap();
+    assert_eq!(runtime_rotations(&circuit), 16);
+    // Nothing changed, so cached analyses stay valid.
+    assert!(preserved.contains(&TypeId::of::<TopologicalOrder>()));
+}
+
+/// Get the constant producing a value.
+fn constant(circuit: &Circuit<Int>, value: ValueId) -> Option<i64> {
+    match circuit.value(value).unwrap().get_producer() {
+        Producer::Constant(id) => Some(*circuit.constant_op(id).unwrap().get_value()),
+        _ => None,
+    }
+}
+
+#[test]
+fn algebraic_simplification_applies_identities() {
+    let mut circuit: Circuit<Int> = Circuit::new();
+    let [x, y] = inputs(&mut circuit, 2, CIPHER)[..] else {
+        unreachable!()
+    };
+    let one = circuit.add_constant(1, CIPHER).1;
+    let two = circuit.add_constant(2, CIPHER).1;
+    let zero = circuit.add_constant(0, CIPHER).1;
+    let other_one = circuit.add_constant(1, CIPHER).1;
+    // ((1 + x) + 2) * 1 becomes x + 3.
+    let a = circuit.add_gate(Int::Add, vec![one, x]).unwrap().1[0];
+    let b = circuit.add_gate(Int::Add, vec![a, two]).unwrap().1[0];
+    let m = circuit.add_gate(Int::Mul, vec![other_one, b]).unwrap().1[0];
+    circuit.add_output(m);
+    // y * 0 becomes 0, leaving `y` unused.
+    let z = circuit.add_gate(Int::Mul, vec![y, zero]).unwrap().1[0];
+    circuit.add_output(z);
+
+    let mut optimizer = Optimizer::new();
+    optimizer.add_pass(algebraic_simplification);
+    optimizer.add_pass(reconcile_ownership);
+    optimizer.add_pass(dead_code_elimination);
+    let circuit = optimizer.optimize(circuit).unwrap();
+
+    assert_eq!(circuit.gate_count(), 1);
+    let (_, gate) = circuit.all_gates().next().unwrap();
+    assert_eq!(*gate.get_gate(), Int::Add);
+    assert_eq!(gate.get_inputs()[0], x);
+    assert_eq!(constant(&circuit, gate.get_inputs()[1]), Some(3));
+    assert_eq!(circuit.input_count(), 1);
+    assert_eq!(fold(&circuit, vec![5]), vec![8, 0]);
+    assert!(circuit.check().is_empty());
+}
+
+#[test]
+fn algebraic_simplification_keeps_borrowed_operands() {
+    let mut circuit: Circuit<Int> = Circuit::new();
+    let x = circuit.add_input(CIPHER).1;
+    let zero = circuit.add_constant(0, CIPHER).1;
+    // `x` is only borrowed by the identity and moved by the negation.
+    let offset = circuit.add_gate(Int::Offset, vec![x, zero]).unwrap().1[0];
+    let negated = circuit.add_gate(Int::Neg, vec![x]).unwrap().1[0];
+    circuit.add_output(offset);
+    circuit.add_output(negated);
+    assert!(circuit.check().is_empty());
+
+    let (circuit, _) = algebraic_simplification(circuit, &mut Analyzer::new()).unwrap();
+    assert_eq!(circuit.gate_count(), 2);
+    assert!(circuit.check().is_empty());
+    assert_eq!(fold(&circuit, vec![4]), vec![4, -4]);
+}
+
+#[test]
+fn algebraic_simplification_drops_annihilated_operands() {
+    let mut circuit: Circuit<Int> = Circuit::new();
+    let x = circuit.add_input(CIPHER).1;
+    let zero = circuit.add_constant(0, CIPHER).1;
+    let product = circuit.add_gate(Int::Mul, vec![x, zero]).unwrap().1[0];
+    circuit.add_output(product);
+
+    let (circuit, _) = algebraic_simplification(circuit, &mut Analyzer::new()).unwrap();
+    assert_eq!(circuit.gate_count(), 0);
+    // `x` was moved by the product, so it is dropped rather than leaked.
+    assert_eq!(circuit.all_drops().count(), 1);
+    assert!(circuit.check().is_empty());
+    assert_eq!(fold(&circuit, vec![5]), vec![0]);
+}
+
+#[test]
+fn constant_folding_evaluates_constant_gates() {
+    let mut circuit: Circuit<Int> = Circuit::new();
+    let x = circuit.add_input(CIPHER).1;
+    let two = circuit.add_constant(2, CIPHER).1;
+    let three = circuit.add_constant(3, CIPHER).1;
+    let five = circuit.add_gate(Int::Add, vec![two, three]).unwrap().1[0];
+    let negated = circuit.add_gate(Int::Neg, vec![five]).unwrap().1[0];
+    let sum = circuit.add_gate(Int::Add, vec![negated, x]).unwrap().1[0];
+    circuit.add_output(sum);
+
+    let mut optimizer = Optimizer::new();
+    optimizer.add_pass(constant_folding);
+    optimizer.add_pass(dead_code_elimination);
+    let circuit = optimizer.optimize(circuit).unwrap();
+    assert_eq!(circuit.gate_count(), 1);
+    assert_eq!(circuit.constant_count(), 1);
+    assert_eq!(fold(&circuit, vec![1]), vec![-4]);
+}
+
+#[test]
+fn level_alignment_switches_operands_to_one_level() {
+    let mut circuit: Circuit<Int> = Circuit::new();
+    let [a, b, c] = inputs(&mut circuit, 3, CIPHER)[..] else {
+        unreachable!()
+    };
+    let (_, bs) = circuit.add_clone(b, 2).unwrap();
+    let product = circuit.add_gate(Int::Mul, vec![a, bs[0]]).unwrap().1[0];
+    let (_, copies) = circuit.add_clone(product, 2).unwrap();
+    let square = circuit
+        .add_gate(Int::Mul, vec![copies[0], bs[1]])
+        .unwrap()
+        .1[0];
+    let high = circuit.add_gate(Int::Add, vec![square, c]).unwrap().1[0];
+    let low = circuit.add_gate(Int::Offset, vec![c, copies[1]]).unwrap().1[0];
+    circuit.add_output(high);
+    circuit.add_output(low);
+    let expected = fold(&circuit, vec![2, 3, 4]);
+
+    let levels = circuit.levels().unwrap();
+    assert_eq!(levels.max_level(), 2);
+    assert_eq!(levels.level(square), Some(2));
+    assert_eq!(levels.level(c), Some(0));
+
+    let mut analyzer = Analyzer::new();
+    let (circuit, _) = level_alignment(circuit, &mut analyzer).unwrap();
+    let levels = circuit.levels().unwrap();
+    for (_, gate) in circuit.all_gates() {
+        let operands: Vec<_> = gate
+            .get_inputs()
+            .iter()
+            .filter_map(|&value| levels.level(value))
+            .collect();
+        assert!(operands.windows(2).all(|pair| pair[0] == pair[1]));
+    }
+    assert_eq!(levels.max_level(), 2);
+    // `b` is switched once for the second product. `c` is switched twice
+    // for the first sum and once for the second, sharing the first switch.
+    let switches = circuit
+        .all_gates()
+        .filter(|(_, gate)| *gate.get_gate() == Int::Switch)
+        .count();
+    assert_eq!(switches, 3);
+    assert_eq!(fold(&circuit, vec![2, 3, 4]), expected);
+
+    let (circuit, _) = reconcile_ownership(circuit, &mut analyzer).unwrap();
+    assert!(circuit.check().is_empty());
+}
+
+#[test]
+fn level_alignment_skips_types_without_levels() {
+    let mut circuit: Circuit<Int> = Circuit::new();
+    let [a, b] = inputs(&mut circuit, 2, CIPHER)[..] else {
+        unreachable!()
+    };
+    let plain = circuit.add_input(PLAIN).1;
+    let product = circuit.add_gate(Int::Mul, vec![a, b]).unwrap().1[0];
+    let scaled = circuit
+        .add_gate(Int::Scale, vec![product, plain])
+        .unwrap()
+        .1[0];
+    circuit.add_output(scaled);
+
+    let (circuit, _) = level_alignment(circuit, &mut Analyzer::new()).unwrap();
+    assert_eq!(circuit.gate_count(), 2);
+}
@@ -0,0 +1,73 @@
+use super::{CIPHER, Int, fold, inputs};
+use crate::{
+    circuit::{Circuit, Operation},
+    gate::Gate,
+    simulate::ClosureModel,
+};
+
+/// Circuit computing `(x * y) + (x * y)`.
+fn doubled_product() -> (Circuit<Int>, Operation) {
+    let mut circuit: Circuit<Int> = Circuit::new();
+    let [x, y] = inputs(&mut circuit, 2, CIPHER)[..] else {
+        unreachable!()
+    };
+    let (mul, product) = circuit.add_gate(Int::Mul, vec![x, y]).unwrap();
+    let (_, copies) = circuit.add_clone(product[0], 2).unwrap();
+    let sum = circuit.add_gate(Int::Add, copies).unwrap().1[0];
+    circuit.add_output(sum);
+    (circuit, Operation::Gate(mul))
+}
+
+#[test]
+fn simulation_follows_the_schedule() {
+    let (circuit, _) = doubled_product();
+    assert_eq!(fold(&circuit, vec![3, 4]), vec![24]);
+
+    let mut model = ClosureModel::new(
+        |gate: &Int, inputs: &[i64]| Ok(gate.fold(inputs).unwrap()),
+        |value: &i64| Ok(*value),
+    );
+    assert!(circuit.simulate(&mut model, vec![3]).is_err());
+}
+
+#[test]
+fn co_simulation_reports_the_first_divergence() {
+    let (circuit, mul) = doubled_product();
+    let mut reference = ClosureModel::new(
+        |gate: &Int, inputs: &[i64]| Ok(gate.fold(inputs).unwrap()),
+        |value: &i64| Ok(*value),
+    );
+    // A kernel adding where it should multiply.
+    let mut faulty = ClosureModel::new(
+        |gate: &Int, inputs: &[i64]| match gate {
+            Int::Mul => Ok(vec![inputs[0] + inputs[1]]),
+            _ => Ok(gate.fold(inputs).unwrap()),
+        },
+        |value: &i64| Ok(*value),
+    );
+
+    let divergence = circuit
+        .co_simulate(
+            &mut faulty,
+            &mut reference,
+            vec![3, 4],
+            vec![3, 4],
+            |a, b| a == b,
+        )
+        .unwrap()
+        .unwrap();
+    assert_eq!(divergence.operation, mul);
+    assert_eq!((divergence.actual, divergence.expected), (7, 12));
+
+    // Both agree where addition and multiplication coincide.
+    let agreement = circuit
+        .co_simulate(
+            &mut faulty,
+            &mut reference,
+            vec![2, 2],
+            vec![2, 2],
+            |a, b| a == b,
+        )
+        .unwrap();
+    assert!(agreement.is_none());
+}
@@ -0,0 +1,252 @@
+use super::{Bit, bits, fold, inputs, number};
+use crate::{
+    circuit::Circuit,
+    handles::ValueId,
+    stdlib::{
+        adders::{carry_lookahead_add, ripple_borrow_subtract, ripple_carry_add},
+        compare::{equal, greater_equal, greater_than, less_equal, less_than, not_equal},
+        integer::EncryptedInt,
+        multipliers::{shift_add_multiply, truncated_multiply},
+        prefix::{prefix_scan, reduce},
+        select::{mux, select},
+    },
+};
+
+/// Circuit over two operands of `width` bits and a carry bit.
+fn operands(width: usize) -> (Circuit<Bit>, Vec<ValueId>, Vec<ValueId>, ValueId) {
+    let mut circuit = Circuit::new();
+    let a = inputs(&mut circuit, width, ());
+    let b = inputs(&mut circuit, width, ());
+    let carry = circuit.add_input(()).1;
+    (circuit, a, b, carry)
+}
+
+/// Mark a bit vector as outputs.
+fn output_all(circuit: &mut Circuit<Bit>, values: &[ValueId]) {
+    for &value in values {
+        circuit.add_output(value);
+    }
+}
+
+/// Evaluate a circuit built by [`operands`] on every pair of operands and
+/// carry, checking each output against `expected`.
+fn check_all<F>(circuit: &Circuit<Bit>, width: usize, mut expected: F)
+where
+    F: FnMut(u64, u64, bool) -> u64,
+{
+    for a in 0..1u64 << width {
+        for b in 0..1u64 << width {
+            for carry in [false, true] {
+                let mut values = bits(a, width);
+                values.extend(bits(b, width));
+                values.push(carry);
+                let outputs = fold(circuit, values);
+                assert_eq!(
+                    number(&outputs),
+                    expected(a, b, carry),
+                    "a = {a}, b = {b}, carry = {carry}"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn select_picks_the_indexed_value() {
+    let mut circuit = Circuit::new();
+    let selectors = inputs(&mut circuit, 2, ());
+    let values = inputs(&mut circuit, 4, ());
+    let picked = select(&mut circuit, &selectors, &values).unwrap();
+    let muxed = mux(&mut circuit, selectors[0], values[1], values[0]).unwrap();
+    output_all(&mut circuit, &[picked, muxed]);
+    for index in 0..4 {
+        for table in 0..16u64 {
+            let mut inputs = bits(index, 2);
+            inputs.extend(bits(table, 4));
+            let outputs = fold(&circuit, inputs);
+            assert_eq!(outputs[0], (table >> index) & 1 == 1);
+            assert_eq!(outputs[1], (table >> (index & 1)) & 1 == 1);
+        }
+    }
+}
+
+#[test]
+fn select_rejects_wrong_tree_sizes() {
+    let (mut circuit, a, b, _) = operands(3);
+    assert!(select(&mut circuit, &a[..1], &b).is_err());
+    assert!(select(&mut circuit, &a[..2], &b).is_err());
+}
+
+#[test]
+fn prefix_networks_combine_every_prefix() {
+    for width in 1..=6 {
+        let mut circuit = Circuit::new();
+        let values = inputs(&mut circuit, width, ());
+        let parities = prefix_scan(&mut circuit, Bit::Xor, &values).unwrap();
+        let all = reduce(&mut circuit, Bit::And, &values).unwrap();
+        output_all(&mut circuit, &parities);
+        circuit.add_output(all);
+        for value in 0..1u64 << width {
+            let outputs = fold(&circuit, bits(value, width));
+            for (idx, &parity) in outputs[..width].iter().enumerate() {
+                let prefix = value & ((2 << idx) - 1);
+                assert_eq!(parity, prefix.count_ones() % 2 == 1);
+            }
+            assert_eq!(outputs[width], value == (1 << width) - 1);
+        }
+    }
+}
+
+#[test]
+fn reduce_rejects_no_values() {
+    let mut circuit: Circuit<Bit> = Circuit::new();
+    assert!(reduce(&mut circuit, Bit::And, &[]).is_err());
+}
+
+#[test]
+fn ripple_carry_add_matches_addition() {
+    for width in 1..=4 {
+        let (mut circuit, a, b, carry) = operands(width);
+        let (sum, carry_out) = ripple_carry_add(&mut circuit, &a, &b, Some(carry)).unwrap();
+        assert_eq!(sum.len(), width);
+        output_all(&mut circuit, &sum);
+        circuit.add_output(carry_out);
+        check_all(&circuit, width, |a, b, carry| a + b + u64::from(carry));
+    }
+}
+
+#[test]
+fn carry_lookahead_add_matches_addition() {
+    for width in 1..=5 {
+        let (mut circuit, a, b, carry) = operands(width);
+        let (sum, carry_out) = carry_lookahead_add(&mut circuit, &a, &b, Some(carry)).unwrap();
+        output_all(&mut circuit, &sum);
+        circuit.add_output(carry_out);
+        check_all(&circuit, width, |a, b, carry| a + b + u64::from(carry));
+
+        let (mut circuit, a, b, _) = operands(width);
+        let (sum, carry_out) = carry_lookahead_add(&mut circuit, &a, &b, None).unwrap();
+        output_all(&mut circuit, &sum);
+        circuit.add_output(carry_out);
+        check_all(&circuit, width, |a, b, _| a + b);
+    }
+}
+
+#[test]
+fn adders_reject_mismatched_widths() {
+    let (mut circuit, a, b, _) = operands(3);
+    assert!(ripple_carry_add(&mut circuit, &a, &b[..2], None).is_err());
+    assert!(carry_lookahead_add(&mut circuit, &a[..1], &b, None).is_err());
+    assert!(ripple_borrow_subtract(&mut circuit, &a, &b[..2]).is_err());
+}
+
+#[test]
+fn ripple_borrow_subtract_matches_subtraction() {
+    for width in 1..=4 {
+        let (mut circuit, a, b, _) = operands(width);
+        let (difference, borrow) = ripple_borrow_subtract(&mut circuit, &a, &b).unwrap();
+        assert_eq!(difference.len(), width);
+        output_all(&mut circuit, &difference);
+        circuit.add_output(borrow);
+        // The borrow out is the sign of the difference, one bit past it.
+        let modulus = 1u64 << width;
+        check_all(&circuit, width, |a, b, _| {
+            a.wrapping_sub(b) % modulus + u64::from(a < b) * modulus
+        });
+    }
+}
+
+#[test]
+fn shift_add_multiply_matches_multiplication() {
+    for width in 1..=4 {
+        let (mut circuit, a, b, _) = operands(width);
+        let product = shift_add_multiply(&mut circuit, &a, &b).unwrap();
+        assert!(product.len() <= 2 * width);
+        output_all(&mut circuit, &product);
+        check_all(&circuit, width, |a, b, _| a * b);
+    }
+}
+
+#[test]
+fn truncated_multiply_matches_wrapping_multiplication() {
+    for width in 1..=4 {
+        let (mut circuit, a, b, _) = operands(width);
+        let product = truncated_multiply(&mut circuit, &a, &b).unwrap();
+        assert_eq!(product.len(), width);
+        output_all(&mut circuit, &product);
+        check_all(&circuit, width, |a, b, _| (a * b) % (1 << width));
+    }
+}
+
+#[test]
+fn multipliers_reject_empty_or_mismatched_operands() {
+    let (mut circuit, a, b, _) = operands(2);
+    assert!(shift_add_multiply(&mut circuit, &a, &[]).is_err());
+    assert!(truncated_multiply(&mut circuit, &a, &b[..1]).is_err());
+}
+
+#[test]
+fn comparators_match_integer_comparison() {
+    for width in 1..=3 {
+        let (mut circuit, a, b, _) = operands(width);
+        let results = [
+            equal(&mut circuit, &a, &b).unwrap(),
+            not_equal(&mut circuit, &a, &b).unwrap(),
+            less_than(&mut circuit, &a, &b).unwrap(),
+            greater_than(&mut circuit, &a, &b).unwrap(),
+            less_equal(&mut circuit, &a, &b).unwrap(),
+            greater_equal(&mut circuit, &a, &b).unwrap(),
+        ];
+        output_all(&mut circuit, &results);
+        check_all(&circuit, width, |a, b, _| {
+            let flags = [a == b, a != b, a < b, a > b, a <= b, a >= b];
+            number(&flags)
+        });
+    }
+}
+
+#[test]
+fn comparators_reject_mismatched_widths() {
+    let (mut circuit, a, b, _) = operands(2);
+    assert!(equal(&mut circuit, &a, &b[..1]).is_err());
+    assert!(less_than(&mut circuit, &a[..1], &b).is_err());
+}
+
+#[test]
+fn encrypted_integers_wrap_around() {
+    let mut circuit: Circuit<Bit> = Circuit::new();
+    let a = EncryptedInt::<3>::input(&mut circuit, ());
+    let b = EncryptedInt::<3>::input(&mut circuit, ());
+    let sel = circuit.add_input(()).1;
+    let sum = a.add(&mut circuit, &b).unwrap();
+    let difference = a.sub(&mut circuit, &b).unwrap();
+    let product = a.mul(&mut circuit, &b).unwrap();
+    let selected = EncryptedInt::select(&mut circuit, sel, &a, &b).unwrap();
+    for result in [sum, difference, product, selected] {
+        result.output(&mut circuit);
+    }
+    let flags = [
+        a.eq(&mut circuit, &b).unwrap(),
+        a.lt(&mut circuit, &b).unwrap(),
+        a.le(&mut circuit, &b).unwrap(),
+        a.gt(&mut circuit, &b).unwrap(),
+        a.ge(&mut circuit, &b).unwrap(),
+    ];
+    output_all(&mut circuit, &flags);
+
+    check_all(&circuit, 3, |a, b, sel| {
+        let results = [
+            (a + b) % 8,
+            a.wrapping_sub(b) % 8,
+            (a * b) % 8,
+            if sel { a } else { b },
+        ];
+        let flags = [a == b, a < b, a <= b, a > b, a >= b];
+        results
+            .iter()
+            .enumerate()
+            .map(|(idx, result)| result << (3 * idx))
+            .sum::<u64>()
+            + (number(&flags) << 12)
+    });
+}
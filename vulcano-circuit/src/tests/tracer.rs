@@ -0,0 +1,111 @@
+use super::{CIPHER, Int, PLAIN, fold};
+use crate::{circuit::Operation, error::Error, tracer::Tracer};
+
+#[test]
+fn operators_record_gates() {
+    let tracer: Tracer<Int> = Tracer::new();
+    let x = tracer.input(CIPHER);
+    let y = tracer.input(CIPHER);
+    let z = &x * &y + -x.clone();
+    tracer.output(&z);
+    let circuit = tracer.finish().unwrap();
+
+    assert_eq!(circuit.gate_count(), 3);
+    assert_eq!(fold(&circuit, vec![3, 5]), vec![12]);
+    let (mul, _) = circuit
+        .all_gates()
+        .find(|(_, gate)| *gate.get_gate() == Int::Mul)
+        .unwrap();
+    let location = circuit
+        .origins(Operation::Gate(mul))
+        .unwrap()
+        .locations()
+        .next()
+        .unwrap();
+    assert!(location.file().ends_with("tests/tracer.rs"));
+}
+
+#[test]
+fn first_error_is_kept_and_poisons_values() {
+    let tracer: Tracer<Int> = Tracer::new();
+    let x = tracer.input(CIPHER);
+    let plain = tracer.input(PLAIN);
+    let poisoned = (&x + &plain) * x.clone();
+    assert!(poisoned.value().is_none());
+    // Int has no subtraction, but the first error wins.
+    let _ = &x - &x;
+    let Err(error) = tracer.finish() else {
+        panic!("error not reported");
+    };
+    assert!(matches!(error.root_cause(), Error::TypeMismatch { .. }));
+}
+
+#[test]
+fn missing_operator_gates_are_reported() {
+    let tracer: Tracer<Int> = Tracer::new();
+    let x = tracer.input(CIPHER);
+    let _ = &x - &x;
+    let Err(error) = tracer.finish() else {
+        panic!("error not reported");
+    };
+    assert!(matches!(error, Error::MissingOperatorGate("sub")));
+}
+
+#[test]
+fn values_of_another_tracer_are_rejected() {
+    let first: Tracer<Int> = Tracer::new();
+    let second: Tracer<Int> = Tracer::new();
+    let x = first.input(CIPHER);
+    let y = second.input(CIPHER);
+
+    let mixed = &x + &y;
+    assert!(mixed.value().is_none());
+    assert!(matches!(
+        first.finish(),
+        Err(Error::ForeignTracedValue("add"))
+    ));
+
+    let applied = second.apply(Int::Add, &[&y, &x]);
+    assert!(applied[0].value().is_none());
+    assert!(matches!(
+        second.finish(),
+        Err(Error::ForeignTracedValue("apply"))
+    ));
+}
+
+#[test]
+fn outputs_of_another_tracer_are_rejected() {
+    let first: Tracer<Int> = Tracer::new();
+    let second: Tracer<Int> = Tracer::new();
+    let x = first.input(CIPHER);
+    second.output(&x);
+    assert!(matches!(
+        second.finish(),
+        Err(Error::ForeignTracedValue("output"))
+    ));
+}
+
+#[test]
+fn repeat_reuses_loop_invariant_gates() {
+    let tracer: Tracer<Int> = Tracer::new();
+    let x = tracer.input(CIPHER);
+    let k = tracer.input(CIPHER);
+    let out = tracer.repeat(4, vec![x], |_, carried| {
+        let invariant = &k * &k;
+        vec![&carried[0] + &invariant]
+    });
+    tracer.output(&out[0]);
+    let circuit = tracer.finish().unwrap();
+
+    // One product and four additions.
+    assert_eq!(circuit.gate_count(), 5);
+    assert_eq!(fold(&circuit, vec![1, 3]), vec![37]);
+}
+
+#[test]
+fn repeat_rejects_changing_carried_values() {
+    let tracer: Tracer<Int> = Tracer::new();
+    let x = tracer.input(CIPHER);
+    let _ = tracer.repeat(2, vec![x], |_, _| Vec::new());
+    assert!(tracer.finish().is_err());
+}
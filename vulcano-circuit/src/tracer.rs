@@ -0,0 +1,354 @@
+//! Expression tracer
+//!
+//! This module provides an operator-overloading front-end to build circuits.
+//! Values handed out by a [`Tracer`] implement `Add`, `Sub`, `Mul` and `Neg`,
+//! and every arithmetic expression on them is recorded as gates.
+//!
+//! Operators cannot fail, so the first error is kept by the tracer and
+//! returned when finishing. Values derived from a failed operation are
+//! poisoned and record nothing. Values may be used any number of times;
+//! ownership is left to reconciliation. Values of different tracers cannot
+//! be mixed: doing so is an error.
+//!
+//! Loops are unrolled with [`Tracer::repeat`]. Gates recorded inside a loop
+//! whose inputs do not depend on the carried values are loop-invariant: they
+//! are recorded once and reused by later iterations.
+
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    ops::{Add, Mul, Neg, Sub},
+    rc::Rc,
+};
+
+use crate::{
+    circuit::Circuit,
+    error::{Error, Result},
+    gate::ArithmeticGates,
+    handles::ValueId,
+};
+
+/// State shared by a tracer and its values.
+struct State<G: ArithmeticGates> {
+    /// Circuit being recorded.
+    circuit: Circuit<G>,
+    /// First error raised while recording.
+    error: Option<Error>,
+    /// Loops being unrolled, innermost last.
+    loops: Vec<Loop<G>>,
+}
+
+/// Bookkeeping of a loop being unrolled.
+struct Loop<G: ArithmeticGates> {
+    /// Values depending on the carried values.
+    variant: HashSet<ValueId>,
+    /// Invariant gates already recorded, by inputs.
+    invariant: HashMap<Vec<ValueId>, Vec<(G, Vec<ValueId>)>>,
+}
+
+/// Records arithmetic expressions into a circuit.
+pub(super) struct Tracer<G: ArithmeticGates> {
+    /// State shared with traced values.
+    state: Rc<RefCell<State<G>>>,
+}
+
+/// A value of a traced expression.
+pub(super) struct Traced<G: ArithmeticGates> {
+    /// The recorded value, or `None` if poisoned by an earlier error.
+    value: Option<ValueId>,
+    /// State of the tracer that created the value.
+    state: Rc<RefCell<State<G>>>,
+}
+
+impl<G: ArithmeticGates> Tracer<G> {
+    /// Create a tracer recording into an empty circuit.
+    pub(super) fn new() -> Self {
+        Self {
+            state: Rc::new(RefCell::new(State {
+                circuit: Circuit::new(),
+                error: None,
+                loops: Vec::new(),
+            })),
+        }
+    }
+
+    /// Wrap a value recorded by this tracer.
+    fn traced(&self, value: Option<ValueId>) -> Traced<G> {
+        Traced {
+            value,
+            state: self.state.clone(),
+        }
+    }
+
+    /// Create a circuit input.
+    pub(super) fn input(&self, value_type: G::Operand) -> Traced<G> {
+        let (_, value) = self.state.borrow_mut().circuit.add_input(value_type);
+        self.traced(Some(value))
+    }
+
+    /// Create a constant.
+    pub(super) fn constant(&self, value: G::Constant, value_type: G::Operand) -> Traced<G> {
+        let (_, value) = self
+            .state
+            .borrow_mut()
+            .circuit
+            .add_constant(value, value_type);
+        self.traced(Some(value))
+    }
+
+    /// Record an arbitrary gate applied to traced values.
+    #[track_caller]
+    pub(super) fn apply(&self, gate: G, inputs: &[&Traced<G>]) -> Vec<Traced<G>> {
+        let values: Option<Vec<ValueId>> = same_tracer(&self.state, "apply", inputs)
+            .then(|| inputs.iter().map(|t| t.value).collect())
+            .flatten();
+        let outputs = record(&self.state, "apply", Some(gate), values);
+        match outputs {
+            Some(outputs) => outputs.into_iter().map(|v| self.traced(Some(v))).collect(),
+            None => (0..gate.output_count())
+                .map(|_| self.traced(None))
+                .collect(),
+        }
+    }
+
+    /// Unroll a loop body `n` times.
+    ///
+    /// The body receives the tracer and the carried values, and returns the
+    /// carried values for the next iteration, which must be as many.
+    /// Returns the carried values after the last iteration.
+    pub(super) fn repeat<F>(&self, n: usize, carried: Vec<Traced<G>>, mut body: F) -> Vec<Traced<G>>
+    where
+        F: FnMut(&Tracer<G>, Vec<Traced<G>>) -> Vec<Traced<G>>,
+    {
+        let expected = carried.len();
+        self.state.borrow_mut().loops.push(Loop {
+            variant: carried.iter().filter_map(|t| t.value).collect(),
+            invariant: HashMap::new(),
+        });
+
+        let mut carried = carried;
+        for _ in 0..n {
+            carried = body(self, carried);
+            if carried.len() != expected {
+                let mut state = self.state.borrow_mut();
+                if state.error.is_none() {
+                    state.error = Some(
+                        Error::WrongInputCount {
+                            expected,
+                            got: carried.len(),
+                        }
+                        .with_context("carrying values between loop iterations"),
+                    );
+                }
+                carried = (0..expected).map(|_| self.traced(None)).collect();
+                break;
+            }
+            // Carried values depend on the previous iteration.
+            let mut state = self.state.borrow_mut();
+            if let Some(current) = state.loops.last_mut() {
+                current
+                    .variant
+                    .extend(carried.iter().filter_map(|t| t.value));
+            }
+        }
+
+        self.state.borrow_mut().loops.pop();
+        carried
+    }
+
+    /// Mark a traced value as a circuit output.
+    pub(super) fn output(&self, traced: &Traced<G>) {
+        if same_tracer(&self.state, "output", &[traced])
+            && let Some(value) = traced.value
+        {
+            self.state.borrow_mut().circuit.add_output(value);
+        }
+    }
+
+    /// Stop recording and return the circuit, or the first error raised.
+    ///
+    /// Traced values left alive are detached from the returned circuit.
+    pub(super) fn finish(self) -> Result<Circuit<G>> {
+        let mut state = self.state.borrow_mut();
+        if let Some(error) = state.error.take() {
+            return Err(error);
+        }
+        Ok(std::mem::take(&mut state.circuit))
+    }
+}
+
+impl<G: ArithmeticGates> Default for Tracer<G> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<G: ArithmeticGates> Traced<G> {
+    /// Get the recorded value, or `None` if poisoned.
+    pub(super) fn value(&self) -> Option<ValueId> {
+        self.value
+    }
+}
+
+impl<G: ArithmeticGates> Clone for Traced<G> {
+    fn clone(&self) -> Self {
+        Self {
+            value: self.value,
+            state: self.state.clone(),
+        }
+    }
+}
+
+/// Check that all operands belong to the tracer owning `state`, keeping an
+/// error in the state otherwise.
+fn same_tracer<G: ArithmeticGates>(
+    state: &Rc<RefCell<State<G>>>,
+    operator: &'static str,
+    operands: &[&Traced<G>],
+) -> bool {
+    if operands.iter().all(|t| Rc::ptr_eq(&t.state, state)) {
+        return true;
+    }
+    let mut state = state.borrow_mut();
+    if state.error.is_none() {
+        state.error = Some(Error::ForeignTracedValue(operator));
+    }
+    false
+}
+
+/// Record a single-output gate, keeping the first error in the state.
+#[track_caller]
+fn record<G: ArithmeticGates>(
+    state: &RefCell<State<G>>,
+    operator: &'static str,
+    gate: Option<G>,
+    inputs: Option<Vec<ValueId>>,
+) -> Option<Vec<ValueId>> {
+    let mut state = state.borrow_mut();
+    if state.error.is_some() {
+        return None;
+    }
+    let inputs = inputs?;
+
+    // Reuse loop-invariant gates recorded by earlier iterations.
+    let invariant = state
+        .loops
+        .last()
+        .is_some_and(|l| inputs.iter().all(|v| !l.variant.contains(v)));
+    if invariant
+        && let Some(gate) = gate
+        && let Some(outputs) = state.loops.last().and_then(|l| {
+            l.invariant
+                .get(&inputs)?
+                .iter()
+                .find(|(g, _)| *g == gate)
+                .map(|(_, o)| o.clone())
+        })
+    {
+        return Some(outputs);
+    }
+
+    let Some(gate) = gate else {
+        state.error = Some(Error::MissingOperatorGate(operator));
+        return None;
+    };
+    match state.circuit.add_gate(gate, inputs.clone()) {
+        Ok((_, outputs)) => {
+            // Outputs vary within every loop where some input varies.
+            for l in &mut state.loops {
+                if inputs.iter().any(|v| l.variant.contains(v)) {
+                    l.variant.extend(outputs.iter().copied());
+                }
+            }
+            if invariant && let Some(l) = state.loops.last_mut() {
+                l.invariant
+                    .entry(inputs)
+                    .or_default()
+                    .push((gate, outputs.clone()));
+            }
+            Some(outputs)
+        }
+        Err(error) => {
+            state.error = Some(error);
+            None
+        }
+    }
+}
+
+/// Record a single-output operator on traced values.
+#[track_caller]
+fn operator<G: ArithmeticGates>(
+    name: &'static str,
+    gate: Option<G>,
+    operands: &[&Traced<G>],
+) -> Traced<G> {
+    let state = operands[0].state.clone();
+    let inputs = same_tracer(&state, name, operands)
+        .then(|| operands.iter().map(|t| t.value).collect())
+        .flatten();
+    let value = record(&state, name, gate, inputs).and_then(|outputs| outputs.first().copied());
+    Traced { value, state }
+}
+
+/// Implement a binary operator for owned and borrowed traced values.
+macro_rules! binary_operator {
+    ($trait:ident, $method:ident, $gate:expr) => {
+        impl<G: ArithmeticGates> $trait<&Traced<G>> for &Traced<G> {
+            type Output = Traced<G>;
+
+            #[track_caller]
+            fn $method(self, rhs: &Traced<G>) -> Traced<G> {
+                operator(stringify!($method), $gate, &[self, rhs])
+            }
+        }
+
+        impl<G: ArithmeticGates> $trait<Traced<G>> for Traced<G> {
+            type Output = Traced<G>;
+
+            #[track_caller]
+            fn $method(self, rhs: Traced<G>) -> Traced<G> {
+                operator(stringify!($method), $gate, &[&self, &rhs])
+            }
+        }
+
+        impl<G: ArithmeticGates> $trait<&Traced<G>> for Traced<G> {
+            type Output = Traced<G>;
+
+            #[track_caller]
+            fn $method(self, rhs: &Traced<G>) -> Traced<G> {
+                operator(stringify!($method), $gate, &[&self, rhs])
+            }
+        }
+
+        impl<G: ArithmeticGates> $trait<Traced<G>> for &Traced<G> {
+            type Output = Traced<G>;
+
+            #[track_caller]
+            fn $method(self, rhs: Traced<G>) -> Traced<G> {
+                operator(stringify!($method), $gate, &[self, &rhs])
+            }
+        }
+    };
+}
+
+binary_operator!(Add, add, Some(G::add()));
+binary_operator!(Sub, sub, G::sub());
+binary_operator!(Mul, mul, Some(G::mul()));
+
+impl<G: ArithmeticGates> Neg for &Traced<G> {
+    type Output = Traced<G>;
+
+    #[track_caller]
+    fn neg(self) -> Traced<G> {
+        operator("neg", G::neg(), &[self])
+    }
+}
+
+impl<G: ArithmeticGates> Neg for Traced<G> {
+    type Output = Traced<G>;
+
+    #[track_caller]
+    fn neg(self) -> Traced<G> {
+        operator("neg", G::neg(), &[&self])
+    }
+}